@@ -0,0 +1,330 @@
+use serde_json::Value as Json;
+
+use ut181a::{Measurement, Mode, Range, Unit, UnitExp, Value};
+
+use measurement::{is_overload, main_value, mode, range};
+use ranges::{mode_ranges, range_index};
+
+/// Coverage factor of expanded uncertainty.
+pub(crate) const COVERAGE: u32 = 2;
+
+/// Accuracy of range: ±(`percent` of reading + `counts` of last digit).
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Spec {
+    pub(crate) percent: f64,
+    pub(crate) counts: u32,
+}
+
+const fn spec(percent: f64, counts: u32) -> Spec {
+    Spec { percent, counts }
+}
+
+// Accuracy specifications of UT181A user manual (23 ± 5 °C, RH < 75%, 1 year).
+// AC is specified for 45 Hz ... 1 kHz. Specs are in order of `ranges::mode_ranges`.
+
+const MVDC: &[Spec] = &[spec(0.05, 20), spec(0.025, 5)];
+const MVAC: &[Spec] = &[spec(0.6, 60), spec(0.3, 40)];
+const VDC: &[Spec] = &[
+    spec(0.025, 5),
+    spec(0.025, 5),
+    spec(0.025, 5),
+    spec(0.05, 5),
+];
+const VAC: &[Spec] = &[spec(0.3, 40), spec(0.3, 40), spec(0.3, 40), spec(0.5, 40)];
+const OHM: &[Spec] = &[
+    spec(0.05, 10),
+    spec(0.05, 5),
+    spec(0.05, 5),
+    spec(0.05, 5),
+    spec(0.3, 10),
+    spec(1.5, 10),
+];
+const SIEMENS: &[Spec] = &[spec(1.0, 10)];
+const DIODE: &[Spec] = &[spec(0.1, 10)];
+const FARAD: &[Spec] = &[
+    spec(1.5, 20),
+    spec(1.0, 10),
+    spec(1.0, 10),
+    spec(1.0, 10),
+    spec(1.0, 10),
+    spec(1.0, 10),
+    spec(2.0, 10),
+    spec(5.0, 20),
+];
+const HZ: &[Spec] = &[spec(0.01, 5); 7];
+const UADC: &[Spec] = &[spec(0.1, 15), spec(0.1, 15)];
+const UAAC: &[Spec] = &[spec(0.6, 20), spec(0.6, 20)];
+const MADC: &[Spec] = &[spec(0.15, 15), spec(0.15, 15)];
+const MAAC: &[Spec] = &[spec(0.6, 20), spec(0.6, 20)];
+const ADC: &[Spec] = &[spec(0.5, 10)];
+const AAC: &[Spec] = &[spec(1.0, 20)];
+const TEMP_C: &[Spec] = &[spec(1.0, 10)];
+const TEMP_F: &[Spec] = &[spec(1.0, 18)];
+
+/// Temperature modes have no ranges, their resolution is 0.1 degree.
+const TEMP_RESOLUTION: f64 = 0.1;
+
+/// Range specifications of mode (`None` if accuracy isn't specified,
+/// e.g. for peak, low pass, dB, AC+DC, duty cycle and pulse width).
+///
+/// Ranges of AC/Hz modes are voltage/current ranges, their main value is frequency.
+pub(crate) fn mode_specs(m: Mode) -> Option<&'static [Spec]> {
+    match m {
+        Mode::mVDC_Normal | Mode::mVDC_Normal_Rel => Some(MVDC),
+        Mode::mVAC_Normal | Mode::mVAC_Normal_Rel => Some(MVAC),
+        Mode::VDC_Normal | Mode::VDC_Normal_Rel => Some(VDC),
+        Mode::VAC_Normal | Mode::VAC_Normal_Rel => Some(VAC),
+        Mode::Resistance | Mode::Resistance_Rel => Some(OHM),
+        Mode::Admittance | Mode::Admittance_Rel => Some(SIEMENS),
+        Mode::Diode_Normal | Mode::Diode_Alarm => Some(DIODE),
+        Mode::Capacitance | Mode::Capacitance_Rel => Some(FARAD),
+        Mode::Frequency | Mode::Frequency_Rel => Some(HZ),
+        Mode::uADC_Normal | Mode::uADC_Normal_Rel => Some(UADC),
+        Mode::uAAC_Normal | Mode::uAAC_Normal_Rel => Some(UAAC),
+        Mode::mADC_Normal | Mode::mADC_Normal_Rel => Some(MADC),
+        Mode::mAAC_Normal | Mode::mAAC_Normal_Rel => Some(MAAC),
+        Mode::ADC_Normal | Mode::ADC_Normal_Rel => Some(ADC),
+        Mode::AAC_Normal | Mode::AAC_Normal_Rel => Some(AAC),
+        Mode::TempC_T1_T2
+        | Mode::TempC_T1_T2_Rel
+        | Mode::TempC_T2_T1
+        | Mode::TempC_T2_T1_Rel
+        | Mode::TempC_T1_T2_Diff
+        | Mode::TempC_T2_T1_Diff => Some(TEMP_C),
+        Mode::TempF_T1_T2
+        | Mode::TempF_T1_T2_Rel
+        | Mode::TempF_T2_T1
+        | Mode::TempF_T2_T1_Rel
+        | Mode::TempF_T1_T2_Diff
+        | Mode::TempF_T2_T1_Diff => Some(TEMP_F),
+        _ => None,
+    }
+}
+
+/// Mode measuring in unit (records keep unit only).
+fn unit_mode(u: UnitExp) -> Option<Mode> {
+    match (u.unit, u.exponent) {
+        (Unit::VDC, -3) => Some(Mode::mVDC_Normal),
+        (Unit::VDC, _) => Some(Mode::VDC_Normal),
+        (Unit::VAC, -3) => Some(Mode::mVAC_Normal),
+        (Unit::VAC, _) => Some(Mode::VAC_Normal),
+        (Unit::ADC, -6) => Some(Mode::uADC_Normal),
+        (Unit::ADC, -3) => Some(Mode::mADC_Normal),
+        (Unit::ADC, _) => Some(Mode::ADC_Normal),
+        (Unit::AAC, -6) => Some(Mode::uAAC_Normal),
+        (Unit::AAC, -3) => Some(Mode::mAAC_Normal),
+        (Unit::AAC, _) => Some(Mode::AAC_Normal),
+        (Unit::Ohm, _) => Some(Mode::Resistance),
+        (Unit::S, _) => Some(Mode::Admittance),
+        (Unit::F, _) => Some(Mode::Capacitance),
+        (Unit::Hz, _) => Some(Mode::Frequency),
+        (Unit::Celsius, _) => Some(Mode::TempC_T1_T2),
+        (Unit::Fahrenheit, _) => Some(Mode::TempF_T1_T2),
+        _ => None,
+    }
+}
+
+/// Value of one count of displayed value (in base units).
+fn resolution(v: &Value) -> Option<f64> {
+    v.precision
+        .map(|prc| 10f64.powi(v.unit.exponent as i32 - prc as i32))
+}
+
+/// Finds spec and count value (in base units) of mode range.
+///
+/// Range is found by resolution of displayed value if `step` is unknown (`Auto`).
+fn find_spec(m: Mode, step: Range, v: &Value) -> Option<(Spec, f64)> {
+    let specs = mode_specs(m)?;
+    let ranges = mode_ranges(m);
+    if ranges.is_empty() {
+        return specs.first().map(|spec| (*spec, TEMP_RESOLUTION));
+    }
+    let i = match range_index(m, step) {
+        Some(i) => i,
+        None => {
+            let res = resolution(v)?;
+            ranges
+                .iter()
+                .position(|r| (r.base_resolution() / res - 1.0).abs() < 0.01)?
+        }
+    };
+    Some((*specs.get(i)?, ranges[i].base_resolution()))
+}
+
+/// Expanded uncertainty (k=2) of `v` in units of `v`.
+///
+/// Specification limits are treated as rectangular distribution:
+/// `U = k * (percent * |reading| + counts * resolution) / sqrt(3)`.
+fn expanded(spec: Spec, count: f64, reading: &Value, v: &Value) -> Option<f64> {
+    if is_overload(v) || is_overload(reading) {
+        return None;
+    }
+    let scale = 10f64.powi(v.unit.exponent as i32);
+    let count = resolution(v).unwrap_or(count) / scale;
+    let limit = spec.percent / 100.0
+        * f64::from(reading.value).abs()
+        * 10f64.powi((reading.unit.exponent - v.unit.exponent) as i32)
+        + f64::from(spec.counts) * count;
+    Some(f64::from(COVERAGE) * limit / 3f64.sqrt())
+}
+
+/// Expanded uncertainty of main value of measurement (in its units).
+///
+/// Percent of reading of relative measurement is taken from measured (not relative) value.
+pub(crate) fn uncertainty(m: &Measurement) -> Option<f64> {
+    let v = main_value(m);
+    let (spec, count) = if mode_specs(mode(m)).is_some() {
+        find_spec(mode(m), range(m), v)?
+    } else if v.unit.unit == Unit::Hz {
+        find_spec(Mode::Frequency, Range::Auto, v)?
+    } else {
+        return None;
+    };
+    let reading = match *m {
+        Measurement::Relative(ref meas) => &meas.measurement,
+        _ => v,
+    };
+    expanded(spec, count, reading, v)
+}
+
+/// Expanded uncertainty of record sample (range is found by resolution).
+pub(crate) fn value_uncertainty(v: &Value) -> Option<f64> {
+    let (spec, count) = find_spec(unit_mode(v.unit)?, Range::Auto, v)?;
+    expanded(spec, count, v, v)
+}
+
+/// Formats uncertainty with 2 significant digits.
+pub(crate) fn format_uncertainty(u: f64) -> String {
+    if u <= 0.0 {
+        return "0".into();
+    }
+    let decimals = (1.0 - u.log10().floor()).max(0.0) as usize;
+    format!("{:.*}", decimals, u)
+}
+
+/// Formats value as `value ± U unit (k=2)` (value only if uncertainty is unknown).
+pub(crate) fn format_with_uncertainty(v: &Value, u: Option<f64>) -> String {
+    match u {
+        Some(u) => format!(
+            "{} ± {} {} (k={})",
+            v,
+            format_uncertainty(u),
+            v.unit,
+            COVERAGE
+        ),
+        None => v.to_string(),
+    }
+}
+
+/// `{"value": U, "unit": ..., "k": 2}` or `null`.
+pub(crate) fn uncertainty_json(v: &Value, u: Option<f64>) -> Json {
+    match u.and_then(|u| format_uncertainty(u).parse::<f64>().ok()) {
+        Some(u) => json!({
+            "value": u,
+            "unit": v.unit.to_string(),
+            "k": COVERAGE,
+        }),
+        None => Json::Null,
+    }
+}
+
+/// Adds `uncertainty` of main value to measurement JSON.
+pub(crate) fn add_uncertainty_json(j: &mut Json, m: &Measurement) {
+    if let Json::Object(ref mut obj) = *j {
+        obj.insert(
+            "uncertainty".into(),
+            uncertainty_json(main_value(m), uncertainty(m)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use modes::MODES;
+    use ut181a::{NormalMeasurement, RelativeMeasurement};
+
+    fn value(value: f32, unit: Unit, exponent: isize, precision: usize) -> Value {
+        Value {
+            overload_neg: false,
+            overload_pos: false,
+            value,
+            precision: Some(precision),
+            unit: UnitExp { unit, exponent },
+        }
+    }
+
+    fn vdc(main: Value, range: Range) -> Measurement {
+        Measurement::Normal(NormalMeasurement {
+            mode: Mode::VDC_Normal,
+            is_holded: false,
+            is_auto_range: false,
+            range,
+            main,
+            aux1: None,
+            aux2: None,
+            fast: None,
+        })
+    }
+
+    fn assert_close(u: Option<f64>, expected: f64) {
+        let u = u.expect("uncertainty is unknown");
+        assert!((u - expected).abs() < 1e-6, "{} != {}", u, expected);
+    }
+
+    #[test]
+    fn specs_match_ranges() {
+        for &(name, m) in MODES {
+            let ranges = mode_ranges(m);
+            if let (Some(specs), false) = (mode_specs(m), ranges.is_empty()) {
+                assert_eq!(specs.len(), ranges.len(), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_range() {
+        // 6 V range: ±(0.025% + 5 counts of 0.0001 V)
+        let m = vdc(value(5.0, Unit::VDC, 0, 4), Range::Step1);
+        assert_close(
+            uncertainty(&m),
+            2.0 * (0.00025 * 5.0 + 0.0005) / 3f64.sqrt(),
+        );
+    }
+
+    #[test]
+    fn range_by_resolution() {
+        // 60 kOhm range (1 Ohm resolution): ±(0.05% + 5 counts)
+        let v = value(47.0, Unit::Ohm, 3, 3);
+        assert_close(
+            value_uncertainty(&v),
+            2.0 * (0.0005 * 47.0 + 0.005) / 3f64.sqrt(),
+        );
+    }
+
+    #[test]
+    fn relative_uses_measured_value() {
+        let m = Measurement::Relative(RelativeMeasurement {
+            mode: Mode::VDC_Normal_Rel,
+            is_holded: false,
+            is_auto_range: false,
+            range: Range::Step1,
+            relative: value(0.01, Unit::VDC, 0, 4),
+            reference: value(5.0, Unit::VDC, 0, 4),
+            measurement: value(5.01, Unit::VDC, 0, 4),
+            fast: None,
+        });
+        assert_close(
+            uncertainty(&m),
+            2.0 * (0.00025 * 5.01 + 0.0005) / 3f64.sqrt(),
+        );
+    }
+
+    #[test]
+    fn overload_is_unknown() {
+        let mut v = value(0.0, Unit::VDC, 0, 4);
+        v.overload_pos = true;
+        assert!(uncertainty(&vdc(v.clone(), Range::Step1)).is_none());
+        assert!(value_uncertainty(&v).is_none());
+    }
+}
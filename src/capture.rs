@@ -0,0 +1,297 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDateTime;
+use serde_json::{self, Map, Value as Json};
+
+use ut181a::{Measurement, Mode, Range, RecordDataItem, RecordInfo};
+
+use error::*;
+use json::{
+    count_json, measurement_from_json, measurement_json, range_name, record_data_from_json,
+    record_data_json, record_info_from_json, record_info_json, saved_json,
+    saved_measurement_from_json, u16_from_json, unit_json,
+};
+use meter::Meter;
+
+/// DMM wrapper writing every command and its reply to capture file.
+///
+/// Capture file contains one JSON object per line:
+/// `{"t": <seconds since start>, "call": <command>, "args": ..., "result": ...}`
+/// (or `"error"` instead of `"result"`).
+pub(crate) struct CaptureMeter {
+    inner: Box<dyn Meter>,
+    out: BufWriter<File>,
+    started: Instant,
+}
+
+impl CaptureMeter {
+    pub(crate) fn new(inner: Box<dyn Meter>, path: &Path) -> Result<CaptureMeter> {
+        Ok(CaptureMeter {
+            inner,
+            out: BufWriter::new(File::create(path)?),
+            started: Instant::now(),
+        })
+    }
+
+    fn write<T, F>(&mut self, call: &str, args: Json, result: Result<T>, to_json: F) -> Result<T>
+    where
+        F: FnOnce(&T) -> Json,
+    {
+        let mut entry = Map::new();
+        entry.insert("t".into(), Json::from(self.started.elapsed().as_secs_f64()));
+        entry.insert("call".into(), Json::from(call));
+        entry.insert("args".into(), args);
+        match result {
+            Ok(ref v) => entry.insert("result".into(), to_json(v)),
+            Err(ref e) => entry.insert("error".into(), Json::from(e.to_string())),
+        };
+        serde_json::to_writer(&mut self.out, &Json::Object(entry))?;
+        writeln!(self.out)?;
+        self.out.flush()?;
+        result
+    }
+}
+
+impl Meter for CaptureMeter {
+    fn toggle_hold(&mut self) -> Result<()> {
+        let r = self.inner.toggle_hold();
+        self.write("toggle_hold", Json::Null, r, unit_json)
+    }
+
+    fn save_measurement(&mut self) -> Result<()> {
+        let r = self.inner.save_measurement();
+        self.write("save_measurement", Json::Null, r, unit_json)
+    }
+
+    fn get_saved_measurement_count(&mut self) -> Result<u16> {
+        let r = self.inner.get_saved_measurement_count();
+        self.write("get_saved_measurement_count", Json::Null, r, count_json)
+    }
+
+    fn get_saved_measurement(&mut self, n: u16) -> Result<(NaiveDateTime, Measurement)> {
+        let r = self.inner.get_saved_measurement(n);
+        self.write("get_saved_measurement", Json::from(n), r, saved_json)
+    }
+
+    fn delete_saved_measurement(&mut self, index: u16) -> Result<()> {
+        let r = self.inner.delete_saved_measurement(index);
+        self.write("delete_saved_measurement", Json::from(index), r, unit_json)
+    }
+
+    fn delete_all_saved_measurement(&mut self) -> Result<()> {
+        let r = self.inner.delete_all_saved_measurement();
+        self.write("delete_all_saved_measurement", Json::Null, r, unit_json)
+    }
+
+    fn set_min_max_mode(&mut self, on: bool) -> Result<()> {
+        let r = self.inner.set_min_max_mode(on);
+        self.write("set_min_max_mode", Json::from(on), r, unit_json)
+    }
+
+    fn set_range(&mut self, range: Range) -> Result<()> {
+        let r = self.inner.set_range(range);
+        self.write("set_range", Json::from(range_name(range)), r, unit_json)
+    }
+
+    fn set_reference_value(&mut self, val: f32) -> Result<()> {
+        let r = self.inner.set_reference_value(val);
+        self.write("set_reference_value", Json::from(val), r, unit_json)
+    }
+
+    fn set_mode(&mut self, mode: Mode) -> Result<()> {
+        let r = self.inner.set_mode(mode);
+        self.write("set_mode", Json::from(mode.to_string()), r, unit_json)
+    }
+
+    fn get_record_count(&mut self) -> Result<u16> {
+        let r = self.inner.get_record_count();
+        self.write("get_record_count", Json::Null, r, count_json)
+    }
+
+    fn get_record_info(&mut self, i: u16) -> Result<RecordInfo> {
+        let r = self.inner.get_record_info(i);
+        self.write("get_record_info", Json::from(i), r, |info| {
+            record_info_json(i, info)
+        })
+    }
+
+    fn get_record_data(&mut self, i: u16) -> Result<Vec<RecordDataItem>> {
+        let r = self.inner.get_record_data(i);
+        self.write("get_record_data", Json::from(i), r, |items| {
+            record_data_json(items)
+        })
+    }
+
+    fn start_record(&mut self, name: &str, interval: u16, duration: u32) -> Result<()> {
+        let r = self.inner.start_record(name, interval, duration);
+        let args = json!({"name": name, "interval": interval, "duration": duration});
+        self.write("start_record", args, r, unit_json)
+    }
+
+    fn stop_record(&mut self) -> Result<()> {
+        let r = self.inner.stop_record();
+        self.write("stop_record", Json::Null, r, unit_json)
+    }
+
+    fn monitor_on(&mut self) -> Result<()> {
+        let r = self.inner.monitor_on();
+        self.write("monitor_on", Json::Null, r, unit_json)
+    }
+
+    fn monitor_off(&mut self) -> Result<()> {
+        let r = self.inner.monitor_off();
+        self.write("monitor_off", Json::Null, r, unit_json)
+    }
+
+    fn get_measurement(&mut self) -> Result<Measurement> {
+        let r = self.inner.get_measurement();
+        self.write("get_measurement", Json::Null, r, measurement_json)
+    }
+}
+
+/// DMM backend replaying capture file written by `CaptureMeter`.
+///
+/// Replies are returned in captured order with captured timing,
+/// mismatch of command or its arguments is reported as an error.
+pub(crate) struct ReplayMeter {
+    entries: VecDeque<Json>,
+    started: Instant,
+}
+
+impl ReplayMeter {
+    pub(crate) fn open(path: &Path) -> Result<ReplayMeter> {
+        let mut entries = VecDeque::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            entries.push_back(serde_json::from_str(&line)?);
+        }
+        Ok(ReplayMeter {
+            entries,
+            started: Instant::now(),
+        })
+    }
+
+    fn next(&mut self, call: &str, args: Json) -> Result<Json> {
+        let entry = self
+            .entries
+            .pop_front()
+            .ok_or_else(|| Error::from(format!("replay is finished, no reply to '{}'", call)))?;
+        let captured = entry["call"].as_str().unwrap_or("");
+        if captured != call {
+            return Err(format!(
+                "replay mismatch: captured '{}' command, but '{}' is requested",
+                captured, call
+            )
+            .into());
+        }
+        if entry["args"] != args {
+            return Err(format!(
+                "replay mismatch: '{}' is captured with arguments {}, but {} are requested",
+                call, entry["args"], args
+            )
+            .into());
+        }
+        if let Some(t) = entry["t"].as_f64() {
+            let at = Duration::from_secs_f64(t.max(0.0));
+            let elapsed = self.started.elapsed();
+            if at > elapsed {
+                thread::sleep(at - elapsed);
+            }
+        }
+        if let Some(e) = entry["error"].as_str() {
+            return Err(e.to_owned().into());
+        }
+        Ok(entry["result"].clone())
+    }
+
+    fn next_unit(&mut self, call: &str, args: Json) -> Result<()> {
+        self.next(call, args).map(|_| ())
+    }
+
+    fn next_u16(&mut self, call: &str) -> Result<u16> {
+        u16_from_json(&self.next(call, Json::Null)?, call)
+    }
+}
+
+impl Meter for ReplayMeter {
+    fn toggle_hold(&mut self) -> Result<()> {
+        self.next_unit("toggle_hold", Json::Null)
+    }
+
+    fn save_measurement(&mut self) -> Result<()> {
+        self.next_unit("save_measurement", Json::Null)
+    }
+
+    fn get_saved_measurement_count(&mut self) -> Result<u16> {
+        self.next_u16("get_saved_measurement_count")
+    }
+
+    fn get_saved_measurement(&mut self, n: u16) -> Result<(NaiveDateTime, Measurement)> {
+        saved_measurement_from_json(&self.next("get_saved_measurement", Json::from(n))?)
+    }
+
+    fn delete_saved_measurement(&mut self, index: u16) -> Result<()> {
+        self.next_unit("delete_saved_measurement", Json::from(index))
+    }
+
+    fn delete_all_saved_measurement(&mut self) -> Result<()> {
+        self.next_unit("delete_all_saved_measurement", Json::Null)
+    }
+
+    fn set_min_max_mode(&mut self, on: bool) -> Result<()> {
+        self.next_unit("set_min_max_mode", Json::from(on))
+    }
+
+    fn set_range(&mut self, range: Range) -> Result<()> {
+        self.next_unit("set_range", Json::from(range_name(range)))
+    }
+
+    fn set_reference_value(&mut self, val: f32) -> Result<()> {
+        self.next_unit("set_reference_value", Json::from(val))
+    }
+
+    fn set_mode(&mut self, mode: Mode) -> Result<()> {
+        self.next_unit("set_mode", Json::from(mode.to_string()))
+    }
+
+    fn get_record_count(&mut self) -> Result<u16> {
+        self.next_u16("get_record_count")
+    }
+
+    fn get_record_info(&mut self, i: u16) -> Result<RecordInfo> {
+        record_info_from_json(&self.next("get_record_info", Json::from(i))?)
+    }
+
+    fn get_record_data(&mut self, i: u16) -> Result<Vec<RecordDataItem>> {
+        record_data_from_json(&self.next("get_record_data", Json::from(i))?)
+    }
+
+    fn start_record(&mut self, name: &str, interval: u16, duration: u32) -> Result<()> {
+        let args = json!({"name": name, "interval": interval, "duration": duration});
+        self.next_unit("start_record", args)
+    }
+
+    fn stop_record(&mut self) -> Result<()> {
+        self.next_unit("stop_record", Json::Null)
+    }
+
+    fn monitor_on(&mut self) -> Result<()> {
+        self.next_unit("monitor_on", Json::Null)
+    }
+
+    fn monitor_off(&mut self) -> Result<()> {
+        self.next_unit("monitor_off", Json::Null)
+    }
+
+    fn get_measurement(&mut self) -> Result<Measurement> {
+        measurement_from_json(&self.next("get_measurement", Json::Null)?)
+    }
+}
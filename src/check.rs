@@ -0,0 +1,302 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use ut181a::{Mode, Unit, Value};
+
+use error::*;
+use measurement::{base_unit, base_value, is_overload, main_value, mode};
+use meter::Meter;
+use quantity::{format_quantity, parse_quantity};
+
+/// Exit code of failed check (errors exit with 1).
+pub(crate) const CHECK_FAILED: i32 = 2;
+
+/// Count of consecutive close readings treated as stable.
+const STABLE_READINGS: usize = 3;
+
+/// Parses `--samples` count, zero count is rejected.
+pub(crate) fn parse_samples(s: &str) -> Result<usize> {
+    match s.parse::<usize>()? {
+        0 => Err(format!("'--samples {}' should be greater than zero", s).into()),
+        n => Ok(n),
+    }
+}
+
+/// Acceptable measured values (in base units).
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Limits {
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+}
+
+impl Limits {
+    pub(crate) fn unbounded() -> Limits {
+        Limits {
+            min: f64::NEG_INFINITY,
+            max: f64::INFINITY,
+        }
+    }
+
+    /// Limits from nominal value and tolerance (`±2%`, `+-0.1V`, `5%`).
+    pub(crate) fn from_tolerance(expected: f64, tolerance: &str) -> Result<Limits> {
+        let t = tolerance.trim();
+        let t = ["±", "+/-", "+-"]
+            .iter()
+            .find(|p| t.starts_with(*p))
+            .map_or(t, |p| &t[p.len()..]);
+        let delta = match t.strip_suffix('%') {
+            Some(percent) => expected.abs() * parse_quantity(percent)?.value / 100.0,
+            None => parse_quantity(t)?.value,
+        };
+        if delta < 0.0 {
+            return Err(ErrorKind::InvalidQuantity(tolerance.to_owned()).into());
+        }
+        Ok(Limits {
+            min: expected - delta,
+            max: expected + delta,
+        })
+    }
+
+    fn contains(&self, v: f64) -> bool {
+        self.min <= v && v <= self.max
+    }
+}
+
+pub(crate) struct CheckOptions {
+    /// Mode to set (readings in other modes are skipped).
+    pub(crate) mode: Option<Mode>,
+    pub(crate) limits: Limits,
+    /// Unit of limits as given by user (empty if omitted).
+    pub(crate) unit: String,
+    /// Count of averaged readings.
+    pub(crate) samples: usize,
+    /// Maximum time to wait for stable reading.
+    pub(crate) timeout: Duration,
+}
+
+/// Unit spellings accepted for unit of reading.
+fn unit_matches(unit: Unit, s: &str) -> bool {
+    let names: &[&str] = match unit {
+        Unit::VDC => &["V", "VDC"],
+        Unit::VAC => &["V", "VAC"],
+        Unit::VAcDc => &["V", "Vac+dc"],
+        Unit::ADC => &["A", "ADC"],
+        Unit::AAC => &["A", "AAC"],
+        Unit::AAcDc => &["A", "Aac+dc"],
+        Unit::Celsius => &["C", "°C"],
+        Unit::Fahrenheit => &["F", "°F"],
+        Unit::F => &["F"],
+        Unit::Hz => &["Hz"],
+        Unit::s => &["s"],
+        Unit::Percent => &["%"],
+        Unit::S => &["S"],
+        Unit::Ohm => &["Ohm", "ohm", "Ω"],
+        Unit::dBm => &["dBm"],
+        Unit::dBV => &["dBV"],
+    };
+    s.is_empty() || names.contains(&s)
+}
+
+/// Readings are close if they differ less than 0.1% or 2 digits.
+fn is_close(a: &Value, b: &Value) -> bool {
+    let (x, y) = (base_value(a), base_value(b));
+    let digit = match b.precision {
+        Some(prc) => 10f64.powi(b.unit.exponent as i32 - prc as i32),
+        None => 0.0,
+    };
+    (x - y).abs() <= (y.abs() * 0.001).max(2.0 * digit)
+}
+
+/// Result of checked reading.
+pub(crate) struct Verdict {
+    pub(crate) pass: bool,
+    /// Averaged value in base units (`None` on overload or unstable reading).
+    pub(crate) value: Option<f64>,
+    /// Unit of reading without SI prefix.
+    pub(crate) unit: String,
+    /// Human-readable reading (`5.0011 VDC`, `OL`).
+    pub(crate) reading: String,
+    pub(crate) limits: Limits,
+}
+
+impl Verdict {
+    fn fail(reading: &str, unit: String, opts: &CheckOptions) -> Verdict {
+        Verdict {
+            pass: false,
+            value: None,
+            unit,
+            reading: reading.to_owned(),
+            limits: opts.limits,
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (min, max) = (self.limits.min, self.limits.max);
+        write!(
+            f,
+            "{}: {}",
+            if self.pass { "PASS" } else { "FAIL" },
+            self.reading
+        )?;
+        match (min.is_finite(), max.is_finite()) {
+            (true, true) => write!(
+                f,
+                " (limits {} ... {})",
+                format_quantity(min, &self.unit),
+                format_quantity(max, &self.unit)
+            ),
+            (true, false) => write!(f, " (limit >= {})", format_quantity(min, &self.unit)),
+            (false, true) => write!(f, " (limit <= {})", format_quantity(max, &self.unit)),
+            (false, false) => Ok(()),
+        }
+    }
+}
+
+/// Sets mode (if any), waits for stable reading and compares it with limits.
+///
+/// Prints one-line verdict and returns `true` on pass.
+pub(crate) fn check(dmm: &mut dyn Meter, opts: &CheckOptions, verbose: bool) -> Result<bool> {
+    if let Some(mode) = opts.mode {
+        if verbose {
+            diagln!("Sending 'SET MODE {}' command to DMM.", mode);
+        }
+        dmm.set_mode(mode)?;
+    }
+    let verdict = read_checked(dmm, opts, verbose)?;
+    println!("{}", verdict);
+    Ok(verdict.pass)
+}
+
+/// Waits for stable reading and compares it with limits.
+pub(crate) fn read_checked(
+    dmm: &mut dyn Meter,
+    opts: &CheckOptions,
+    verbose: bool,
+) -> Result<Verdict> {
+    if verbose {
+        diagln!("Sending 'MONITOR ON' command to DMM.");
+    }
+    dmm.monitor_on()?;
+    let result = check_readings(dmm, opts, verbose);
+    if verbose {
+        diagln!("Sending 'MONITOR OFF' command to DMM.");
+    }
+    dmm.monitor_off()?;
+    result
+}
+
+fn check_readings(dmm: &mut dyn Meter, opts: &CheckOptions, verbose: bool) -> Result<Verdict> {
+    let mode_name = opts.mode.map(|m| m.to_string());
+    let started = Instant::now();
+    let mut readings: Vec<Value> = Vec::new();
+    while readings.len() < STABLE_READINGS {
+        if started.elapsed() > opts.timeout {
+            let unit = readings.last().map(base_unit).unwrap_or_default();
+            return Ok(Verdict::fail("reading is not stable", unit, opts));
+        }
+        if verbose {
+            diagln!("Reading a message from DMM.");
+        }
+        let m = dmm.get_measurement()?;
+        if mode_name
+            .as_ref()
+            .is_some_and(|name| *name != mode(&m).to_string())
+        {
+            readings.clear();
+            continue;
+        }
+        let v = main_value(&m).clone();
+        // overload is final, it won't become stable reading within limits
+        if is_overload(&v) {
+            let reading = if v.overload_neg { "-OL" } else { "OL" };
+            return Ok(Verdict::fail(reading, base_unit(&v), opts));
+        }
+        if !readings.last().is_none_or(|last| is_close(last, &v)) {
+            readings.clear();
+        }
+        readings.push(v);
+    }
+
+    let first = readings.pop().ok_or("no readings")?;
+    if !unit_matches(first.unit.unit, &opts.unit) {
+        let reading = format!("{} (wrong mode or range, '{}' expected)", first, opts.unit);
+        return Ok(Verdict::fail(&reading, opts.unit.clone(), opts));
+    }
+    let unit = base_unit(&first);
+    let mut samples = vec![first];
+    while samples.len() < opts.samples {
+        if verbose {
+            diagln!("Reading a message from DMM.");
+        }
+        let m = dmm.get_measurement()?;
+        samples.push(main_value(&m).clone());
+    }
+
+    if let Some(v) = samples.iter().find(|v| is_overload(v)) {
+        let reading = if v.overload_neg { "-OL" } else { "OL" };
+        return Ok(Verdict::fail(reading, unit, opts));
+    }
+    let average = samples.iter().map(base_value).sum::<f64>() / samples.len() as f64;
+    let mut reading = format_quantity(average, &unit);
+    if samples.len() > 1 {
+        reading = format!("{} (average of {})", reading, samples.len());
+    }
+    Ok(Verdict {
+        pass: opts.limits.contains(average),
+        value: Some(average),
+        unit,
+        reading,
+        limits: opts.limits,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_limits(expected: f64, tolerance: &str, min: f64, max: f64) {
+        let l = Limits::from_tolerance(expected, tolerance).unwrap();
+        assert!(
+            (l.min - min).abs() < 1e-9 && (l.max - max).abs() < 1e-9,
+            "{} {}: {:?}",
+            expected,
+            tolerance,
+            l
+        );
+    }
+
+    #[test]
+    fn percent_tolerance() {
+        assert_limits(5.0, "±2%", 4.9, 5.1);
+        assert_limits(5.0, "2%", 4.9, 5.1);
+        assert_limits(-5.0, "+-10%", -5.5, -4.5);
+    }
+
+    #[test]
+    fn absolute_tolerance() {
+        assert_limits(5.0, "+-0.1V", 4.9, 5.1);
+        assert_limits(5.0, "+/-100mV", 4.9, 5.1);
+        assert_limits(1e3, "±1e1", 990.0, 1010.0);
+    }
+
+    #[test]
+    fn invalid_tolerance() {
+        assert!(Limits::from_tolerance(5.0, "-1%").is_err());
+        assert!(Limits::from_tolerance(5.0, "±x").is_err());
+    }
+
+    #[test]
+    fn samples_reject_zero() {
+        assert!(parse_samples("0").is_err());
+        assert_eq!(parse_samples("3").unwrap(), 3);
+    }
+
+    #[test]
+    fn limits_are_inclusive() {
+        let l = Limits::from_tolerance(5.0, "±1").unwrap();
+        assert!(l.contains(4.0) && l.contains(6.0));
+        assert!(!l.contains(3.99) && !l.contains(6.01));
+    }
+}
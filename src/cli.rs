@@ -1,102 +1,64 @@
+use std::sync::OnceLock;
+
 use clap;
 use clap::{App, Arg, SubCommand};
 
+use ut181a::Range;
+
+use color::{no_color_env, CHOICES};
+use db::SUMMARIES;
+use modes::MODES;
+use ranges::step_summary;
+
 fn mode_subcommands<'a, 'b>() -> Vec<App<'a, 'b>> {
     vec![
-        SubCommand::with_name("vac").about("VAC"),
-        SubCommand::with_name("vac-rel").about("VAC/Rel"),
-        SubCommand::with_name("vac-hz").about("VAC/Hz"),
-        SubCommand::with_name("vac-peak").about("VAC/Peak"),
-        SubCommand::with_name("vac-lowpass").about("VAC/LowPass"),
-        SubCommand::with_name("vac-lowpass-rel").about("VAC/LowPass/Rel"),
-        SubCommand::with_name("vac-dbv").about("VAC/dBV"),
-        SubCommand::with_name("vac-dbv-rel").about("VAC/dBV/Rel"),
-        SubCommand::with_name("vac-dbm").about("VAC/dBm"),
-        SubCommand::with_name("vac-dbm-rel").about("VAC/dBm/Rel"),
-        SubCommand::with_name("mvac").about("mVAC"),
-        SubCommand::with_name("mvac-rel").about("mVAC/Rel"),
-        SubCommand::with_name("mvac-hz").about("mVAC/Hz"),
-        SubCommand::with_name("mvac-peak").about("mVAC/Peak"),
-        SubCommand::with_name("mvac-acdc").about("mVAC/AC+DC"),
-        SubCommand::with_name("mvac-acdc-rel").about("mVAC/AC+DC/Rel"),
-        SubCommand::with_name("vdc").about("VDC"),
-        SubCommand::with_name("vdc-rel").about("VDC/Rel"),
-        SubCommand::with_name("vdc-acdc").about("VDC/AC+DC"),
-        SubCommand::with_name("vdc-acdc-rel").about("VDC/AC+DC/Rel"),
-        SubCommand::with_name("vdc-peak").about("VDC/Peak"),
-        SubCommand::with_name("mvdc").about("mVDC"),
-        SubCommand::with_name("mvdc-rel").about("mVDC/Rel"),
-        SubCommand::with_name("mvdc-peak").about("mVDC/Peak"),
-        SubCommand::with_name("temp-c-t1t2").about("Temp C/T1,T2"),
-        SubCommand::with_name("temp-c-t1t2-rel").about("Temp C/T1,T2/Rel"),
-        SubCommand::with_name("temp-c-t2t1").about("Temp C/T2,T1"),
-        SubCommand::with_name("temp-c-t2t1-rel").about("Temp C/T2,T1/Rel"),
-        SubCommand::with_name("temp-c-t1t2-diff").about("Temp C/T1-T2"),
-        SubCommand::with_name("temp-c-t2t1-diff").about("Temp C/T2-T1"),
-        SubCommand::with_name("temp-f-t1t2").about("Temp F/T1,T2"),
-        SubCommand::with_name("temp-f-t1t2-rel").about("Temp F/T1,T2/Rel"),
-        SubCommand::with_name("temp-f-t2t1").about("Temp F/T2,T1"),
-        SubCommand::with_name("temp-f-t2t1-rel").about("Temp F/T2,T1/Rel"),
-        SubCommand::with_name("temp-f-t1t2-diff").about("Temp F/T1-T2"),
-        SubCommand::with_name("temp-f-t2t1-diff").about("Temp F/T2-T1"),
-        SubCommand::with_name("res").about("Resistance"),
-        SubCommand::with_name("res-rel").about("Resistance/Rel"),
-        SubCommand::with_name("beeper-short").about("Beeper/Short"),
-        SubCommand::with_name("beeper-open").about("Beeper/Open"),
-        SubCommand::with_name("adm").about("Admittance"),
-        SubCommand::with_name("adm-rel").about("Admittance/Rel"),
-        SubCommand::with_name("diode").about("Diode"),
-        SubCommand::with_name("diode-alarm").about("Diode/Alarm"),
-        SubCommand::with_name("cap").about("Capacitance"),
-        SubCommand::with_name("cap-rel").about("Capacitance/Rel"),
-        SubCommand::with_name("freq").about("Frequency"),
-        SubCommand::with_name("freq-rel").about("Frequency/Rel"),
-        SubCommand::with_name("duty").about("Duty cycle"),
-        SubCommand::with_name("duty-rel").about("Duty cycle/Rel"),
-        SubCommand::with_name("pulse").about("Pulse width"),
-        SubCommand::with_name("pulse-rel").about("Pulse/Rel"),
-        SubCommand::with_name("uadc").about("uADC"),
-        SubCommand::with_name("uadc-rel").about("uADC/Rel"),
-        SubCommand::with_name("uadc-acdc").about("uADC/AC+DC"),
-        SubCommand::with_name("uadc-acdc-rel").about("uADC/AC+DC/Rel"),
-        SubCommand::with_name("uadc-peak").about("uADC/Peak"),
-        SubCommand::with_name("madc").about("mADC"),
-        SubCommand::with_name("madc-rel").about("mADC/Rel"),
-        SubCommand::with_name("madc-acdc").about("mADC/AC+DC"),
-        SubCommand::with_name("madc-acdc-rel").about("mADC/AC+DC/Rel"),
-        SubCommand::with_name("madc-peak").about("mADC/Peak"),
-        SubCommand::with_name("adc").about("ADC"),
-        SubCommand::with_name("adc-rel").about("ADC/Rel"),
-        SubCommand::with_name("adc-acdc").about("ADC/AC+DC"),
-        SubCommand::with_name("adc-acdc-rel").about("ADC/AC+DC/Rel"),
-        SubCommand::with_name("adc-peak").about("ADC/Peak"),
-        SubCommand::with_name("uaac").about("uAAC"),
-        SubCommand::with_name("uaac-rel").about("uAAC/Rel"),
-        SubCommand::with_name("uaac-hz").about("uAAC/Hz"),
-        SubCommand::with_name("uaac-peak").about("uAAC/Peak"),
-        SubCommand::with_name("maac").about("mAAC"),
-        SubCommand::with_name("maac-rel").about("mAAC/Rel"),
-        SubCommand::with_name("maac-hz").about("mAAC/Hz"),
-        SubCommand::with_name("maac-peak").about("mAAC/Peak"),
-        SubCommand::with_name("aac").about("AAC"),
-        SubCommand::with_name("aac-rel").about("AAC/Rel"),
-        SubCommand::with_name("aac-hz").about("AAC/Hz"),
-        SubCommand::with_name("aac-peak").about("AAC/Peak"),
+        SubCommand::with_name("list").about("Print names of all modes"),
+        SubCommand::with_name("get").about("Print current mode"),
     ]
 }
 
+const STEPS: &[(&str, Range)] = &[
+    ("step1", Range::Step1),
+    ("step2", Range::Step2),
+    ("step3", Range::Step3),
+    ("step4", Range::Step4),
+    ("step5", Range::Step5),
+    ("step6", Range::Step6),
+    ("step7", Range::Step7),
+    ("step8", Range::Step8),
+];
+
+/// Help of step subcommands (clap 2 borrows it, shell rebuilds CLI for every line).
+fn step_abouts() -> &'static [String] {
+    static ABOUTS: OnceLock<Vec<String>> = OnceLock::new();
+    ABOUTS.get_or_init(|| {
+        STEPS
+            .iter()
+            .map(|&(_, step)| format!("{} range", step_summary(step)))
+            .collect()
+    })
+}
+
 fn range_subcommands<'a, 'b>() -> Vec<App<'a, 'b>> {
+    let mut apps = vec![SubCommand::with_name("auto").about("Auto range")];
+    for (&(name, _), about) in STEPS.iter().zip(step_abouts()) {
+        apps.push(SubCommand::with_name(name).about(about.as_str()));
+    }
+    apps.push(SubCommand::with_name("list").about("Print ranges of current mode"));
+    apps
+}
+
+fn min_max_subcommands<'a, 'b>() -> Vec<App<'a, 'b>> {
+    vec![
+        SubCommand::with_name("on").about("Turn on or restart Min/Max mode"),
+        SubCommand::with_name("off").about("Turn off Min/Max mode"),
+    ]
+}
+
+fn read_subcommands<'a, 'b>() -> Vec<App<'a, 'b>> {
     vec![
-        SubCommand::with_name("auto").about("Auto range"),
-        SubCommand::with_name("step1").about("60 mV/6 V/600 uA/60 mA/600 Ohm/60 Hz/6 nF range"),
-        SubCommand::with_name("step2")
-            .about("600 mV/60 V/6000 uA/600 mA/6 KOhm/600 Hz/60 nF range"),
-        SubCommand::with_name("step3").about("600V/60 KOhm/6 KHz/600 nF range"),
-        SubCommand::with_name("step4").about("1000 V/600 KOhm/60 KHz/6 uF range"),
-        SubCommand::with_name("step5").about("6 MOhm/600 KHz/60 uF range"),
-        SubCommand::with_name("step6").about("60 MOhm/6 MHz/600 uF range"),
-        SubCommand::with_name("step7").about("60 MHz/6 mF range"),
-        SubCommand::with_name("step8").about("60 mF range"),
+        SubCommand::with_name("once").about("Read current measurement once"),
+        SubCommand::with_name("cont").about("Read current measurement continuously"),
     ]
 }
 
@@ -135,7 +97,26 @@ fn record_subcommands<'a, 'b>() -> Vec<App<'a, 'b>> {
                     .help("Record index")
                     .required(true)
                     .index(1),
-            ),
+            )
+            .arg_from_usage("--csv 'Print data in CSV format'"),
+        SubCommand::with_name("export")
+            .about("Export data of record to CSV file")
+            .arg(
+                Arg::with_name("INDEX")
+                    .help("Record index")
+                    .required(true)
+                    .index(1),
+            )
+            .arg_from_usage("-o --output=[FILE] 'Write CSV to FILE instead of stdout'"),
+        SubCommand::with_name("plot")
+            .about("Plot data of record to SVG or PNG file")
+            .arg(
+                Arg::with_name("INDEX")
+                    .help("Record index")
+                    .required(true)
+                    .index(1),
+            )
+            .arg_from_usage("-o --output=<FILE> 'Chart file (.svg or .png)'"),
         SubCommand::with_name("start")
             .about("Start new recording")
             .arg(
@@ -146,81 +127,281 @@ fn record_subcommands<'a, 'b>() -> Vec<App<'a, 'b>> {
             )
             .arg(
                 Arg::with_name("INTERVAL")
-                    .help("Sampling interval in seconds")
+                    .help("Sampling interval, 1s...1h (e.g. 10s, 5m; seconds by default)")
                     .required(true)
                     .index(2),
             )
             .arg(
                 Arg::with_name("DURATION")
-                    .help("Recording duration in minutes")
+                    .help("Recording duration, 1m...99d (e.g. 90m, 8h, 2d; minutes by default)")
                     .required(true)
                     .index(3),
+            )
+            .arg_from_usage("--wait 'Wait until recording is finished and download it'")
+            .arg(
+                Arg::from_usage("-o --output=[FILE] 'Write CSV of finished record to FILE'")
+                    .requires("wait"),
             ),
+        SubCommand::with_name("status").about("Get state of last record"),
         SubCommand::with_name("stop").about("Stop current recording"),
     ]
 }
 
+pub(crate) fn commands<'a, 'b>() -> Vec<App<'a, 'b>> {
+    vec![
+        SubCommand::with_name("list-devices").about("Enumerate all connected DMMs"),
+        SubCommand::with_name("hold").about("Hold current measurement"),
+        SubCommand::with_name("min-max-mode")
+            .about("Min/Max mode commands")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommands(min_max_subcommands()),
+        SubCommand::with_name("ref")
+            .about("Set reference value in relative mode")
+            .arg(
+                Arg::with_name("VALUE")
+                    .help("Reference value")
+                    .required(true)
+                    .index(1),
+            ),
+        SubCommand::with_name("range")
+            .about("Set measuring range commands ('range list' prints ranges of current mode)")
+            .setting(clap::AppSettings::ArgRequiredElseHelp)
+            .setting(clap::AppSettings::ArgsNegateSubcommands)
+            .arg(
+                Arg::with_name("VALUE")
+                    .help("Full scale of range in current mode (e.g. '600mV', '60k', '6uF')")
+                    .index(1),
+            )
+            .subcommand(SubCommand::with_name("auto").about("Auto range"))
+            .subcommands(range_subcommands()),
+        SubCommand::with_name("mode")
+            .about("Set measuring mode commands")
+            .setting(clap::AppSettings::ArgRequiredElseHelp)
+            .setting(clap::AppSettings::ArgsNegateSubcommands)
+            .arg(
+                Arg::with_name("MODE")
+                    .help("Mode name ('vac-dbm-rel' or 'VAC/dBm/Rel', see 'mode list')")
+                    .index(1),
+            )
+            .subcommands(mode_subcommands()),
+        SubCommand::with_name("read")
+            .about("Read measurement commands")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommands(read_subcommands()),
+        SubCommand::with_name("save")
+            .about("Saved measurements commands")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommands(save_subcommands()),
+        SubCommand::with_name("record")
+            .about("Record management commands")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommands(record_subcommands()),
+        SubCommand::with_name("log")
+            .about("Log measurements to file until limit is reached or Ctrl-C is pressed")
+            .arg(
+                Arg::with_name("FILE")
+                    .help("Output file (CSV or NDJSON), it can be omitted with --db")
+                    .index(1),
+            )
+            .arg(
+                Arg::from_usage(
+                    "-t --type=[TYPE] 'File type (detected by FILE extension by default)'",
+                )
+                .possible_values(&["csv", "ndjson"]),
+            )
+            .arg_from_usage("--duration=[DURATION] 'Stop after DURATION (e.g. 90s, 8h, 1h30m)'")
+            .arg_from_usage("--samples=[N] 'Stop after N written samples'")
+            .arg_from_usage("--every=[INTERVAL] 'Write one sample per INTERVAL (e.g. 500ms, 10s)'"),
+        SubCommand::with_name("plot")
+            .about("Plot file written by 'log' or 'record export' to SVG or PNG file")
+            .arg(
+                Arg::with_name("FILE")
+                    .help("Input file (CSV or NDJSON)")
+                    .required(true)
+                    .index(1),
+            )
+            .arg_from_usage("-o --output=<FILE> 'Chart file (.svg or .png)'"),
+        SubCommand::with_name("check")
+            .about("Check that measured value is within limits (exit code 0 on pass, 2 on fail)")
+            .arg(
+                Arg::with_name("MODE")
+                    .help("Mode name (see 'mode list')")
+                    .required(true)
+                    .index(1),
+            )
+            .arg(
+                Arg::with_name("EXPECTED")
+                    .help("Expected value (e.g. 3.3V, 470mV, 4.7k)")
+                    .required_unless_one(&["min", "max"])
+                    .index(2),
+            )
+            .arg(
+                Arg::with_name("TOLERANCE")
+                    .help("Tolerance, relative or absolute (e.g. ±2%, +-0.1V)")
+                    .requires("EXPECTED")
+                    .index(3),
+            )
+            .arg(
+                Arg::from_usage("--min=[VALUE] 'Lower limit'")
+                    .conflicts_with_all(&["EXPECTED", "TOLERANCE"]),
+            )
+            .arg(
+                Arg::from_usage("--max=[VALUE] 'Upper limit'")
+                    .conflicts_with_all(&["EXPECTED", "TOLERANCE"]),
+            )
+            .arg_from_usage("--samples=[N] 'Average N readings (default: 1)'")
+            .arg_from_usage(
+                "--timeout=[DURATION] 'Wait for stable reading at most DURATION (default: 10s)'",
+            ),
+        SubCommand::with_name("profile")
+            .about("Apply mode, range, reference and check limits of config profile")
+            .arg(
+                Arg::with_name("NAME")
+                    .help("Profile name ([profile.NAME] table), lists profiles if omitted")
+                    .index(1),
+            )
+            .arg_from_usage("--report=[FILE] 'Write JSON report of check to FILE'"),
+        SubCommand::with_name("run")
+            .about("Run test sequence from TOML or YAML file (exit code 2 if any read fails)")
+            .arg(
+                Arg::with_name("SCRIPT")
+                    .help("Sequence file (.toml, .yaml or .yml)")
+                    .required(true)
+                    .index(1),
+            )
+            .arg_from_usage("-r --report=[FILE] 'Write JSON report to FILE'"),
+        SubCommand::with_name("sync")
+            .about("Download all records and saved measurements into directory")
+            .arg(
+                Arg::with_name("DIR")
+                    .help("Archive directory, it can be omitted with --db")
+                    .index(1),
+            ),
+        SubCommand::with_name("serve-metrics")
+            .about("Serve live readings as Prometheus metrics on /metrics")
+            .arg(
+                Arg::from_usage("--listen=[ADDRESS] 'Listen address'")
+                    .default_value("127.0.0.1:9181"),
+            ),
+        SubCommand::with_name("http")
+            .about("Serve REST API and WebSocket stream of measurements")
+            .arg(
+                Arg::from_usage("--listen=[ADDRESS] 'Listen address'")
+                    .default_value("127.0.0.1:8181"),
+            ),
+        SubCommand::with_name("scpi-server")
+            .about("Accept SCPI commands over TCP like a bench DMM")
+            .arg(Arg::from_usage("--port=[PORT] 'TCP port'").default_value("5025"))
+            .arg(Arg::from_usage("--bind=[ADDRESS] 'Listen address'").default_value("127.0.0.1")),
+        SubCommand::with_name("publish")
+            .about("Publish measurements to message broker")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("mqtt")
+                    .about(
+                        "Publish measurements and state to MQTT broker, execute received commands",
+                    )
+                    .arg(
+                        Arg::from_usage("--broker=[HOST:PORT] 'MQTT broker address'")
+                            .default_value("localhost:1883"),
+                    )
+                    .arg(
+                        Arg::from_usage("--prefix=[PREFIX] 'Topic prefix'").default_value("ut181a"),
+                    )
+                    .arg(
+                        Arg::from_usage("--client-id=[ID] 'MQTT client ID'")
+                            .default_value("ut181a-cli"),
+                    )
+                    .arg_from_usage("--username=[USER] 'MQTT user name'")
+                    .arg(
+                        Arg::from_usage("--password=[PASSWORD] 'MQTT password'")
+                            .requires("username"),
+                    ),
+            ),
+        SubCommand::with_name("db")
+            .about("SQLite database (--db) commands")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("query")
+                    .about("Print summary of stored data")
+                    .arg(
+                        Arg::with_name("SUMMARY")
+                            .help("Summary")
+                            .possible_values(SUMMARIES)
+                            .required(true)
+                            .index(1),
+                    )
+                    .arg_from_usage("--session=[ID] 'Only data of session ID'"),
+            ),
+        SubCommand::with_name("watch").about("Full-screen dashboard of live readings"),
+        SubCommand::with_name("daemon")
+            .about("Keep DMM open and share it with other commands over Unix socket"),
+        SubCommand::with_name("shell")
+            .about("Interactive shell executing commands against one opened DMM"),
+    ]
+}
+
+fn names(apps: &[App]) -> Vec<String> {
+    apps.iter().map(|app| app.get_name().to_owned()).collect()
+}
+
+/// Returns candidates for the word following `words` (shell completion).
+pub(crate) fn completions(words: &[&str]) -> Vec<String> {
+    match *words {
+        [] => names(&commands()),
+        ["mode"] => names(&mode_subcommands())
+            .into_iter()
+            .chain(MODES.iter().map(|&(name, _)| name.to_owned()))
+            .collect(),
+        ["range"] => names(&range_subcommands()),
+        ["min-max-mode"] => names(&min_max_subcommands()),
+        ["read"] => names(&read_subcommands()),
+        ["save"] => names(&save_subcommands()),
+        ["record"] => names(&record_subcommands()),
+        _ => Vec::new(),
+    }
+}
+
 pub(crate) fn clap_app<'a, 'b>() -> App<'a, 'b> {
     App::new("UT181A-CLI")
         .version("0.2.0")
         .author("Anton Ageev <antage@gmail.com>")
         .about("Remote control for DMM UT181A.")
-        .global_setting(clap::AppSettings::ColorAuto)
+        .global_setting(if no_color_env() {
+            clap::AppSettings::ColorNever
+        } else {
+            clap::AppSettings::ColorAuto
+        })
         .global_setting(clap::AppSettings::ColoredHelp)
         .setting(clap::AppSettings::SubcommandRequiredElseHelp)
         .arg_from_usage("-d --device=[DEVICE] 'Open device at DEVICE path'")
         .arg_from_usage("-v --verbose 'Verbose mode'")
-        .subcommand(SubCommand::with_name("list-devices").about("Enumerate all connected DMMs"))
-        .subcommand(SubCommand::with_name("hold").about("Hold current measurement"))
-        .subcommand(
-            SubCommand::with_name("min-max-mode")
-                .about("Min/Max mode commands")
-                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
-                .subcommand(SubCommand::with_name("on").about("Turn on or restart Min/Max mode"))
-                .subcommand(SubCommand::with_name("off").about("Turn off Min/Max mode")),
-        )
-        .subcommand(
-            SubCommand::with_name("ref")
-                .about("Set reference value in relative mode")
-                .arg(
-                    Arg::with_name("VALUE")
-                        .help("Reference value")
-                        .required(true)
-                        .index(1),
-                ),
+        .arg_from_usage(
+            "--config=[FILE] 'Config file [default: $XDG_CONFIG_HOME/ut181a-cli/config.toml]'",
         )
-        .subcommand(
-            SubCommand::with_name("range")
-                .about("Set measuring range  commands")
-                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
-                .subcommand(SubCommand::with_name("auto").about("Auto range"))
-                .subcommands(range_subcommands()),
+        .arg_from_usage("--simulate 'Use simulated DMM instead of real device'")
+        .arg_from_usage("--capture=[FILE] 'Write all DMM commands and replies to FILE'")
+        .arg(
+            Arg::from_usage("--replay=[FILE] 'Replay DMM replies captured in FILE'")
+                .conflicts_with_all(&["simulate", "device"]),
         )
-        .subcommand(
-            SubCommand::with_name("mode")
-                .about("Set measuring mode commands")
-                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
-                .subcommands(mode_subcommands()),
+        .arg_from_usage(
+            "--db=[FILE] 'Store log, sync, save read and record read/export data in SQLite FILE'",
         )
-        .subcommand(
-            SubCommand::with_name("read")
-                .about("Read measurement commands")
-                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
-                .subcommand(SubCommand::with_name("once").about("Read current measurement once"))
-                .subcommand(
-                    SubCommand::with_name("cont").about("Read current measurement continuously"),
-                ),
+        .arg_from_usage("--socket=[PATH] 'Unix socket of daemon'")
+        .arg_from_usage("--no-daemon 'Open device directly even if daemon is running'")
+        .arg(
+            Arg::from_usage("-f --format=[FORMAT] 'Output format'")
+                .possible_values(&["text", "json"])
+                .default_value("text"),
         )
-        .subcommand(
-            SubCommand::with_name("save")
-                .about("Saved measurements commands")
-                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
-                .subcommands(save_subcommands()),
+        .arg_from_usage(
+            "--uncertainty 'Add expanded uncertainty (k=2) of UT181A specs to readings'",
         )
-        .subcommand(
-            SubCommand::with_name("record")
-                .about("Record management commands")
-                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
-                .subcommands(record_subcommands()),
+        .arg(
+            Arg::from_usage("--color=[WHEN] 'Colorize output (NO_COLOR disables auto colors)'")
+                .possible_values(CHOICES)
+                .default_value("auto"),
         )
+        .subcommands(commands())
 }
@@ -0,0 +1,85 @@
+use std::env;
+use std::fmt::Display;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ansi_term::{Colour, Style};
+
+const AUTO: usize = 0;
+const ALWAYS: usize = 1;
+const NEVER: usize = 2;
+
+static CHOICE: AtomicUsize = AtomicUsize::new(AUTO);
+
+pub(crate) const CHOICES: &[&str] = &["auto", "always", "never"];
+
+/// Sets `--color` choice (`auto` by default).
+pub(crate) fn init(choice: Option<&str>) {
+    let choice = match choice {
+        Some("always") => ALWAYS,
+        Some("never") => NEVER,
+        _ => AUTO,
+    };
+    CHOICE.store(choice, Ordering::Relaxed);
+}
+
+/// `NO_COLOR` (non-empty) disables colors unless `--color always` is given.
+pub(crate) fn no_color_env() -> bool {
+    env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+fn enabled(is_tty: bool) -> bool {
+    match CHOICE.load(Ordering::Relaxed) {
+        ALWAYS => true,
+        NEVER => false,
+        _ => is_tty && !no_color_env(),
+    }
+}
+
+/// Applies `style` to text printed to stdout.
+pub(crate) fn paint<T: Display>(style: Style, text: T) -> String {
+    if enabled(io::stdout().is_terminal()) {
+        style.paint(text.to_string()).to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+pub(crate) fn hold() -> Style {
+    Colour::Yellow.bold()
+}
+
+pub(crate) fn overload() -> Style {
+    Colour::Red.bold()
+}
+
+pub(crate) fn value() -> Style {
+    Style::new().bold()
+}
+
+pub(crate) fn positive() -> Style {
+    Colour::Green.normal()
+}
+
+pub(crate) fn negative() -> Style {
+    Colour::Red.normal()
+}
+
+pub(crate) fn maximum() -> Style {
+    Colour::Purple.normal()
+}
+
+pub(crate) fn minimum() -> Style {
+    Colour::Cyan.normal()
+}
+
+/// Prints error to stderr (in red if colors are enabled for it).
+pub(crate) fn eprint_error<T: Display>(text: T) {
+    let text = text.to_string();
+    let text = text.trim_end();
+    if enabled(io::stderr().is_terminal()) {
+        eprintln!("{}", Colour::Red.paint(text));
+    } else {
+        eprintln!("{}", text);
+    }
+}
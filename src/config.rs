@@ -0,0 +1,201 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+use serde_json::{Map, Value as Json};
+use toml;
+
+use color::CHOICES;
+use error::*;
+use json::{range_from_name, range_name};
+use modes::parse_mode;
+use ranges::range_by_value;
+use script::Script;
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// Settings of `config.toml`.
+///
+/// Top-level keys are defaults of global options (`device`, `format`, `color`,
+/// `verbose`), `[profile.NAME]` tables bundle `mode`, `range`, `reference`
+/// and `check` limits (the same keys as `read` step of script).
+pub(crate) struct Config {
+    pub(crate) path: PathBuf,
+    pub(crate) device: Option<String>,
+    pub(crate) format: Option<String>,
+    pub(crate) color: Option<String>,
+    pub(crate) verbose: bool,
+    profiles: Map<String, Json>,
+}
+
+fn invalid(msg: &str) -> Error {
+    ErrorKind::InvalidConfig(msg.to_owned()).into()
+}
+
+/// `$XDG_CONFIG_HOME/ut181a-cli/config.toml` (`~/.config` by default).
+fn default_path() -> PathBuf {
+    let dir = match (env::var_os("XDG_CONFIG_HOME"), env::var_os("HOME")) {
+        (Some(dir), _) => PathBuf::from(dir),
+        (None, Some(home)) => PathBuf::from(home).join(".config"),
+        (None, None) => env::temp_dir(),
+    };
+    dir.join("ut181a-cli").join(CONFIG_FILE)
+}
+
+fn string_field(j: &Json, key: &str, choices: &[&str]) -> Result<Option<String>> {
+    match j[key] {
+        Json::Null => Ok(None),
+        Json::String(ref s) if choices.is_empty() || choices.contains(&s.as_str()) => {
+            Ok(Some(s.clone()))
+        }
+        _ if choices.is_empty() => Err(invalid(&format!("'{}' should be string", key))),
+        _ => Err(invalid(&format!(
+            "'{}' should be one of: {}",
+            key,
+            choices.join(", ")
+        ))),
+    }
+}
+
+impl Config {
+    /// Loads config from `path` (`--config`) or from default location.
+    ///
+    /// Missing default config is the same as empty one, invalid default config
+    /// is reported as warning and ignored. Only explicit `--config` fails.
+    pub(crate) fn load(path: Option<&str>) -> Result<Config> {
+        if let Some(path) = path {
+            return Config::parse(Path::new(path))
+                .chain_err(|| format!("can't load config '{}'", path));
+        }
+        let path = default_path();
+        if !path.exists() {
+            return Ok(Config::empty(path));
+        }
+        match Config::parse(&path) {
+            Ok(config) => Ok(config),
+            Err(e) => {
+                eprintln!("Warning: ignoring config '{}': {}", path.display(), e);
+                Ok(Config::empty(path))
+            }
+        }
+    }
+
+    fn empty(path: PathBuf) -> Config {
+        Config {
+            path,
+            device: None,
+            format: None,
+            color: None,
+            verbose: false,
+            profiles: Map::new(),
+        }
+    }
+
+    fn parse(path: &Path) -> Result<Config> {
+        let j: Json = toml::from_str(&fs::read_to_string(path)?)?;
+        let obj = j
+            .as_object()
+            .ok_or_else(|| invalid("config should be table"))?;
+        if let Some(key) = obj
+            .keys()
+            .find(|k| !["device", "format", "color", "verbose", "profile"].contains(&k.as_str()))
+        {
+            return Err(invalid(&format!("unknown key '{}'", key)));
+        }
+        let profiles = match j["profile"] {
+            Json::Null => Map::new(),
+            Json::Object(ref profiles) => profiles.clone(),
+            _ => return Err(invalid("'profile' should be table")),
+        };
+        Ok(Config {
+            path: path.to_owned(),
+            device: string_field(&j, "device", &[])?,
+            format: string_field(&j, "format", &["text", "json"])?,
+            color: string_field(&j, "color", CHOICES)?,
+            verbose: match j["verbose"] {
+                Json::Null => false,
+                Json::Bool(b) => b,
+                _ => return Err(invalid("'verbose' should be true or false")),
+            },
+            profiles,
+        })
+    }
+
+    /// Global options of config which aren't given in command line.
+    pub(crate) fn default_args(&self, cli: &ArgMatches) -> Vec<String> {
+        // `device` isn't here, running daemon takes precedence over it (see `Device`).
+        let mut args = Vec::new();
+        if let Some(ref format) = self.format {
+            if cli.occurrences_of("format") == 0 {
+                args.push(format!("--format={}", format));
+            }
+        }
+        if let Some(ref color) = self.color {
+            if cli.occurrences_of("color") == 0 {
+                args.push(format!("--color={}", color));
+            }
+        }
+        if self.verbose && !cli.is_present("verbose") {
+            args.push("--verbose".to_owned());
+        }
+        args
+    }
+
+    pub(crate) fn profile_names(&self) -> Vec<&str> {
+        self.profiles.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// Builds script setting mode, range and reference of profile and checking its limits.
+    pub(crate) fn profile(&self, name: &str) -> Result<Script> {
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| invalid(&format!("unknown profile '{}'", name)))?;
+        Config::profile_script(profile).chain_err(|| format!("invalid profile '{}'", name))
+    }
+
+    fn profile_script(profile: &Json) -> Result<Script> {
+        let obj = profile
+            .as_object()
+            .ok_or_else(|| invalid("profile should be table"))?;
+        if let Some(key) = obj
+            .keys()
+            .find(|k| !["mode", "range", "reference", "check"].contains(&k.as_str()))
+        {
+            return Err(invalid(&format!("unknown key '{}'", key)));
+        }
+        let mut steps = Vec::new();
+        let mode = match string_field(profile, "mode", &[])? {
+            Some(name) => {
+                let mode = parse_mode(&name)?;
+                steps.push(json!({ "mode": name }));
+                Some(mode)
+            }
+            None => None,
+        };
+        if let Some(range) = string_field(profile, "range", &[])? {
+            // full scale (`600mV`) is resolved against profile mode
+            let step = match (range_from_name(&range), mode) {
+                (Ok(_), _) => range.clone(),
+                (Err(_), Some(mode)) => range_name(range_by_value(mode, &range)?).to_owned(),
+                (Err(_), None) => {
+                    return Err(invalid(
+                        "full scale 'range' requires 'mode' (or use step name)",
+                    ))
+                }
+            };
+            steps.push(json!({ "range": step, "name": format!("range {}", range) }));
+        }
+        match profile["reference"] {
+            Json::Null => {}
+            Json::Number(ref v) => steps.push(json!({ "reference": v })),
+            _ => return Err(invalid("'reference' should be number")),
+        }
+        match profile["check"] {
+            Json::Null => {}
+            ref check => steps.push(json!({ "read": check, "name": "check" })),
+        }
+        Script::from_json(&json!({ "steps": steps }))
+    }
+}
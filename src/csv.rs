@@ -0,0 +1,76 @@
+use std::io::Write;
+
+use ut181a::{RecordDataItem, RecordInfo, Value};
+
+use accuracy::{format_uncertainty, value_uncertainty};
+use display_measurement::format_number;
+use error::*;
+use json::format_timestamp;
+
+const RECORD_HEADER: &str = "index,timestamp,value,unit,flag";
+
+/// Quotes CSV field if it contains separators, quotes or line breaks.
+pub(crate) fn csv_field(s: &str) -> String {
+    if s.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+/// Returns numeric part of value and flag column content.
+pub(crate) fn csv_value(v: &Value) -> (String, &'static str) {
+    if v.overload_neg {
+        return (String::new(), "-OL");
+    }
+    if v.overload_pos {
+        return (String::new(), "OL");
+    }
+    if !v.value.is_finite() {
+        return (String::new(), "invalid");
+    }
+    (format_number(v), "")
+}
+
+/// Returns uncertainty column content (empty if it's unknown).
+pub(crate) fn csv_uncertainty(u: Option<f64>) -> String {
+    u.map(format_uncertainty).unwrap_or_default()
+}
+
+/// Writes record data, `uncertainty` adds column of expanded uncertainty (k=2).
+///
+/// Record info is written once as `# key=value` comment lines before the header.
+pub(crate) fn write_record_csv<W: Write>(
+    w: &mut W,
+    info: &RecordInfo,
+    items: &[RecordDataItem],
+    uncertainty: bool,
+) -> Result<()> {
+    writeln!(w, "# name={}", info.name.replace(&['\n', '\r'][..], " "))?;
+    writeln!(w, "# start={}", format_timestamp(&info.start))?;
+    writeln!(w, "# interval={}", info.interval.as_secs())?;
+    writeln!(w, "# unit={}", info.unit)?;
+    writeln!(w, "# sample_count={}", info.sample_count)?;
+    if uncertainty {
+        writeln!(w, "{},uncertainty", RECORD_HEADER)?;
+    } else {
+        writeln!(w, "{}", RECORD_HEADER)?;
+    }
+    for (i, item) in items.iter().enumerate() {
+        let (value, flag) = csv_value(&item.value);
+        write!(
+            w,
+            "{},{},{},{},{}",
+            i + 1,
+            format_timestamp(&item.timestamp),
+            value,
+            item.value.unit,
+            flag
+        )?;
+        if uncertainty {
+            write!(w, ",{}", csv_uncertainty(value_uncertainty(&item.value)))?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
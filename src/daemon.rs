@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, DirBuilder, Permissions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use clap::ArgMatches;
+use error_chain::ChainedError;
+use serde_json::{self, Value as Json};
+
+use ut181a::{Measurement, Mode, Range, RecordDataItem, RecordInfo};
+
+use color;
+use error::*;
+use interrupt;
+use json::{
+    count_json, measurement_from_json, measurement_json, range_from_name, range_name,
+    record_data_from_json, record_data_json, record_info_from_json, record_info_json, saved_json,
+    saved_measurement_from_json, u16_from_json, unit_json,
+};
+use meter::Meter;
+use modes::parse_mode;
+
+const SOCKET_FILE: &str = "ut181a-cli.sock";
+
+/// Period of checking Ctrl-C while there are no requests.
+const IDLE_PERIOD: u64 = 100; // milliseconds
+
+/// Socket path from `--socket` option, by default `$XDG_RUNTIME_DIR/ut181a-cli.sock`.
+///
+/// There is no default without `XDG_RUNTIME_DIR`, shared temporary directory
+/// would expose DMM to other users.
+pub(crate) fn socket_path(cli: &ArgMatches) -> Result<PathBuf> {
+    if let Some(path) = cli.value_of("socket") {
+        return Ok(PathBuf::from(path));
+    }
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => Ok(PathBuf::from(dir).join(SOCKET_FILE)),
+        None => Err("XDG_RUNTIME_DIR isn't set, use '--socket PATH'".into()),
+    }
+}
+
+/// Request from client connection thread to DMM owner thread.
+enum Request {
+    Call {
+        client: usize,
+        call: String,
+        args: Json,
+        reply: Sender<Json>,
+    },
+    Disconnect(usize),
+}
+
+fn reply_json(result: Result<Json>) -> Json {
+    match result {
+        Ok(result) => json!({ "result": result }),
+        Err(e) => json!({ "error": e.to_string() }),
+    }
+}
+
+fn u16_arg(args: &Json) -> Result<u16> {
+    u16_from_json(args, "argument")
+}
+
+fn str_arg(args: &Json) -> Result<&str> {
+    args.as_str()
+        .ok_or_else(|| "invalid argument (string is expected)".into())
+}
+
+/// Executes control command (all but monitoring ones) and encodes its result.
+fn execute(dmm: &mut dyn Meter, call: &str, args: &Json) -> Result<Json> {
+    match call {
+        "toggle_hold" => dmm.toggle_hold().map(|r| unit_json(&r)),
+        "save_measurement" => dmm.save_measurement().map(|r| unit_json(&r)),
+        "get_saved_measurement_count" => dmm.get_saved_measurement_count().map(|r| count_json(&r)),
+        "get_saved_measurement" => dmm
+            .get_saved_measurement(u16_arg(args)?)
+            .map(|r| saved_json(&r)),
+        "delete_saved_measurement" => dmm
+            .delete_saved_measurement(u16_arg(args)?)
+            .map(|r| unit_json(&r)),
+        "delete_all_saved_measurement" => dmm.delete_all_saved_measurement().map(|r| unit_json(&r)),
+        "set_min_max_mode" => {
+            let on = args
+                .as_bool()
+                .ok_or("invalid argument (boolean is expected)")?;
+            dmm.set_min_max_mode(on).map(|r| unit_json(&r))
+        }
+        "set_range" => dmm
+            .set_range(range_from_name(str_arg(args)?)?)
+            .map(|r| unit_json(&r)),
+        "set_reference_value" => {
+            let val = args
+                .as_f64()
+                .ok_or("invalid argument (number is expected)")?;
+            dmm.set_reference_value(val as f32).map(|r| unit_json(&r))
+        }
+        "set_mode" => {
+            let mode = parse_mode(str_arg(args)?)?;
+            dmm.set_mode(mode).map(|r| unit_json(&r))
+        }
+        "get_record_count" => dmm.get_record_count().map(|r| count_json(&r)),
+        "get_record_info" => {
+            let i = u16_arg(args)?;
+            dmm.get_record_info(i)
+                .map(|info| record_info_json(i, &info))
+        }
+        "get_record_data" => dmm
+            .get_record_data(u16_arg(args)?)
+            .map(|items| record_data_json(&items)),
+        "start_record" => {
+            let name = args["name"].as_str().ok_or("invalid record name")?;
+            let interval = args["interval"].as_u64().ok_or("invalid record interval")?;
+            let duration = args["duration"].as_u64().ok_or("invalid record duration")?;
+            dmm.start_record(name, interval as u16, duration as u32)
+                .map(|r| unit_json(&r))
+        }
+        "stop_record" => dmm.stop_record().map(|r| unit_json(&r)),
+        _ => Err(format!("unknown call '{}'", call).into()),
+    }
+}
+
+/// Executes control command, DMM ignores commands while monitoring is on.
+fn execute_paused(dmm: &mut dyn Meter, call: &str, args: &Json, monitoring: bool) -> Result<Json> {
+    if !monitoring {
+        return execute(dmm, call, args);
+    }
+    dmm.monitor_off()?;
+    let result = execute(dmm, call, args);
+    dmm.monitor_on()?;
+    result
+}
+
+/// Switches monitoring on while at least one client has it on.
+fn update_monitoring(
+    dmm: &mut dyn Meter,
+    clients: &HashMap<usize, bool>,
+    monitoring: &mut bool,
+    verbose: bool,
+) -> Result<()> {
+    let wanted = clients.values().any(|&on| on);
+    if wanted != *monitoring {
+        if verbose {
+            diagln!("Monitoring is {}.", if wanted { "on" } else { "off" });
+        }
+        if wanted {
+            dmm.monitor_on()?;
+        } else {
+            dmm.monitor_off()?;
+        }
+        *monitoring = wanted;
+    }
+    Ok(())
+}
+
+/// Reads requests of one client and writes replies.
+fn client_loop(client: usize, stream: UnixStream, requests: Sender<Request>) -> Result<()> {
+    let mut out = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let reply = match serde_json::from_str::<Json>(&line) {
+            Ok(j) => {
+                let (tx, rx) = mpsc::channel();
+                let request = Request::Call {
+                    client,
+                    call: j["call"].as_str().unwrap_or("").to_owned(),
+                    args: j["args"].clone(),
+                    reply: tx,
+                };
+                if requests.send(request).is_err() {
+                    break;
+                }
+                rx.recv()
+                    .unwrap_or_else(|_| json!({"error": "daemon is stopped"}))
+            }
+            Err(e) => json!({ "error": e.to_string() }),
+        };
+        writeln!(out, "{}", reply)?;
+    }
+    Ok(())
+}
+
+fn accept_loop(listener: UnixListener, requests: Sender<Request>) {
+    for (client, stream) in listener.incoming().enumerate() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let requests = requests.clone();
+        thread::spawn(move || {
+            // Client errors only close its connection.
+            let _ = client_loop(client, stream, requests.clone());
+            let _ = requests.send(Request::Disconnect(client));
+        });
+    }
+}
+
+/// Binds socket only owner may connect to (other users mustn't control DMM).
+///
+/// Socket is created with umask permissions, so it's bound in private directory
+/// and moved to `path` after its permissions are restricted.
+fn bind_private(path: &Path) -> Result<UnixListener> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("invalid socket path '{}'", path.display()))?;
+    let dir = path.with_file_name(format!(
+        ".{}.{}",
+        file_name.to_string_lossy(),
+        process::id()
+    ));
+    DirBuilder::new().mode(0o700).create(&dir)?;
+    let tmp = dir.join(file_name);
+    let result = UnixListener::bind(&tmp).and_then(|listener| {
+        fs::set_permissions(&tmp, Permissions::from_mode(0o600))?;
+        fs::rename(&tmp, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_dir_all(&dir);
+    Ok(result?)
+}
+
+/// Owns DMM and serves clients connected to Unix socket at `path` until Ctrl-C is pressed.
+///
+/// Monitoring is on while at least one client has it on, every measurement is
+/// delivered to all clients waiting for it. Control commands are executed one by one.
+pub(crate) fn serve(dmm: &mut dyn Meter, path: &Path, verbose: bool) -> Result<()> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("daemon is already running at '{}'", path.display()).into());
+        }
+        // Stale socket of crashed daemon.
+        fs::remove_file(path)?;
+    }
+    let listener = bind_private(path)?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || accept_loop(listener, tx));
+    println!("Listening on '{}'.", path.display());
+
+    interrupt::catch()?;
+    let result = serve_requests(dmm, &rx, verbose);
+    fs::remove_file(path)?;
+    result
+}
+
+fn serve_requests(dmm: &mut dyn Meter, requests: &Receiver<Request>, verbose: bool) -> Result<()> {
+    // Monitoring flag of every connected client.
+    let mut clients: HashMap<usize, bool> = HashMap::new();
+    let mut waiting: Vec<Sender<Json>> = Vec::new();
+    let mut monitoring = false;
+    dmm.monitor_off()?;
+    while !interrupt::is_interrupted() {
+        let timeout = if monitoring { 0 } else { IDLE_PERIOD };
+        let mut next = match requests.recv_timeout(Duration::from_millis(timeout)) {
+            Ok(request) => Some(request),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        while let Some(request) = next {
+            match request {
+                Request::Call {
+                    client,
+                    call,
+                    args,
+                    reply,
+                } => {
+                    if verbose {
+                        diagln!("Client #{}: {} {}", client, call, args);
+                    }
+                    match call.as_str() {
+                        "monitor_on" | "monitor_off" => {
+                            let previous = clients.insert(client, call == "monitor_on");
+                            let result = update_monitoring(dmm, &clients, &mut monitoring, verbose);
+                            if result.is_err() {
+                                // DMM state is unchanged, so is client one.
+                                clients.insert(client, previous.unwrap_or(false));
+                            }
+                            let _ = reply.send(reply_json(result.map(|_| Json::Null)));
+                        }
+                        "get_measurement" if clients.get(&client) == Some(&true) => {
+                            waiting.push(reply);
+                        }
+                        "get_measurement" => {
+                            let _ = reply.send(reply_json(Err("monitoring is off".into())));
+                        }
+                        _ => {
+                            let result = execute_paused(dmm, &call, &args, monitoring);
+                            let _ = reply.send(reply_json(result));
+                        }
+                    }
+                }
+                Request::Disconnect(client) => {
+                    clients.remove(&client);
+                    // Nobody to report to, next monitoring request retries.
+                    if let Err(e) = update_monitoring(dmm, &clients, &mut monitoring, verbose) {
+                        color::eprint_error(e.display_chain());
+                    }
+                }
+            }
+            next = requests.try_recv().ok();
+        }
+
+        if monitoring {
+            let reply = reply_json(dmm.get_measurement().map(|m| measurement_json(&m)));
+            for tx in waiting.drain(..) {
+                let _ = tx.send(reply.clone());
+            }
+        }
+    }
+    if monitoring {
+        dmm.monitor_off()?;
+    }
+    Ok(())
+}
+
+/// DMM backend forwarding commands to daemon.
+pub(crate) struct DaemonClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl DaemonClient {
+    pub(crate) fn connect(path: &Path) -> Result<DaemonClient> {
+        let stream = UnixStream::connect(path)?;
+        Ok(DaemonClient {
+            writer: stream.try_clone()?,
+            reader: BufReader::new(stream),
+        })
+    }
+
+    fn call(&mut self, call: &str, args: Json) -> Result<Json> {
+        writeln!(self.writer, "{}", json!({"call": call, "args": args}))?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err("daemon closed connection".into());
+        }
+        let reply: Json = serde_json::from_str(&line)?;
+        if let Some(e) = reply["error"].as_str() {
+            return Err(e.to_owned().into());
+        }
+        Ok(reply["result"].clone())
+    }
+
+    fn call_unit(&mut self, call: &str, args: Json) -> Result<()> {
+        self.call(call, args).map(|_| ())
+    }
+}
+
+impl Meter for DaemonClient {
+    fn toggle_hold(&mut self) -> Result<()> {
+        self.call_unit("toggle_hold", Json::Null)
+    }
+
+    fn save_measurement(&mut self) -> Result<()> {
+        self.call_unit("save_measurement", Json::Null)
+    }
+
+    fn get_saved_measurement_count(&mut self) -> Result<u16> {
+        let j = self.call("get_saved_measurement_count", Json::Null)?;
+        u16_from_json(&j, "get_saved_measurement_count")
+    }
+
+    fn get_saved_measurement(&mut self, n: u16) -> Result<(NaiveDateTime, Measurement)> {
+        saved_measurement_from_json(&self.call("get_saved_measurement", Json::from(n))?)
+    }
+
+    fn delete_saved_measurement(&mut self, index: u16) -> Result<()> {
+        self.call_unit("delete_saved_measurement", Json::from(index))
+    }
+
+    fn delete_all_saved_measurement(&mut self) -> Result<()> {
+        self.call_unit("delete_all_saved_measurement", Json::Null)
+    }
+
+    fn set_min_max_mode(&mut self, on: bool) -> Result<()> {
+        self.call_unit("set_min_max_mode", Json::from(on))
+    }
+
+    fn set_range(&mut self, range: Range) -> Result<()> {
+        self.call_unit("set_range", Json::from(range_name(range)))
+    }
+
+    fn set_reference_value(&mut self, val: f32) -> Result<()> {
+        self.call_unit("set_reference_value", Json::from(val))
+    }
+
+    fn set_mode(&mut self, mode: Mode) -> Result<()> {
+        self.call_unit("set_mode", Json::from(mode.to_string()))
+    }
+
+    fn get_record_count(&mut self) -> Result<u16> {
+        let j = self.call("get_record_count", Json::Null)?;
+        u16_from_json(&j, "get_record_count")
+    }
+
+    fn get_record_info(&mut self, i: u16) -> Result<RecordInfo> {
+        record_info_from_json(&self.call("get_record_info", Json::from(i))?)
+    }
+
+    fn get_record_data(&mut self, i: u16) -> Result<Vec<RecordDataItem>> {
+        record_data_from_json(&self.call("get_record_data", Json::from(i))?)
+    }
+
+    fn start_record(&mut self, name: &str, interval: u16, duration: u32) -> Result<()> {
+        let args = json!({"name": name, "interval": interval, "duration": duration});
+        self.call_unit("start_record", args)
+    }
+
+    fn stop_record(&mut self) -> Result<()> {
+        self.call_unit("stop_record", Json::Null)
+    }
+
+    fn monitor_on(&mut self) -> Result<()> {
+        self.call_unit("monitor_on", Json::Null)
+    }
+
+    fn monitor_off(&mut self) -> Result<()> {
+        self.call_unit("monitor_off", Json::Null)
+    }
+
+    fn get_measurement(&mut self) -> Result<Measurement> {
+        measurement_from_json(&self.call("get_measurement", Json::Null)?)
+    }
+}
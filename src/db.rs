@@ -0,0 +1,391 @@
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{Map, Value as Json};
+
+use ut181a::{Measurement, RecordDataItem, RecordInfo, Value};
+
+use csv::csv_value;
+use error::*;
+use json::{format_timestamp, measurement_json, range_name};
+use log::host_time;
+use measurement::{base_unit, fast_value, is_auto_range, is_holded, main_value, mode, range};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY,
+    started TEXT NOT NULL,
+    command TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS readings (
+    id INTEGER PRIMARY KEY,
+    session_id INTEGER NOT NULL REFERENCES sessions(id),
+    time TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    range TEXT NOT NULL,
+    hold INTEGER NOT NULL,
+    auto_range INTEGER NOT NULL,
+    value REAL,
+    unit TEXT NOT NULL,
+    flag TEXT NOT NULL,
+    fast REAL,
+    measurement TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS readings_session ON readings(session_id);
+CREATE TABLE IF NOT EXISTS saves (
+    id INTEGER PRIMARY KEY,
+    session_id INTEGER NOT NULL REFERENCES sessions(id),
+    timestamp TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    value REAL,
+    unit TEXT NOT NULL,
+    flag TEXT NOT NULL,
+    measurement TEXT NOT NULL,
+    UNIQUE (timestamp, measurement)
+);
+CREATE TABLE IF NOT EXISTS records (
+    id INTEGER PRIMARY KEY,
+    session_id INTEGER NOT NULL REFERENCES sessions(id),
+    name TEXT NOT NULL,
+    unit TEXT NOT NULL,
+    start TEXT NOT NULL,
+    interval INTEGER NOT NULL,
+    duration INTEGER NOT NULL,
+    sample_count INTEGER NOT NULL,
+    max REAL,
+    average REAL,
+    min REAL,
+    UNIQUE (name, start, sample_count)
+);
+CREATE TABLE IF NOT EXISTS samples (
+    record_id INTEGER NOT NULL REFERENCES records(id),
+    number INTEGER NOT NULL,
+    timestamp TEXT NOT NULL,
+    value REAL,
+    unit TEXT NOT NULL,
+    flag TEXT NOT NULL,
+    PRIMARY KEY (record_id, number)
+);
+";
+
+/// Summaries of `db query` command.
+pub(crate) const SUMMARIES: &[&str] = &["sessions", "readings", "saves", "records"];
+
+const SESSIONS_QUERY: &str = "
+SELECT s.id, s.started, s.command,
+    (SELECT COUNT(*) FROM readings WHERE session_id = s.id),
+    (SELECT COUNT(*) FROM saves WHERE session_id = s.id),
+    (SELECT COUNT(*) FROM records WHERE session_id = s.id)
+FROM sessions s WHERE ?1 IS NULL OR s.id = ?1 ORDER BY s.id";
+
+const READINGS_QUERY: &str = "
+SELECT session_id, mode, unit, COUNT(*), COUNT(value), MIN(value), AVG(value), MAX(value),
+    MIN(time), MAX(time)
+FROM readings WHERE ?1 IS NULL OR session_id = ?1
+GROUP BY session_id, mode, unit ORDER BY session_id, MIN(id)";
+
+const SAVES_QUERY: &str = "
+SELECT id, timestamp, mode, value, unit, flag
+FROM saves WHERE ?1 IS NULL OR session_id = ?1 ORDER BY timestamp, id";
+
+const RECORDS_QUERY: &str = "
+SELECT r.id, r.name, r.start, r.interval, r.sample_count, r.unit,
+    MIN(s.value), AVG(s.value), MAX(s.value)
+FROM records r LEFT JOIN samples s ON s.record_id = r.id
+WHERE ?1 IS NULL OR r.session_id = ?1
+GROUP BY r.id ORDER BY r.start, r.id";
+
+/// Value in base units (`None` on overload), base unit and flag (`OL`, `-OL`).
+fn stored_value(v: &Value) -> (Option<f64>, String, &'static str) {
+    let (number, flag) = csv_value(v);
+    // Parsing decimal form avoids f32 rounding noise.
+    let value = if number.is_empty() {
+        None
+    } else {
+        format!("{}e{}", number, v.unit.exponent)
+            .parse::<f64>()
+            .ok()
+    };
+    (value, base_unit(v), flag)
+}
+
+fn stored_number(v: &Value) -> Option<f64> {
+    stored_value(v).0
+}
+
+/// SQLite database of readings, saved measurements and records.
+pub(crate) struct Db {
+    conn: Connection,
+    /// Command writing data (`log`, `sync`...).
+    command: String,
+    session: Option<i64>,
+    /// Transaction is open (it's committed by `flush()`).
+    pending: bool,
+}
+
+impl Db {
+    /// Opens database at `path` for `command`, creates tables if they don't exist.
+    pub(crate) fn open(path: &Path, command: &str) -> Result<Db> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Db {
+            conn,
+            command: command.to_owned(),
+            session: None,
+            pending: false,
+        })
+    }
+
+    /// Session of current command, it's created on first write.
+    fn session(&mut self) -> Result<i64> {
+        if let Some(id) = self.session {
+            return Ok(id);
+        }
+        self.conn.execute(
+            "INSERT INTO sessions (started, command) VALUES (?1, ?2)",
+            params![host_time(), self.command],
+        )?;
+        let id = self.conn.last_insert_rowid();
+        self.session = Some(id);
+        Ok(id)
+    }
+
+    fn begin(&mut self) -> Result<()> {
+        if !self.pending {
+            self.conn.execute_batch("BEGIN")?;
+            self.pending = true;
+        }
+        Ok(())
+    }
+
+    /// Commits written rows.
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        if self.pending {
+            self.conn.execute_batch("COMMIT")?;
+            self.pending = false;
+        }
+        Ok(())
+    }
+
+    /// Adds reading taken by host logger at `time`.
+    pub(crate) fn insert_reading(&mut self, time: &str, m: &Measurement) -> Result<()> {
+        let session = self.session()?;
+        self.begin()?;
+        let (value, unit, flag) = stored_value(main_value(m));
+        self.conn.execute(
+            "INSERT INTO readings (session_id, time, mode, range, hold, auto_range, value, unit,
+                flag, fast, measurement)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                session,
+                time,
+                mode(m).to_string(),
+                range_name(range(m)),
+                is_holded(m),
+                is_auto_range(m),
+                value,
+                unit,
+                flag,
+                fast_value(m).and_then(stored_number),
+                measurement_json(m).to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Adds saved measurement, returns `false` if it's already imported.
+    pub(crate) fn insert_save(&mut self, t: &NaiveDateTime, m: &Measurement) -> Result<bool> {
+        let session = self.session()?;
+        let (value, unit, flag) = stored_value(main_value(m));
+        let inserted = self.conn.execute(
+            "INSERT OR IGNORE INTO saves (session_id, timestamp, mode, value, unit, flag,
+                measurement)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                session,
+                format_timestamp(t),
+                mode(m).to_string(),
+                value,
+                unit,
+                flag,
+                measurement_json(m).to_string()
+            ],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    /// Record is already imported (the same name, start time and sample count).
+    pub(crate) fn has_record(&self, info: &RecordInfo) -> Result<bool> {
+        let id: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT id FROM records WHERE name = ?1 AND start = ?2 AND sample_count = ?3",
+                params![info.name, format_timestamp(&info.start), info.sample_count],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(id.is_some())
+    }
+
+    /// Adds record with its samples, returns `false` if it's already imported.
+    pub(crate) fn insert_record(
+        &mut self,
+        info: &RecordInfo,
+        items: &[RecordDataItem],
+    ) -> Result<bool> {
+        if self.has_record(info)? {
+            return Ok(false);
+        }
+        let session = self.session()?;
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO records (session_id, name, unit, start, interval, duration, sample_count,
+                max, average, min)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                session,
+                info.name,
+                info.unit.to_string(),
+                format_timestamp(&info.start),
+                info.interval.as_secs(),
+                info.duration.as_secs(),
+                info.sample_count,
+                stored_number(&info.max),
+                stored_number(&info.average),
+                stored_number(&info.min)
+            ],
+        )?;
+        let record = tx.last_insert_rowid();
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO samples (record_id, number, timestamp, value, unit, flag)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for (i, item) in items.iter().enumerate() {
+                let (value, unit, flag) = stored_value(&item.value);
+                insert.execute(params![
+                    record,
+                    i + 1,
+                    format_timestamp(&item.timestamp),
+                    value,
+                    unit,
+                    flag
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Runs summary query, returns column names and rows.
+    pub(crate) fn query(
+        &self,
+        summary: &str,
+        session: Option<i64>,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        let (sql, columns): (&str, &[&str]) = match summary {
+            "sessions" => (
+                SESSIONS_QUERY,
+                &[
+                    "session", "started", "command", "readings", "saves", "records",
+                ],
+            ),
+            "readings" => (
+                READINGS_QUERY,
+                &[
+                    "session", "mode", "unit", "count", "valid", "min", "avg", "max", "first",
+                    "last",
+                ],
+            ),
+            "saves" => (
+                SAVES_QUERY,
+                &["id", "timestamp", "mode", "value", "unit", "flag"],
+            ),
+            "records" => (
+                RECORDS_QUERY,
+                &[
+                    "id", "name", "start", "interval", "samples", "unit", "min", "avg", "max",
+                ],
+            ),
+            _ => return Err(format!("unknown summary '{}'", summary).into()),
+        };
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt
+            .query_map(params![session], |row| {
+                (0..columns.len())
+                    .map(|i| row.get::<_, SqlValue>(i))
+                    .collect()
+            })?
+            .collect::<::std::result::Result<Vec<Vec<SqlValue>>, _>>()?;
+        Ok((columns.iter().map(|c| (*c).to_owned()).collect(), rows))
+    }
+}
+
+impl Drop for Db {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Formats SQL value for text table.
+pub(crate) fn sql_text(v: &SqlValue) -> String {
+    match *v {
+        SqlValue::Null => "-".to_owned(),
+        SqlValue::Integer(i) => i.to_string(),
+        SqlValue::Real(f) => format!("{}", (f * 1e9).round() / 1e9),
+        SqlValue::Text(ref s) => s.clone(),
+        SqlValue::Blob(ref b) => format!("<{} bytes>", b.len()),
+    }
+}
+
+pub(crate) fn sql_json(v: &SqlValue) -> Json {
+    match *v {
+        SqlValue::Null => Json::Null,
+        SqlValue::Integer(i) => Json::from(i),
+        SqlValue::Real(f) => Json::from(f),
+        SqlValue::Text(ref s) => Json::from(s.clone()),
+        SqlValue::Blob(ref b) => Json::from(b.clone()),
+    }
+}
+
+/// Row as JSON object.
+pub(crate) fn row_json(columns: &[String], row: &[SqlValue]) -> Json {
+    let mut obj = Map::new();
+    for (c, v) in columns.iter().zip(row) {
+        obj.insert(c.clone(), sql_json(v));
+    }
+    Json::Object(obj)
+}
+
+/// Prints rows as table with aligned columns.
+pub(crate) fn print_table(columns: &[String], rows: &[Vec<SqlValue>]) {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(sql_text).collect())
+        .collect();
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain(Some(columns[i].len()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |row: &[String]| {
+        row.iter()
+            .zip(&widths)
+            .map(|(cell, &w)| format!("{:<w$}", cell, w = w))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+    println!("{}", line(columns));
+    for row in &cells {
+        println!("{}", line(row));
+    }
+}
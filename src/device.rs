@@ -0,0 +1,136 @@
+use std::path::Path;
+
+use clap::ArgMatches;
+use hid;
+
+use ut181a::Dmm;
+
+use capture::{CaptureMeter, ReplayMeter};
+use daemon::{socket_path, DaemonClient};
+use error::*;
+use meter::Meter;
+use sim::SimulatedDmm;
+
+pub(crate) const VENDOR_ID: u16 = 0x10C4;
+pub(crate) const PRODUCT_ID: u16 = 0xEA80;
+
+/// Connects to running daemon unless particular device is requested.
+fn connect_daemon(cli: &ArgMatches) -> Option<DaemonClient> {
+    if ["simulate", "replay", "device", "no-daemon"]
+        .iter()
+        .any(|name| cli.is_present(name))
+    {
+        return None;
+    }
+    let path = socket_path(cli).ok()?;
+    let client = DaemonClient::connect(&path).ok()?;
+    if cli.is_present("verbose") {
+        diagln!("Use daemon at '{}'.", path.display());
+    }
+    Some(client)
+}
+
+fn open_device(
+    manager: Option<&hid::Manager>,
+    cli: &ArgMatches,
+    default_path: Option<&str>,
+    allow_daemon: bool,
+) -> Result<Box<dyn Meter>> {
+    if let Some(path) = cli.value_of("replay") {
+        if cli.is_present("verbose") {
+            diagln!("Replay captured device from '{}'.", path);
+        }
+        return Ok(Box::new(ReplayMeter::open(Path::new(path))?));
+    }
+    if allow_daemon {
+        if let Some(client) = connect_daemon(cli) {
+            return Ok(Box::new(client));
+        }
+    }
+    let manager = match manager {
+        Some(manager) => manager,
+        None => {
+            if cli.is_present("verbose") {
+                diagln!("Open simulated device.");
+            }
+            return Ok(Box::new(SimulatedDmm::new()));
+        }
+    };
+    let mut devices = manager.find(Some(VENDOR_ID), Some(PRODUCT_ID));
+    if let Some(path) = cli.value_of("device").or(default_path) {
+        if cli.is_present("verbose") {
+            diagln!("Open device at path '{}'.", path);
+        }
+        let p = Path::new(path);
+        for device in devices {
+            if device.path() == p {
+                return Ok(Box::new(Dmm::new(device.open()?)?));
+            }
+        }
+    } else {
+        if cli.is_present("verbose") {
+            diagln!("Open first found device.");
+        }
+        if let Some(device) = devices.next() {
+            return Ok(Box::new(Dmm::new(device.open()?)?));
+        }
+    }
+    Err(ErrorKind::DmmIsNotFound.into())
+}
+
+fn open_dmm(
+    manager: Option<&hid::Manager>,
+    cli: &ArgMatches,
+    default_path: Option<&str>,
+    allow_daemon: bool,
+) -> Result<Box<dyn Meter>> {
+    let dmm = open_device(manager, cli, default_path, allow_daemon)?;
+    match cli.value_of("capture") {
+        Some(path) => {
+            if cli.is_present("verbose") {
+                diagln!("Capture DMM traffic to '{}'.", path);
+            }
+            Ok(Box::new(CaptureMeter::new(dmm, Path::new(path))?))
+        }
+        None => Ok(dmm),
+    }
+}
+
+/// DMM opened on first use and kept open until the end (e.g. for `shell`).
+pub(crate) struct Device<'a> {
+    pub(crate) manager: Option<&'a hid::Manager>,
+    /// Device path of config, opened if `--device` isn't given and daemon isn't running.
+    default_path: Option<&'a str>,
+    /// Use running daemon instead of opening device (`false` for `daemon` itself).
+    pub(crate) allow_daemon: bool,
+    dmm: Option<Box<dyn Meter>>,
+}
+
+impl<'a> Device<'a> {
+    /// `manager` is `None` for simulated or replayed device.
+    pub(crate) fn new(
+        manager: Option<&'a hid::Manager>,
+        default_path: Option<&'a str>,
+    ) -> Device<'a> {
+        Device {
+            manager,
+            default_path,
+            allow_daemon: true,
+            dmm: None,
+        }
+    }
+
+    /// Returns opened DMM, opens it on first call.
+    pub(crate) fn get(&mut self, cli: &ArgMatches) -> Result<&mut dyn Meter> {
+        let dmm = match self.dmm.take() {
+            Some(dmm) => dmm,
+            None => open_dmm(self.manager, cli, self.default_path, self.allow_daemon)?,
+        };
+        Ok(&mut **self.dmm.get_or_insert(dmm))
+    }
+
+    /// Closes DMM (e.g. after disconnect), next `get()` reopens it.
+    pub(crate) fn close(&mut self) {
+        self.dmm = None;
+    }
+}
@@ -0,0 +1,24 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Prints verbose diagnostics (`-v`) to stdout, or to stderr while
+/// machine-readable output (JSON, CSV) is written to stdout.
+macro_rules! diagln {
+    ($($arg:tt)*) => {
+        if $crate::diag::to_stderr() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Sends diagnostics to stderr if stdout carries machine-readable output.
+pub(crate) fn init(to_stderr: bool) {
+    TO_STDERR.store(to_stderr, Ordering::Relaxed);
+}
+
+pub(crate) fn to_stderr() -> bool {
+    TO_STDERR.load(Ordering::Relaxed)
+}
@@ -1,302 +1,94 @@
 use std::time::Duration;
 
-use ut181a::{Measurement, Mode, Range};
+use ut181a::{Measurement, Mode, Range, Value};
 
+use accuracy::{self, format_uncertainty, COVERAGE};
+use color::{self, paint};
 use error::*;
+use measurement::{is_overload, main_value};
+use ranges::{mode_ranges, range_info};
 
+/// Formats duration as `h:mm:ss` (with days prefix if it's longer than a day).
 pub(crate) fn format_duration(d: Duration) -> String {
-    let s = d.as_secs() % 60;
-    let m = (d.as_secs() - s) / 60;
-    let h = (d.as_secs() - (m * 60) - s) / 3600;
+    let secs = d.as_secs();
+    let (days, h, m, s) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
 
-    format!("{}:{:02}:{:02}", h, m, s)
-}
-
-fn display_mvdc_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("-60...60 mV"),
-        Range::Step2 => print!("-600...600 mV"),
-        _ => {
-            return Err("Unused range step".into());
-        }
-    }
-    Ok(())
-}
-
-fn display_mvac_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("0...60 mV"),
-        Range::Step2 => print!("0...600 mV"),
-        _ => {
-            return Err("Unused range step".into());
-        }
-    }
-    Ok(())
-}
-
-fn display_vdc_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("-6...6 V"),
-        Range::Step2 => print!("-60...60 V"),
-        Range::Step3 => print!("-600...600 V"),
-        Range::Step4 => print!("-1000...1000 V"),
-        _ => {
-            return Err("Unused range step".into());
-        }
-    }
-    Ok(())
-}
-
-fn display_vac_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("0...6 V"),
-        Range::Step2 => print!("0...60 V"),
-        Range::Step3 => print!("0...600 V"),
-        Range::Step4 => print!("0...1000 V"),
-        _ => {
-            return Err("Unused range step".into());
-        }
-    }
-    Ok(())
-}
-
-fn display_no_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("-"),
-        _ => {
-            return Err("Unused range step".into());
-        }
+    if days > 0 {
+        format!("{}d {}:{:02}:{:02}", days, h, m, s)
+    } else {
+        format!("{}:{:02}:{:02}", h, m, s)
     }
-    Ok(())
 }
 
-fn display_ohm_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("0...600 Ohm"),
-        Range::Step2 => print!("0...6 kOhm"),
-        Range::Step3 => print!("0...60 kOhm"),
-        Range::Step4 => print!("0...600 kOhm"),
-        Range::Step5 => print!("0...6 MOhm"),
-        Range::Step6 => print!("0...60 MOhm"),
-        _ => {
-            return Err("Unused range step".into());
-        }
+/// Formats numeric part of value with its precision (without unit).
+pub(crate) fn format_number(v: &Value) -> String {
+    match v.precision {
+        Some(prc) => format!("{:.*}", prc, v.value),
+        None => format!("{}", v.value),
     }
-    Ok(())
 }
 
-fn display_siemens_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("0...60 nS"),
-        _ => {
-            return Err("Unused range step".into());
-        }
+/// Value of reading (overload is highlighted).
+fn display_value(v: &Value) -> String {
+    if is_overload(v) {
+        paint(color::overload(), v)
+    } else {
+        paint(color::value(), v)
     }
-    Ok(())
-}
-
-fn display_diode_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("0...3 V"),
-        _ => {
-            return Err("Unused range step".into());
-        }
-    }
-    Ok(())
 }
 
-fn display_farad_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("0...6 nF"),
-        Range::Step2 => print!("0...60 nF"),
-        Range::Step3 => print!("0...600 nF"),
-        Range::Step4 => print!("0...6 uF"),
-        Range::Step5 => print!("0...60 uF"),
-        Range::Step6 => print!("0...600 uF"),
-        Range::Step7 => print!("0...6 mF"),
-        Range::Step8 => print!("0...60 mF"),
-        _ => {
-            return Err("Unused range step".into());
-        }
+/// Relative delta with explicit sign (green if positive, red if negative).
+fn display_delta(v: &Value) -> String {
+    if is_overload(v) {
+        paint(color::overload(), v)
+    } else if v.value > 0.0 {
+        paint(color::positive(), format!("+{}", v))
+    } else if v.value < 0.0 {
+        paint(color::negative(), v)
+    } else {
+        v.to_string()
     }
-    Ok(())
 }
 
-fn display_hz_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("0...60 Hz"),
-        Range::Step2 => print!("0...600 Hz"),
-        Range::Step3 => print!("0...6 kHz"),
-        Range::Step4 => print!("0...60 kHz"),
-        Range::Step5 => print!("0...600 kHz"),
-        Range::Step6 => print!("0...6 MHz"),
-        Range::Step7 => print!("0...60 MHz"),
-        _ => {
-            return Err("Unused range step".into());
-        }
+/// ` ± U unit (k=2)` of main value (empty if it's disabled or unknown).
+fn display_uncertainty(m: &Measurement, uncertainty: bool) -> String {
+    match accuracy::uncertainty(m) {
+        Some(u) if uncertainty => format!(
+            " ± {} {} (k={})",
+            format_uncertainty(u),
+            main_value(m).unit,
+            COVERAGE
+        ),
+        _ => String::new(),
     }
-    Ok(())
 }
 
-fn display_uadc_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("-600...600 uA"),
-        Range::Step2 => print!("-6000...6000 uA"),
-        _ => {
-            return Err("Unused range step".into());
-        }
-    }
-    Ok(())
+fn display_mode(mode: Mode, is_holded: bool, is_auto_range: bool) {
+    let display_auto = if is_auto_range { "AUTO" } else { "" };
+    let display_hold = if is_holded {
+        paint(color::hold(), "HOLD")
+    } else {
+        String::new()
+    };
+    println!("Mode: {} [{}] [{}]", mode, display_hold, display_auto);
 }
 
-fn display_uaac_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("0...600 uA"),
-        Range::Step2 => print!("0...6000 uA"),
-        _ => {
-            return Err("Unused range step".into());
-        }
+/// Formats range of mode (`-` if mode has no ranges).
+fn format_range(m: Mode, r: Range) -> Result<String> {
+    match range_info(m, r) {
+        Some(info) => Ok(info.to_string()),
+        None if mode_ranges(m).is_empty() && r == Range::Step1 => Ok("-".into()),
+        None => Err("Unused range step".into()),
     }
-    Ok(())
 }
 
-fn display_madc_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("-60...60 mA"),
-        Range::Step2 => print!("-600...600 mA"),
-        _ => {
-            return Err("Unused range step".into());
-        }
-    }
-    Ok(())
-}
-
-fn display_maac_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("0...60 mA"),
-        Range::Step2 => print!("0...600 mA"),
-        _ => {
-            return Err("Unused range step".into());
-        }
-    }
-    Ok(())
-}
-
-fn display_adc_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("-20...20 A"),
-        _ => {
-            return Err("Unused range step".into());
-        }
-    }
-    Ok(())
-}
-
-fn display_aac_range(r: Range) -> Result<()> {
-    match r {
-        Range::Step1 => print!("0...20 A"),
-        _ => {
-            return Err("Unused range step".into());
-        }
-    }
-    Ok(())
-}
-
-fn display_range(m: Mode, r: Range) -> Result<()> {
-    match m {
-        Mode::mVDC_Normal | Mode::mVDC_Normal_Rel | Mode::mVDC_Peak => display_mvdc_range(r),
-
-        Mode::mVAC_Normal
-        | Mode::mVAC_Normal_Rel
-        | Mode::mVAC_Peak
-        | Mode::mVAC_Hz
-        | Mode::mVAC_AC_DC
-        | Mode::mVAC_AC_DC_Rel => display_mvac_range(r),
-
-        Mode::VDC_Normal
-        | Mode::VDC_Normal_Rel
-        | Mode::VDC_Peak
-        | Mode::VDC_AC_DC
-        | Mode::VDC_AC_DC_Rel => display_vdc_range(r),
-
-        Mode::VAC_Normal
-        | Mode::VAC_Normal_Rel
-        | Mode::VAC_Peak
-        | Mode::VAC_Hz
-        | Mode::VAC_LowPass
-        | Mode::VAC_LowPass_Rel
-        | Mode::VAC_dBV
-        | Mode::VAC_dBV_Rel
-        | Mode::VAC_dBm
-        | Mode::VAC_dBm_Rel => display_vac_range(r),
-
-        Mode::TempC_T1_T2
-        | Mode::TempC_T1_T2_Rel
-        | Mode::TempC_T2_T1
-        | Mode::TempC_T2_T1_Rel
-        | Mode::TempC_T1_T2_Diff
-        | Mode::TempC_T2_T1_Diff
-        | Mode::TempF_T1_T2
-        | Mode::TempF_T1_T2_Rel
-        | Mode::TempF_T2_T1
-        | Mode::TempF_T2_T1_Rel
-        | Mode::TempF_T1_T2_Diff
-        | Mode::TempF_T2_T1_Diff => display_no_range(r),
-        Mode::Resistance | Mode::Resistance_Rel | Mode::Beeper_Open | Mode::Beeper_Short => {
-            display_ohm_range(r)
-        }
-        Mode::Admittance | Mode::Admittance_Rel => display_siemens_range(r),
-        Mode::Diode_Normal | Mode::Diode_Alarm => display_diode_range(r),
-        Mode::Capacitance | Mode::Capacitance_Rel => display_farad_range(r),
-        Mode::Frequency
-        | Mode::Frequency_Rel
-        | Mode::DutyCycle
-        | Mode::DutyCycle_Rel
-        | Mode::PulseWidth
-        | Mode::PulseWidth_Rel => display_hz_range(r),
-
-        Mode::uADC_Normal
-        | Mode::uADC_Normal_Rel
-        | Mode::uADC_Peak
-        | Mode::uADC_AC_DC
-        | Mode::uADC_AC_DC_Rel => display_uadc_range(r),
-
-        Mode::uAAC_Normal | Mode::uAAC_Normal_Rel | Mode::uAAC_Peak | Mode::uAAC_Hz => {
-            display_uaac_range(r)
-        }
-
-        Mode::mADC_Normal
-        | Mode::mADC_Normal_Rel
-        | Mode::mADC_Peak
-        | Mode::mADC_AC_DC
-        | Mode::mADC_AC_DC_Rel => display_madc_range(r),
-
-        Mode::mAAC_Normal | Mode::mAAC_Normal_Rel | Mode::mAAC_Peak | Mode::mAAC_Hz => {
-            display_maac_range(r)
-        }
-
-        Mode::ADC_Normal
-        | Mode::ADC_Normal_Rel
-        | Mode::ADC_Peak
-        | Mode::ADC_AC_DC
-        | Mode::ADC_AC_DC_Rel => display_adc_range(r),
-
-        Mode::AAC_Normal | Mode::AAC_Normal_Rel | Mode::AAC_Peak | Mode::AAC_Hz => {
-            display_aac_range(r)
-        }
-    }
-}
-
-pub(crate) fn display_measurement(m: &Measurement) -> Result<()> {
-    match m {
-        &Measurement::Normal(ref meas) => {
-            let display_auto = if meas.is_auto_range { "AUTO" } else { "" };
-            let display_hold = if meas.is_holded { "HOLD" } else { "" };
-            println!("Mode: {} [{}] [{}]", meas.mode, display_hold, display_auto);
-            print!("Range: ");
-            display_range(meas.mode, meas.range)?;
-            println!();
-            println!("{}", meas.main);
+pub(crate) fn display_measurement(m: &Measurement, uncertainty: bool) -> Result<()> {
+    let u = display_uncertainty(m, uncertainty);
+    match *m {
+        Measurement::Normal(ref meas) => {
+            display_mode(meas.mode, meas.is_holded, meas.is_auto_range);
+            println!("Range: {}", format_range(meas.mode, meas.range)?);
+            println!("{}{}", display_value(&meas.main), u);
             if let Some(ref aux1_val) = meas.aux1 {
                 println!("AUX1: {}", aux1_val);
             }
@@ -307,45 +99,51 @@ pub(crate) fn display_measurement(m: &Measurement) -> Result<()> {
                 println!("FAST: {}", fast_val);
             }
         }
-        &Measurement::Relative(ref meas) => {
-            let display_auto = if meas.is_auto_range { "AUTO" } else { "" };
-            let display_hold = if meas.is_holded { "HOLD" } else { "" };
-            println!("Mode: {} [{}] [{}]", meas.mode, display_hold, display_auto);
-            print!("Range: ");
-            display_range(meas.mode, meas.range)?;
-            println!();
-            println!("REL: {}", meas.relative);
+        Measurement::Relative(ref meas) => {
+            display_mode(meas.mode, meas.is_holded, meas.is_auto_range);
+            println!("Range: {}", format_range(meas.mode, meas.range)?);
+            println!("REL: {}{}", display_delta(&meas.relative), u);
             println!("REFERENCE: {}", meas.reference);
-            println!("MEASUREMENT: {}", meas.measurement);
+            println!("MEASUREMENT: {}", display_value(&meas.measurement));
             if let Some(ref fast_val) = meas.fast {
                 println!("FAST: {}", fast_val);
             }
         }
-        &Measurement::MinMax(ref meas) => {
-            let display_auto = if meas.is_auto_range { "AUTO" } else { "" };
-            let display_hold = if meas.is_holded { "HOLD" } else { "" };
-            println!("Mode: {} [{}] [{}]", meas.mode, display_hold, display_auto);
-            print!("Range: ");
-            display_range(meas.mode, meas.range)?;
-            println!();
-            println!("{}", meas.main);
-            println!("MAXIMUM: {}\t{}", meas.max, format_duration(meas.max_time));
+        Measurement::MinMax(ref meas) => {
+            display_mode(meas.mode, meas.is_holded, meas.is_auto_range);
+            println!("Range: {}", format_range(meas.mode, meas.range)?);
+            println!("{}{}", display_value(&meas.main), u);
+            println!(
+                "{}: {}\t{}",
+                paint(color::maximum(), "MAXIMUM"),
+                paint(color::maximum(), &meas.max),
+                format_duration(meas.max_time)
+            );
             println!(
                 "AVERAGE: {}\t{}",
                 meas.average,
                 format_duration(meas.average_time)
             );
-            println!("MINIMUM: {}\t{}", meas.min, format_duration(meas.min_time));
+            println!(
+                "{}: {}\t{}",
+                paint(color::minimum(), "MINIMUM"),
+                paint(color::minimum(), &meas.min),
+                format_duration(meas.min_time)
+            );
         }
-        &Measurement::Peak(ref meas) => {
-            let display_auto = if meas.is_auto_range { "AUTO" } else { "" };
-            let display_hold = if meas.is_holded { "HOLD" } else { "" };
-            println!("Mode: {} [{}] [{}]", meas.mode, display_hold, display_auto);
-            print!("Range: ");
-            display_range(meas.mode, meas.range)?;
-            println!();
-            println!("PEAK MAX: {}", meas.max);
-            println!("PEAK MIN: {}", meas.min);
+        Measurement::Peak(ref meas) => {
+            display_mode(meas.mode, meas.is_holded, meas.is_auto_range);
+            println!("Range: {}", format_range(meas.mode, meas.range)?);
+            println!(
+                "{}: {}",
+                paint(color::maximum(), "PEAK MAX"),
+                paint(color::maximum(), &meas.max)
+            );
+            println!(
+                "{}: {}",
+                paint(color::minimum(), "PEAK MIN"),
+                paint(color::minimum(), &meas.min)
+            );
         }
     }
     Ok(())
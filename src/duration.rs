@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use error::*;
+
+fn unit_duration(unit: &str) -> Option<Duration> {
+    match unit {
+        "ms" => Some(Duration::from_millis(1)),
+        "s" => Some(Duration::from_secs(1)),
+        "m" | "min" => Some(Duration::from_secs(60)),
+        "h" => Some(Duration::from_secs(3600)),
+        "d" => Some(Duration::from_secs(86_400)),
+        _ => None,
+    }
+}
+
+/// Parses human-friendly duration (`500ms`, `90s`, `1h30m`, `2d`).
+///
+/// A bare number is multiplied by `default_unit`.
+pub(crate) fn parse_duration(s: &str, default_unit: Duration) -> Result<Duration> {
+    let s = s.trim();
+    let invalid = || Error::from(ErrorKind::InvalidDuration(s.to_owned()));
+    if s.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(n) = s.parse::<u32>() {
+        return Ok(default_unit * n);
+    }
+
+    let mut total = Duration::from_secs(0);
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        if digits == 0 {
+            return Err(invalid());
+        }
+        let n = rest[..digits].parse::<u32>().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = unit_duration(&rest[..unit_len]).ok_or_else(invalid)?;
+        total += unit * n;
+        rest = &rest[unit_len..];
+    }
+    Ok(total)
+}
+
+/// Formats duration in the form accepted by `parse_duration` (`1h30m`, `2d`).
+pub(crate) fn format_short_duration(d: Duration) -> String {
+    let mut rest = d.as_secs();
+    let mut s = String::new();
+    for &(unit, secs) in &[("d", 86_400), ("h", 3600), ("m", 60), ("s", 1)] {
+        if rest >= secs {
+            s += &format!("{}{}", rest / secs, unit);
+            rest %= secs;
+        }
+    }
+    let ms = d.subsec_millis();
+    if ms > 0 || s.is_empty() {
+        s += &format!("{}ms", ms);
+    }
+    s
+}
+
+/// Sampling interval limits of DMM records.
+const RECORD_INTERVAL: (u64, u64) = (1, 3600);
+
+/// Duration limits of DMM records (in minutes).
+const RECORD_DURATION: (u64, u64) = (1, 143_999);
+
+/// Parses record interval (seconds by default), checks it against DMM limits.
+pub(crate) fn parse_record_interval(s: &str) -> Result<u16> {
+    let d = parse_duration(s, Duration::from_secs(1))?;
+    if d.subsec_nanos() != 0 {
+        return Err(format!("record interval {} isn't a whole number of seconds", s).into());
+    }
+    let (min, max) = RECORD_INTERVAL;
+    if d.as_secs() < min || d.as_secs() > max {
+        return Err(format!(
+            "record interval {} is out of range ({}...{})",
+            s,
+            format_short_duration(Duration::from_secs(min)),
+            format_short_duration(Duration::from_secs(max))
+        )
+        .into());
+    }
+    Ok(d.as_secs() as u16)
+}
+
+/// Parses record duration (minutes by default), checks it against DMM limits.
+pub(crate) fn parse_record_duration(s: &str) -> Result<u32> {
+    let d = parse_duration(s, Duration::from_secs(60))?;
+    if d.subsec_nanos() != 0 || d.as_secs() % 60 != 0 {
+        return Err(format!("record duration {} isn't a whole number of minutes", s).into());
+    }
+    let (min, max) = RECORD_DURATION;
+    let minutes = d.as_secs() / 60;
+    if minutes < min || minutes > max {
+        return Err(format!(
+            "record duration {} is out of range ({}...{})",
+            s,
+            format_short_duration(Duration::from_secs(min * 60)),
+            format_short_duration(Duration::from_secs(max * 60))
+        )
+        .into());
+    }
+    Ok(minutes as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn duration_with_units() {
+        assert_eq!(
+            parse_duration("1h30m", SECOND).unwrap(),
+            Duration::from_secs(5400)
+        );
+        assert_eq!(
+            parse_duration("90s", SECOND).unwrap(),
+            Duration::from_secs(90)
+        );
+        assert_eq!(
+            parse_duration("2d", SECOND).unwrap(),
+            Duration::from_secs(172_800)
+        );
+    }
+
+    #[test]
+    fn bare_number_uses_default_unit() {
+        assert_eq!(
+            parse_duration("15", SECOND).unwrap(),
+            Duration::from_secs(15)
+        );
+        assert_eq!(
+            parse_duration("15", Duration::from_secs(60)).unwrap(),
+            Duration::from_secs(900)
+        );
+    }
+
+    #[test]
+    fn invalid_duration() {
+        for s in &["1.5h", "h", "10x", ""] {
+            assert!(parse_duration(s, SECOND).is_err(), "'{}' is accepted", s);
+        }
+    }
+
+    #[test]
+    fn record_interval_limits() {
+        assert!(parse_record_interval("0").is_err());
+        assert_eq!(parse_record_interval("1").unwrap(), 1);
+        assert_eq!(parse_record_interval("3600").unwrap(), 3600);
+        assert_eq!(parse_record_interval("1h").unwrap(), 3600);
+        assert!(parse_record_interval("3601").is_err());
+    }
+
+    #[test]
+    fn record_duration_in_whole_minutes() {
+        assert_eq!(parse_record_duration("90").unwrap(), 90);
+        assert_eq!(parse_record_duration("1h30m").unwrap(), 90);
+        assert!(parse_record_duration("90s").is_err());
+        assert!(parse_record_duration("0").is_err());
+    }
+}
@@ -1,18 +1,53 @@
+#![allow(deprecated)]
+
 error_chain! {
     links {
         Dmm(::ut181a::Error, ::ut181a::ErrorKind);
     }
     foreign_links {
         HidError(::hid::Error);
+        Io(::std::io::Error);
         Clap(::clap::Error);
         FloatParse(::std::num::ParseFloatError);
         IntParse(::std::num::ParseIntError);
+        Json(::serde_json::Error);
+        Sqlite(::rusqlite::Error);
+        Toml(::toml::de::Error);
+        Yaml(::serde_yaml::Error);
     }
     errors {
         DmmIsNotFound {
             description("DMM is not found")
             display("DMM is not found")
         }
+        InvalidDuration(s: String) {
+            description("Invalid duration")
+            display("invalid duration '{}' (use e.g. '500ms', '90s', '1h30m', '2d')", s)
+        }
+        InvalidQuantity(s: String) {
+            description("Invalid quantity")
+            display("invalid quantity '{}' (use e.g. '3.3V', '600mV', '4.7k')", s)
+        }
+        UnknownMode(name: String) {
+            description("Unknown mode")
+            display("unknown mode '{}' (see 'mode list')", name)
+        }
+        UnknownRange(name: String) {
+            description("Unknown range")
+            display("unknown range '{}' (use 'auto' or 'step1'...'step8')", name)
+        }
+        RangeIsNotAvailable(range: String, mode: String, valid: String) {
+            description("Range is not available in mode")
+            display("range '{}' is not available in {} mode (valid ranges: {})", range, mode, valid)
+        }
+        InvalidConfig(msg: String) {
+            description("Invalid config")
+            display("invalid config: {}", msg)
+        }
+        InvalidScript(msg: String) {
+            description("Invalid script")
+            display("invalid script: {}", msg)
+        }
         UnknownCliCommand(cmd: String) {
             description("Unknown CLI command error")
             display("unknown CLI command '{}'", cmd)
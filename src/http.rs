@@ -0,0 +1,461 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use error_chain::ChainedError;
+use serde_json::{self, Value as Json};
+use tiny_http::{Header, Method, ReadWrite, Request, Response, Server, StatusCode};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::{CloseFrame, Role};
+use tungstenite::{Message, WebSocket};
+
+use ut181a::{self, Measurement};
+
+use color;
+use error::*;
+use interrupt;
+use json::{
+    measurement_json, range_from_name, record_info_json, record_item_json, saved_measurement_json,
+};
+use meter::Meter;
+use modes::parse_mode;
+
+/// Period of checking Ctrl-C while there are no requests.
+const IDLE_PERIOD: u64 = 100; // milliseconds
+
+/// Event from HTTP server thread to DMM owner thread.
+enum Event {
+    Request(Request),
+    /// WebSocket client waiting for measurements.
+    Subscribe(Sender<Frame>),
+}
+
+/// Message to WebSocket client.
+enum Frame {
+    Measurement(String),
+    /// DMM failure, the stream is closed after it.
+    Error(String),
+}
+
+/// Error reply with HTTP status code.
+struct HttpError {
+    status: u16,
+    message: String,
+}
+
+impl HttpError {
+    fn new(status: u16, message: &str) -> HttpError {
+        HttpError {
+            status,
+            message: message.to_owned(),
+        }
+    }
+}
+
+/// HTTP status code of error: 4xx for invalid requests, 5xx for DMM failures.
+fn status_code(e: &Error) -> u16 {
+    match *e.kind() {
+        ErrorKind::Dmm(ref kind) => match *kind {
+            ut181a::ErrorKind::OutOfRange
+            | ut181a::ErrorKind::RecordNameTooLong(_)
+            | ut181a::ErrorKind::InvalidRecordName(_)
+            | ut181a::ErrorKind::RecordIntervalIsOutOfRange(_)
+            | ut181a::ErrorKind::RecordDurationIsOutOfRange(_) => 400,
+            ut181a::ErrorKind::CommandError => 409,
+            ut181a::ErrorKind::WaitTimeout => 504,
+            ut181a::ErrorKind::HidError(_)
+            | ut181a::ErrorKind::UartError(_)
+            | ut181a::ErrorKind::CommandWrite(_) => 503,
+            _ => 502,
+        },
+        ErrorKind::UnknownMode(_)
+        | ErrorKind::UnknownRange(_)
+        | ErrorKind::InvalidQuantity(_)
+        | ErrorKind::InvalidDuration(_)
+        | ErrorKind::Json(_)
+        | ErrorKind::FloatParse(_)
+        | ErrorKind::IntParse(_) => 400,
+        ErrorKind::DmmIsNotFound | ErrorKind::HidError(_) | ErrorKind::Io(_) => 503,
+        _ => 500,
+    }
+}
+
+impl From<Error> for HttpError {
+    fn from(e: Error) -> HttpError {
+        HttpError {
+            status: status_code(&e),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Monitoring failure: 504 if DMM doesn't respond, 503 otherwise.
+fn unavailable(e: Error) -> HttpError {
+    let status = match *e.kind() {
+        ErrorKind::Dmm(ut181a::ErrorKind::WaitTimeout) => 504,
+        _ => 503,
+    };
+    HttpError {
+        status,
+        message: e.to_string(),
+    }
+}
+
+/// Reply body (`None` for 204 No Content).
+type Reply = ::std::result::Result<Option<Json>, HttpError>;
+
+fn bad_request(message: &str) -> HttpError {
+    HttpError::new(400, message)
+}
+
+fn index(s: &str) -> ::std::result::Result<u16, HttpError> {
+    s.parse::<u16>()
+        .map_err(|_| HttpError::new(404, "not found"))
+}
+
+fn field<'a>(body: &'a Json, name: &str) -> ::std::result::Result<&'a Json, HttpError> {
+    match body.get(name) {
+        Some(v) if !v.is_null() => Ok(v),
+        _ => Err(bad_request(&format!("missing field '{}'", name))),
+    }
+}
+
+fn str_field<'a>(body: &'a Json, name: &str) -> ::std::result::Result<&'a str, HttpError> {
+    field(body, name)?
+        .as_str()
+        .ok_or_else(|| bad_request(&format!("field '{}' must be a string", name)))
+}
+
+fn u64_field(body: &Json, name: &str) -> ::std::result::Result<u64, HttpError> {
+    field(body, name)?
+        .as_u64()
+        .ok_or_else(|| bad_request(&format!("field '{}' must be a positive integer", name)))
+}
+
+/// REST API over DMM.
+struct Api<'a> {
+    dmm: &'a mut dyn Meter,
+    verbose: bool,
+    /// DMM is in monitor mode (there are WebSocket clients).
+    monitoring: bool,
+    last: Option<Measurement>,
+}
+
+impl<'a> Api<'a> {
+    fn handle(&mut self, mut request: Request) {
+        let result = self.route(&mut request);
+        let (status, body) = match result {
+            Ok(Some(body)) => (200, Some(body)),
+            Ok(None) => (204, None),
+            Err(e) => (e.status, Some(json!({ "error": e.message }))),
+        };
+        if self.verbose {
+            diagln!("{} {} -> {}", request.method(), request.url(), status);
+        }
+        let response = match body {
+            Some(body) => {
+                let header = Header::from_bytes("Content-Type", "application/json")
+                    .expect("valid Content-Type header");
+                Response::from_string(body.to_string()).with_header(header)
+            }
+            None => Response::from_string(""),
+        };
+        // Client can close connection before response.
+        let _ = request.respond(response.with_status_code(StatusCode(status)));
+    }
+
+    fn route(&mut self, request: &mut Request) -> Reply {
+        let mut body = String::new();
+        request
+            .as_reader()
+            .read_to_string(&mut body)
+            .map_err(Error::from)?;
+        let body: Json = if body.trim().is_empty() {
+            Json::Null
+        } else {
+            serde_json::from_str(&body).map_err(|e| bad_request(&e.to_string()))?
+        };
+        let url = request.url().to_owned();
+        let path: Vec<&str> = url
+            .split('?')
+            .next()
+            .unwrap_or("")
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        match (request.method(), path.as_slice()) {
+            (&Method::Get, ["measurement"]) => self.measurement(),
+            (&Method::Put, ["mode"]) => {
+                let mode = parse_mode(str_field(&body, "mode")?)?;
+                self.control(|dmm| dmm.set_mode(mode))
+            }
+            (&Method::Put, ["range"]) => {
+                let range = range_from_name(str_field(&body, "range")?)?;
+                self.control(|dmm| dmm.set_range(range))
+            }
+            (&Method::Post, ["hold"]) => self.control(|dmm| dmm.toggle_hold()),
+            (&Method::Put, ["min-max"]) => {
+                let on = field(&body, "on")?
+                    .as_bool()
+                    .ok_or_else(|| bad_request("field 'on' must be a boolean"))?;
+                self.control(|dmm| dmm.set_min_max_mode(on))
+            }
+            (&Method::Put, ["ref"]) => {
+                let value = field(&body, "value")?
+                    .as_f64()
+                    .ok_or_else(|| bad_request("field 'value' must be a number"))?;
+                self.control(|dmm| dmm.set_reference_value(value as f32))
+            }
+            (&Method::Get, ["saved"]) => self.query(|dmm| {
+                let count = dmm.get_saved_measurement_count()?;
+                let mut saves = Vec::new();
+                for i in 1..(count + 1) {
+                    let (t, m) = dmm.get_saved_measurement(i)?;
+                    saves.push(saved_measurement_json(i, &t, &m));
+                }
+                Ok(Json::Array(saves))
+            }),
+            (&Method::Post, ["saved"]) => self.control(|dmm| dmm.save_measurement()),
+            (&Method::Delete, ["saved"]) => self.control(|dmm| dmm.delete_all_saved_measurement()),
+            (&Method::Get, ["saved", n]) => {
+                let n = index(n)?;
+                self.query(|dmm| {
+                    let (t, m) = dmm.get_saved_measurement(n)?;
+                    Ok(saved_measurement_json(n, &t, &m))
+                })
+            }
+            (&Method::Delete, ["saved", n]) => {
+                let n = index(n)?;
+                self.control(|dmm| dmm.delete_saved_measurement(n))
+            }
+            (&Method::Get, ["records"]) => self.query(|dmm| {
+                let count = dmm.get_record_count()?;
+                let mut infos = Vec::new();
+                for i in 1..(count + 1) {
+                    infos.push(record_info_json(i, &dmm.get_record_info(i)?));
+                }
+                Ok(Json::Array(infos))
+            }),
+            (&Method::Post, ["records"]) => {
+                let name = str_field(&body, "name")?.to_owned();
+                let interval = u64_field(&body, "interval")?;
+                let duration = u64_field(&body, "duration")?;
+                if interval > u64::from(u16::MAX) || duration > u64::from(u32::MAX) {
+                    return Err(bad_request("record interval or duration is out of range"));
+                }
+                self.control(|dmm| dmm.start_record(&name, interval as u16, duration as u32))
+            }
+            (&Method::Post, ["records", "stop"]) => self.control(|dmm| dmm.stop_record()),
+            (&Method::Get, ["records", n]) => {
+                let n = index(n)?;
+                self.query(|dmm| {
+                    let mut record = record_info_json(n, &dmm.get_record_info(n)?);
+                    let items = dmm.get_record_data(n)?;
+                    record["data"] = Json::Array(items.iter().map(record_item_json).collect());
+                    Ok(record)
+                })
+            }
+            _ => Err(HttpError::new(404, "not found")),
+        }
+    }
+
+    /// Executes command (DMM ignores commands in monitor mode).
+    fn query<F>(&mut self, f: F) -> Reply
+    where
+        F: FnOnce(&mut dyn Meter) -> Result<Json>,
+    {
+        if self.monitoring {
+            self.dmm.monitor_off().map_err(unavailable)?;
+        }
+        let result = f(&mut *self.dmm);
+        if self.monitoring {
+            self.dmm.monitor_on().map_err(unavailable)?;
+        }
+        Ok(Some(result?))
+    }
+
+    fn control<F>(&mut self, f: F) -> Reply
+    where
+        F: FnOnce(&mut dyn Meter) -> Result<()>,
+    {
+        self.query(|dmm| f(dmm).map(|_| Json::Null)).map(|_| None)
+    }
+
+    /// The latest measurement while streaming, otherwise reads one.
+    fn measurement(&mut self) -> Reply {
+        if self.monitoring {
+            if let Some(ref m) = self.last {
+                return Ok(Some(measurement_json(m)));
+            }
+            let m = self.dmm.get_measurement().map_err(unavailable)?;
+            return Ok(Some(measurement_json(&m)));
+        }
+        self.dmm.monitor_on().map_err(unavailable)?;
+        let result = self.dmm.get_measurement();
+        self.dmm.monitor_off().map_err(unavailable)?;
+        Ok(Some(measurement_json(&result.map_err(unavailable)?)))
+    }
+}
+
+fn is_websocket(request: &Request) -> bool {
+    request.url() == "/ws"
+        && request
+            .headers()
+            .iter()
+            .any(|h| h.field.equiv("Upgrade") && h.value.as_str().eq_ignore_ascii_case("websocket"))
+}
+
+/// Completes WebSocket handshake and sends received measurements to client.
+fn stream_measurements(request: Request, events: &Sender<Event>) {
+    let key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+        .map(|h| derive_accept_key(h.value.as_bytes()));
+    let key = match key {
+        Some(key) => key,
+        None => {
+            let _ = request.respond(Response::from_string("").with_status_code(400));
+            return;
+        }
+    };
+    let response = Response::empty(101)
+        .with_header(Header::from_bytes("Connection", "Upgrade").expect("valid header"))
+        .with_header(Header::from_bytes("Sec-WebSocket-Accept", key).expect("valid header"));
+    let stream = request.upgrade("websocket", response);
+
+    let (tx, rx) = mpsc::channel();
+    if events.send(Event::Subscribe(tx)).is_err() {
+        return;
+    }
+    thread::spawn(move || send_messages(stream, &rx));
+}
+
+fn send_messages(stream: Box<dyn ReadWrite + Send>, messages: &Receiver<Frame>) {
+    let mut ws = WebSocket::from_raw_socket(stream, Role::Server, None);
+    for frame in messages {
+        match frame {
+            Frame::Measurement(text) => {
+                if ws.send(Message::Text(text)).is_err() {
+                    // Client is disconnected, dropped receiver unsubscribes it.
+                    break;
+                }
+            }
+            Frame::Error(message) => {
+                let text = json!({ "error": message }).to_string();
+                let _ = ws.send(Message::Text(text));
+                let _ = ws.close(Some(CloseFrame {
+                    code: CloseCode::Error,
+                    reason: "DMM failure".into(),
+                }));
+                let _ = ws.flush();
+                break;
+            }
+        }
+    }
+}
+
+/// Sends error to WebSocket clients and unsubscribes them.
+fn close_subscribers(subscribers: &mut Vec<Sender<Frame>>, e: &Error) {
+    for tx in subscribers.drain(..) {
+        let _ = tx.send(Frame::Error(e.to_string()));
+    }
+}
+
+fn serve_http(server: &Server, events: &Sender<Event>) {
+    for request in server.incoming_requests() {
+        if is_websocket(&request) {
+            stream_measurements(request, events);
+        } else if events.send(Event::Request(request)).is_err() {
+            break;
+        }
+    }
+}
+
+/// Serves REST API and WebSocket stream of measurements on `listen` address
+/// until Ctrl-C is pressed.
+pub(crate) fn serve(dmm: &mut dyn Meter, listen: &str, verbose: bool) -> Result<()> {
+    let server = Arc::new(
+        Server::http(listen)
+            .map_err(|e| Error::from(format!("can't listen on '{}': {}", listen, e)))?,
+    );
+    let (tx, rx) = mpsc::channel();
+    let http = {
+        let server = server.clone();
+        thread::spawn(move || serve_http(&server, &tx))
+    };
+    println!("Serving API on http://{}/", listen);
+
+    interrupt::catch()?;
+    let result = serve_events(dmm, &rx, verbose);
+    server.unblock();
+    let _ = http.join();
+    result
+}
+
+fn serve_events(dmm: &mut dyn Meter, events: &Receiver<Event>, verbose: bool) -> Result<()> {
+    let mut api = Api {
+        dmm,
+        verbose,
+        monitoring: false,
+        last: None,
+    };
+    let mut subscribers: Vec<Sender<Frame>> = Vec::new();
+    // DMM errors only close streams (REST requests report their own ones).
+    while !interrupt::is_interrupted() {
+        let wanted = !subscribers.is_empty();
+        if wanted != api.monitoring {
+            if verbose {
+                diagln!("Streaming is {}.", if wanted { "on" } else { "off" });
+            }
+            if wanted {
+                match api.dmm.monitor_on() {
+                    Ok(()) => api.monitoring = true,
+                    Err(e) => close_subscribers(&mut subscribers, &e),
+                }
+            } else {
+                if let Err(e) = api.dmm.monitor_off() {
+                    // Nobody to report to, the next request reports DMM failure if any.
+                    color::eprint_error(e.display_chain());
+                }
+                api.monitoring = false;
+                api.last = None;
+            }
+        }
+
+        let timeout = if api.monitoring { 0 } else { IDLE_PERIOD };
+        let mut next = match events.recv_timeout(Duration::from_millis(timeout)) {
+            Ok(event) => Some(event),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        while let Some(event) = next {
+            match event {
+                Event::Request(request) => api.handle(request),
+                Event::Subscribe(tx) => subscribers.push(tx),
+            }
+            next = events.try_recv().ok();
+        }
+
+        if api.monitoring {
+            match api.dmm.get_measurement() {
+                Ok(m) => {
+                    let text = measurement_json(&m).to_string();
+                    subscribers.retain(|tx| tx.send(Frame::Measurement(text.clone())).is_ok());
+                    api.last = Some(m);
+                }
+                Err(e) => {
+                    close_subscribers(&mut subscribers, &e);
+                    api.last = None;
+                }
+            }
+        }
+    }
+    if api.monitoring {
+        api.dmm.monitor_off()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,26 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+
+use error::*;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static INSTALL: Once = Once::new();
+
+/// Catches Ctrl-C instead of terminating the process.
+///
+/// Ctrl-C handler is installed once per process and the flag is reset
+/// on every call, so long-running commands can be repeated (e.g. in `shell`).
+pub(crate) fn catch() -> Result<()> {
+    let mut result = Ok(());
+    INSTALL.call_once(|| {
+        result = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))
+            .map_err(|e| Error::from(format!("can't set Ctrl-C handler: {}", e)));
+    });
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    result
+}
+
+/// Returns `true` if Ctrl-C was pressed after last `catch()`.
+pub(crate) fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,370 @@
+use std::time::Duration;
+
+use serde_json::{Map, Number, Value as Json};
+
+use ut181a::{
+    Measurement, MinMaxMeasurement, NormalMeasurement, PeakMeasurement, Range, RecordDataItem,
+    RecordInfo, RelativeMeasurement, Unit, UnitExp, Value,
+};
+
+use chrono::NaiveDateTime;
+
+use display_measurement::format_number;
+use error::*;
+use modes::parse_mode;
+
+pub(crate) fn format_timestamp(t: &NaiveDateTime) -> String {
+    t.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+pub(crate) fn range_name(r: Range) -> &'static str {
+    match r {
+        Range::Auto => "auto",
+        Range::Step1 => "step1",
+        Range::Step2 => "step2",
+        Range::Step3 => "step3",
+        Range::Step4 => "step4",
+        Range::Step5 => "step5",
+        Range::Step6 => "step6",
+        Range::Step7 => "step7",
+        Range::Step8 => "step8",
+    }
+}
+
+fn duration_json(d: Duration) -> Json {
+    Json::from(d.as_secs())
+}
+
+/// Converts `f32` to JSON number keeping only significant digits.
+fn number_json(v: &Value) -> Json {
+    format_number(v)
+        .parse::<f64>()
+        .ok()
+        .and_then(Number::from_f64)
+        .map(Json::Number)
+        .unwrap_or(Json::Null)
+}
+
+pub(crate) fn value_json(v: &Value) -> Json {
+    let mut obj = Map::new();
+    obj.insert("value".into(), number_json(v));
+    obj.insert("unit".into(), Json::from(v.unit.to_string()));
+    obj.insert(
+        "precision".into(),
+        v.precision.map(Json::from).unwrap_or(Json::Null),
+    );
+    obj.insert("overload_pos".into(), Json::from(v.overload_pos));
+    obj.insert("overload_neg".into(), Json::from(v.overload_neg));
+    Json::Object(obj)
+}
+
+fn optional_value_json(v: &Option<Value>) -> Json {
+    v.as_ref().map(value_json).unwrap_or(Json::Null)
+}
+
+pub(crate) fn measurement_json(m: &Measurement) -> Json {
+    let mut obj = Map::new();
+    match *m {
+        Measurement::Normal(ref meas) => {
+            obj.insert("kind".into(), Json::from("normal"));
+            obj.insert("mode".into(), Json::from(meas.mode.to_string()));
+            obj.insert("range".into(), Json::from(range_name(meas.range)));
+            obj.insert("hold".into(), Json::from(meas.is_holded));
+            obj.insert("auto_range".into(), Json::from(meas.is_auto_range));
+            obj.insert("main".into(), value_json(&meas.main));
+            obj.insert("aux1".into(), optional_value_json(&meas.aux1));
+            obj.insert("aux2".into(), optional_value_json(&meas.aux2));
+            obj.insert("fast".into(), optional_value_json(&meas.fast));
+        }
+        Measurement::Relative(ref meas) => {
+            obj.insert("kind".into(), Json::from("relative"));
+            obj.insert("mode".into(), Json::from(meas.mode.to_string()));
+            obj.insert("range".into(), Json::from(range_name(meas.range)));
+            obj.insert("hold".into(), Json::from(meas.is_holded));
+            obj.insert("auto_range".into(), Json::from(meas.is_auto_range));
+            obj.insert("relative".into(), value_json(&meas.relative));
+            obj.insert("reference".into(), value_json(&meas.reference));
+            obj.insert("measurement".into(), value_json(&meas.measurement));
+            obj.insert("fast".into(), optional_value_json(&meas.fast));
+        }
+        Measurement::MinMax(ref meas) => {
+            obj.insert("kind".into(), Json::from("min_max"));
+            obj.insert("mode".into(), Json::from(meas.mode.to_string()));
+            obj.insert("range".into(), Json::from(range_name(meas.range)));
+            obj.insert("hold".into(), Json::from(meas.is_holded));
+            obj.insert("auto_range".into(), Json::from(meas.is_auto_range));
+            obj.insert("main".into(), value_json(&meas.main));
+            obj.insert("max".into(), value_json(&meas.max));
+            obj.insert("max_time".into(), duration_json(meas.max_time));
+            obj.insert("average".into(), value_json(&meas.average));
+            obj.insert("average_time".into(), duration_json(meas.average_time));
+            obj.insert("min".into(), value_json(&meas.min));
+            obj.insert("min_time".into(), duration_json(meas.min_time));
+        }
+        Measurement::Peak(ref meas) => {
+            obj.insert("kind".into(), Json::from("peak"));
+            obj.insert("mode".into(), Json::from(meas.mode.to_string()));
+            obj.insert("range".into(), Json::from(range_name(meas.range)));
+            obj.insert("hold".into(), Json::from(meas.is_holded));
+            obj.insert("auto_range".into(), Json::from(meas.is_auto_range));
+            obj.insert("max".into(), value_json(&meas.max));
+            obj.insert("min".into(), value_json(&meas.min));
+        }
+    }
+    Json::Object(obj)
+}
+
+pub(crate) fn saved_measurement_json(index: u16, t: &NaiveDateTime, m: &Measurement) -> Json {
+    let mut obj = Map::new();
+    obj.insert("index".into(), Json::from(index));
+    obj.insert("timestamp".into(), Json::from(format_timestamp(t)));
+    obj.insert("measurement".into(), measurement_json(m));
+    Json::Object(obj)
+}
+
+pub(crate) fn record_info_json(index: u16, info: &RecordInfo) -> Json {
+    let mut obj = Map::new();
+    obj.insert("index".into(), Json::from(index));
+    obj.insert("name".into(), Json::from(info.name.clone()));
+    obj.insert("unit".into(), Json::from(info.unit.to_string()));
+    obj.insert("start".into(), Json::from(format_timestamp(&info.start)));
+    obj.insert("interval".into(), duration_json(info.interval));
+    obj.insert("duration".into(), duration_json(info.duration));
+    obj.insert("sample_count".into(), Json::from(info.sample_count));
+    obj.insert("max".into(), value_json(&info.max));
+    obj.insert("average".into(), value_json(&info.average));
+    obj.insert("min".into(), value_json(&info.min));
+    Json::Object(obj)
+}
+
+pub(crate) fn record_item_json(item: &RecordDataItem) -> Json {
+    let mut obj = Map::new();
+    obj.insert(
+        "timestamp".into(),
+        Json::from(format_timestamp(&item.timestamp)),
+    );
+    obj.insert("value".into(), value_json(&item.value));
+    Json::Object(obj)
+}
+
+/// Reply of DMM command returning nothing.
+pub(crate) fn unit_json(_: &()) -> Json {
+    Json::Null
+}
+
+/// Reply of DMM count command (`get_record_count`).
+pub(crate) fn count_json<T: Copy + Into<Json>>(v: &T) -> Json {
+    (*v).into()
+}
+
+/// Saved measurement without index (reply of `get_saved_measurement`).
+pub(crate) fn saved_json(save: &(NaiveDateTime, Measurement)) -> Json {
+    let mut j = saved_measurement_json(0, &save.0, &save.1);
+    if let Json::Object(ref mut obj) = j {
+        obj.remove("index");
+    }
+    j
+}
+
+pub(crate) fn record_data_json(items: &[RecordDataItem]) -> Json {
+    Json::Array(items.iter().map(record_item_json).collect())
+}
+
+fn invalid(what: &str) -> Error {
+    format!("invalid JSON: {}", what).into()
+}
+
+fn str_field<'a>(j: &'a Json, name: &str) -> Result<&'a str> {
+    j[name].as_str().ok_or_else(|| invalid(name))
+}
+
+fn bool_field(j: &Json, name: &str) -> Result<bool> {
+    j[name].as_bool().ok_or_else(|| invalid(name))
+}
+
+fn u64_field(j: &Json, name: &str) -> Result<u64> {
+    j[name].as_u64().ok_or_else(|| invalid(name))
+}
+
+fn duration_field(j: &Json, name: &str) -> Result<Duration> {
+    u64_field(j, name).map(Duration::from_secs)
+}
+
+pub(crate) fn parse_timestamp(s: &str) -> Result<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S")
+        .map_err(|_| invalid(&format!("timestamp '{}'", s)))
+}
+
+pub(crate) fn range_from_name(s: &str) -> Result<Range> {
+    match s {
+        "auto" => Ok(Range::Auto),
+        "step1" => Ok(Range::Step1),
+        "step2" => Ok(Range::Step2),
+        "step3" => Ok(Range::Step3),
+        "step4" => Ok(Range::Step4),
+        "step5" => Ok(Range::Step5),
+        "step6" => Ok(Range::Step6),
+        "step7" => Ok(Range::Step7),
+        "step8" => Ok(Range::Step8),
+        _ => Err(ErrorKind::UnknownRange(s.to_owned()).into()),
+    }
+}
+
+/// Parses unit in `UnitExp` display form (`mVDC`, `kOhm`, ...).
+pub(crate) fn unit_from_str(s: &str) -> Result<UnitExp> {
+    fn unit(s: &str) -> Option<Unit> {
+        match s {
+            "VDC" => Some(Unit::VDC),
+            "VAC" => Some(Unit::VAC),
+            "Vac+dc" => Some(Unit::VAcDc),
+            "ADC" => Some(Unit::ADC),
+            "AAC" => Some(Unit::AAC),
+            "Aac+dc" => Some(Unit::AAcDc),
+            "C" => Some(Unit::Celsius),
+            "F" => Some(Unit::F),
+            "Hz" => Some(Unit::Hz),
+            "s" => Some(Unit::s),
+            "%" => Some(Unit::Percent),
+            "S" => Some(Unit::S),
+            "Ohm" => Some(Unit::Ohm),
+            "dBm" => Some(Unit::dBm),
+            "dBV" => Some(Unit::dBV),
+            _ => None,
+        }
+    }
+
+    if let Some(u) = unit(s) {
+        // Capacitance is always displayed with prefix (nF, uF, mF).
+        let u = if u == Unit::F { Unit::Fahrenheit } else { u };
+        return Ok(UnitExp {
+            unit: u,
+            exponent: 0,
+        });
+    }
+    let mut chars = s.chars();
+    let exponent = match chars.next() {
+        Some('p') => -12,
+        Some('n') => -9,
+        Some('u') => -6,
+        Some('m') => -3,
+        Some('k') => 3,
+        Some('M') => 6,
+        Some('G') => 9,
+        _ => return Err(invalid(&format!("unit '{}'", s))),
+    };
+    match unit(chars.as_str()) {
+        Some(u) => Ok(UnitExp { unit: u, exponent }),
+        None => Err(invalid(&format!("unit '{}'", s))),
+    }
+}
+
+pub(crate) fn value_from_json(j: &Json) -> Result<Value> {
+    Ok(Value {
+        overload_neg: bool_field(j, "overload_neg")?,
+        overload_pos: bool_field(j, "overload_pos")?,
+        value: j["value"].as_f64().unwrap_or(f64::NAN) as f32,
+        precision: j["precision"].as_u64().map(|p| p as usize),
+        unit: unit_from_str(str_field(j, "unit")?)?,
+    })
+}
+
+fn optional_value_from_json(j: &Json) -> Result<Option<Value>> {
+    if j.is_null() {
+        Ok(None)
+    } else {
+        value_from_json(j).map(Some)
+    }
+}
+
+pub(crate) fn measurement_from_json(j: &Json) -> Result<Measurement> {
+    let mode_name = str_field(j, "mode")?;
+    let mode = parse_mode(mode_name).map_err(|_| invalid(&format!("mode '{}'", mode_name)))?;
+    let range = range_from_name(str_field(j, "range")?)?;
+    let is_holded = bool_field(j, "hold")?;
+    let is_auto_range = bool_field(j, "auto_range")?;
+    match str_field(j, "kind")? {
+        "normal" => Ok(Measurement::Normal(NormalMeasurement {
+            mode,
+            is_holded,
+            is_auto_range,
+            range,
+            main: value_from_json(&j["main"])?,
+            aux1: optional_value_from_json(&j["aux1"])?,
+            aux2: optional_value_from_json(&j["aux2"])?,
+            fast: optional_value_from_json(&j["fast"])?,
+        })),
+        "relative" => Ok(Measurement::Relative(RelativeMeasurement {
+            mode,
+            is_holded,
+            is_auto_range,
+            range,
+            relative: value_from_json(&j["relative"])?,
+            reference: value_from_json(&j["reference"])?,
+            measurement: value_from_json(&j["measurement"])?,
+            fast: optional_value_from_json(&j["fast"])?,
+        })),
+        "min_max" => Ok(Measurement::MinMax(MinMaxMeasurement {
+            mode,
+            is_holded,
+            is_auto_range,
+            range,
+            main: value_from_json(&j["main"])?,
+            max: value_from_json(&j["max"])?,
+            max_time: duration_field(j, "max_time")?,
+            average: value_from_json(&j["average"])?,
+            average_time: duration_field(j, "average_time")?,
+            min: value_from_json(&j["min"])?,
+            min_time: duration_field(j, "min_time")?,
+        })),
+        "peak" => Ok(Measurement::Peak(PeakMeasurement {
+            mode,
+            is_holded,
+            is_auto_range,
+            range,
+            max: value_from_json(&j["max"])?,
+            min: value_from_json(&j["min"])?,
+        })),
+        kind => Err(invalid(&format!("measurement kind '{}'", kind))),
+    }
+}
+
+pub(crate) fn saved_measurement_from_json(j: &Json) -> Result<(NaiveDateTime, Measurement)> {
+    Ok((
+        parse_timestamp(str_field(j, "timestamp")?)?,
+        measurement_from_json(&j["measurement"])?,
+    ))
+}
+
+pub(crate) fn record_info_from_json(j: &Json) -> Result<RecordInfo> {
+    Ok(RecordInfo {
+        name: str_field(j, "name")?.to_owned(),
+        unit: unit_from_str(str_field(j, "unit")?)?,
+        interval: duration_field(j, "interval")?,
+        duration: duration_field(j, "duration")?,
+        sample_count: u64_field(j, "sample_count")? as u32,
+        max: value_from_json(&j["max"])?,
+        average: value_from_json(&j["average"])?,
+        min: value_from_json(&j["min"])?,
+        start: parse_timestamp(str_field(j, "start")?)?,
+    })
+}
+
+pub(crate) fn record_item_from_json(j: &Json) -> Result<RecordDataItem> {
+    Ok(RecordDataItem {
+        value: value_from_json(&j["value"])?,
+        timestamp: parse_timestamp(str_field(j, "timestamp")?)?,
+    })
+}
+
+pub(crate) fn u16_from_json(j: &Json, call: &str) -> Result<u16> {
+    j.as_u64()
+        .map(|v| v as u16)
+        .ok_or_else(|| format!("invalid reply to '{}'", call).into())
+}
+
+pub(crate) fn record_data_from_json(j: &Json) -> Result<Vec<RecordDataItem>> {
+    match *j {
+        Json::Array(ref items) => items.iter().map(record_item_from_json).collect(),
+        _ => Err("invalid reply to 'get_record_data'".into()),
+    }
+}
@@ -0,0 +1,235 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use serde_json::Value as Json;
+
+use ut181a::Measurement;
+
+use accuracy::{self, add_uncertainty_json};
+use csv::{csv_field, csv_uncertainty, csv_value};
+use db::Db;
+use duration::parse_duration;
+use error::*;
+use interrupt;
+use json::{measurement_json, range_name};
+use measurement::{fast_value, is_auto_range, is_holded, main_value, mode, range};
+use meter::Meter;
+
+/// Written data is flushed to the file at least once per this period.
+const FLUSH_PERIOD: u64 = 1; // second
+
+const LOG_HEADER: &str = "time,mode,range,hold,auto_range,value,unit,flag,fast,fast_unit";
+
+/// Parses `--every` period (bare number is seconds), zero period is rejected.
+pub(crate) fn parse_every(s: &str) -> Result<Duration> {
+    let every = parse_duration(s, Duration::from_secs(1))?;
+    if every == Duration::ZERO {
+        return Err(format!("'--every {}' should be greater than zero", s).into());
+    }
+    Ok(every)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum LogFormat {
+    Csv,
+    Ndjson,
+}
+
+impl LogFormat {
+    /// Detects format by file extension (NDJSON by default).
+    pub(crate) fn from_path(path: &Path) -> LogFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => LogFormat::Csv,
+            _ => LogFormat::Ndjson,
+        }
+    }
+}
+
+pub(crate) struct LogOptions {
+    pub(crate) format: LogFormat,
+    /// Stop logging after this duration.
+    pub(crate) duration: Option<Duration>,
+    /// Stop logging after this count of written samples.
+    pub(crate) samples: Option<u64>,
+    /// Write one sample per this period at most.
+    pub(crate) every: Option<Duration>,
+    /// Add expanded uncertainty (k=2) of main value.
+    pub(crate) uncertainty: bool,
+}
+
+pub(crate) fn host_time() -> String {
+    Local::now().format("%Y-%m-%dT%H:%M:%S%.3f").to_string()
+}
+
+fn write_csv_row<W: Write>(
+    w: &mut W,
+    time: &str,
+    m: &Measurement,
+    uncertainty: bool,
+) -> Result<()> {
+    let (value, flag) = csv_value(main_value(m));
+    let (fast, fast_unit) = match fast_value(m) {
+        Some(v) => (csv_value(v).0, v.unit.to_string()),
+        None => (String::new(), String::new()),
+    };
+    write!(
+        w,
+        "{},{},{},{},{},{},{},{},{},{}",
+        time,
+        csv_field(&mode(m).to_string()),
+        range_name(range(m)),
+        is_holded(m),
+        is_auto_range(m),
+        value,
+        main_value(m).unit,
+        flag,
+        fast,
+        fast_unit
+    )?;
+    if uncertainty {
+        write!(w, ",{}", csv_uncertainty(accuracy::uncertainty(m)))?;
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+fn write_ndjson_row<W: Write>(
+    w: &mut W,
+    time: &str,
+    m: &Measurement,
+    uncertainty: bool,
+) -> Result<()> {
+    let mut j = measurement_json(m);
+    if uncertainty {
+        add_uncertainty_json(&mut j, m);
+    }
+    if let Json::Object(ref mut obj) = j {
+        obj.insert("time".into(), Json::from(time));
+    }
+    writeln!(w, "{}", j)?;
+    Ok(())
+}
+
+fn flush<W: Write>(out: Option<&mut W>, db: Option<&mut Db>) -> Result<()> {
+    if let Some(out) = out {
+        out.flush()?;
+    }
+    if let Some(db) = db {
+        db.flush()?;
+    }
+    Ok(())
+}
+
+/// Writes measurements to `path` and/or `db` until limits are reached or Ctrl-C is pressed.
+///
+/// Returns count of written samples.
+pub(crate) fn log(
+    dmm: &mut dyn Meter,
+    path: Option<&Path>,
+    mut db: Option<&mut Db>,
+    opts: &LogOptions,
+    verbose: bool,
+) -> Result<u64> {
+    interrupt::catch()?;
+
+    let mut out = match path {
+        Some(path) => Some(BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+    if let Some(ref mut out) = out {
+        match (opts.format, opts.uncertainty) {
+            (LogFormat::Csv, true) => writeln!(out, "{},uncertainty", LOG_HEADER)?,
+            (LogFormat::Csv, false) => writeln!(out, "{}", LOG_HEADER)?,
+            (LogFormat::Ndjson, _) => {}
+        }
+    }
+
+    if verbose {
+        diagln!("Sending 'MONITOR ON' command to DMM.");
+    }
+    dmm.monitor_on()?;
+
+    let started = Instant::now();
+    let mut last_flush = Instant::now();
+    let mut next_due = Instant::now();
+    let mut written = 0;
+    let result = loop {
+        if interrupt::is_interrupted() {
+            break Ok(());
+        }
+        if opts.duration.is_some_and(|d| started.elapsed() >= d) {
+            break Ok(());
+        }
+        if opts.samples.is_some_and(|n| written >= n) {
+            break Ok(());
+        }
+
+        if verbose {
+            diagln!("Reading a message from DMM.");
+        }
+        let m = match dmm.get_measurement() {
+            Ok(m) => m,
+            Err(e) => break Err(e),
+        };
+
+        if let Some(every) = opts.every {
+            let now = Instant::now();
+            if now < next_due {
+                continue;
+            }
+            while next_due <= now {
+                next_due += every;
+            }
+        }
+
+        let time = host_time();
+        let row = match (out.as_mut(), opts.format) {
+            (Some(out), LogFormat::Csv) => write_csv_row(out, &time, &m, opts.uncertainty),
+            (Some(out), LogFormat::Ndjson) => write_ndjson_row(out, &time, &m, opts.uncertainty),
+            (None, _) => Ok(()),
+        };
+        let row = row.and_then(|_| match db {
+            Some(ref mut db) => db.insert_reading(&time, &m),
+            None => Ok(()),
+        });
+        if let Err(e) = row {
+            break Err(e);
+        }
+        written += 1;
+
+        if last_flush.elapsed() >= Duration::from_secs(FLUSH_PERIOD) {
+            if let Err(e) = flush(out.as_mut(), db.as_deref_mut()) {
+                break Err(e);
+            }
+            last_flush = Instant::now();
+        }
+    };
+
+    flush(out.as_mut(), db)?;
+    if verbose {
+        diagln!("Sending 'MONITOR OFF' command to DMM.");
+    }
+    dmm.monitor_off()?;
+    result.map(|_| written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_rejects_zero() {
+        assert!(parse_every("0").is_err());
+        assert!(parse_every("0s").is_err());
+        assert!(parse_every("0ms").is_err());
+    }
+
+    #[test]
+    fn every_accepts_period() {
+        assert_eq!(parse_every("10").unwrap(), Duration::from_secs(10));
+        assert_eq!(parse_every("500ms").unwrap(), Duration::from_millis(500));
+    }
+}
@@ -1,88 +1,288 @@
 extern crate ansi_term;
+extern crate chrono;
 extern crate clap;
+extern crate crossterm;
+extern crate ctrlc;
 #[macro_use]
 extern crate error_chain;
 extern crate hid;
+extern crate resvg;
+extern crate rumqttc;
+extern crate rusqlite;
+extern crate rustyline;
+#[macro_use]
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate tiny_http;
+extern crate toml;
+extern crate tungstenite;
 extern crate ut181a;
 
+#[macro_use]
+mod diag;
 mod error;
 use error::*;
+mod accuracy;
+mod capture;
+mod check;
 mod cli;
+mod color;
+mod config;
+mod csv;
+mod daemon;
+mod db;
+mod device;
 mod display_measurement;
-
+mod duration;
+mod http;
+mod interrupt;
+mod json;
+mod log;
+mod measurement;
+mod meter;
+mod metrics;
+mod modes;
+mod mqtt;
+mod plot;
+mod quantity;
+mod ranges;
+mod record;
+mod scpi;
+mod script;
+mod shell;
+mod sim;
+mod sync;
+mod watch;
+
+use std::env;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
 use std::path::Path;
+use std::time::Duration;
 
 use clap::ArgMatches;
+use error_chain::ChainedError;
 
-use ut181a::{Dmm, Mode, Range};
+use ut181a::{Measurement, Mode, Range, RecordDataItem, RecordInfo};
+use accuracy::{add_uncertainty_json, format_with_uncertainty, value_uncertainty};
+use check::{CheckOptions, Limits, CHECK_FAILED};
 use cli::clap_app;
+use config::Config;
+use device::{Device, PRODUCT_ID, VENDOR_ID};
 
+use csv::write_record_csv;
+use db::Db;
 use display_measurement::{display_measurement, format_duration};
+use duration::{parse_duration, parse_record_duration, parse_record_interval};
+use json::{measurement_json, record_info_json, saved_measurement_json};
+use log::{LogFormat, LogOptions};
+use meter::Meter;
+use modes::{mode_name, parse_mode, MODES};
+use plot::Chart;
+use quantity::parse_quantity;
+use ranges::{mode_ranges, range_by_value};
+use script::Script;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum OutputFormat {
+    Text,
+    Json,
+}
 
-const VENDOR_ID: u16 = 0x10C4;
-const PRODUCT_ID: u16 = 0xEA80;
+fn output_format(cli: &ArgMatches) -> OutputFormat {
+    match cli.value_of("format") {
+        Some("json") => OutputFormat::Json,
+        _ => OutputFormat::Text,
+    }
+}
 
-fn open_dmm<'a>(manager: &hid::Manager, cli: &ArgMatches) -> Result<Dmm> {
-    let mut devices = manager.find(Some(VENDOR_ID), Some(PRODUCT_ID));
-    if let Some(path) = cli.value_of("device") {
-        if cli.is_present("verbose") {
-            println!("Open device at path '{}'.", path);
+fn print_measurement(m: &Measurement, format: OutputFormat, uncertainty: bool) -> Result<()> {
+    match format {
+        OutputFormat::Text => display_measurement(m, uncertainty),
+        OutputFormat::Json => {
+            let mut j = measurement_json(m);
+            if uncertainty {
+                add_uncertainty_json(&mut j, m);
+            }
+            println!("{}", j);
+            Ok(())
         }
-        let p = Path::new(path);
-        for device in devices {
-            if device.path() == p {
-                return Dmm::new(device.open()?).map_err(Error::from);
+    }
+}
+
+fn print_modes(format: OutputFormat) {
+    match format {
+        OutputFormat::Text => {
+            for &(name, mode) in MODES {
+                println!("{:<18} {}", name, mode);
             }
         }
-    } else {
-        if cli.is_present("verbose") {
-            println!("Open first found device.");
+        OutputFormat::Json => {
+            let modes = MODES
+                .iter()
+                .map(|&(name, mode)| json!({"name": name, "mode": mode.to_string()}))
+                .collect();
+            println!("{}", serde_json::Value::Array(modes));
+        }
+    }
+}
+
+/// Reads one measurement to learn current mode and range.
+fn read_measurement(dmm: &mut dyn Meter, verbose: bool) -> Result<Measurement> {
+    if verbose {
+        diagln!("Sending 'MONITOR ON' command to DMM.");
+    }
+    dmm.monitor_on()?;
+    let measurement = dmm.get_measurement();
+    if verbose {
+        diagln!("Sending 'MONITOR OFF' command to DMM.");
+    }
+    dmm.monitor_off()?;
+    measurement
+}
+
+fn print_ranges(m: Mode, current: Range, format: OutputFormat) {
+    let ranges = mode_ranges(m);
+    match format {
+        OutputFormat::Text => {
+            println!("Mode: {}", m);
+            if ranges.is_empty() {
+                println!("Mode has no selectable ranges.");
+            }
+            for info in ranges {
+                println!(
+                    "{} {:<6} {:<22} resolution {:<12} {} counts",
+                    if info.step == current { "*" } else { " " },
+                    json::range_name(info.step),
+                    info.to_string(),
+                    info.format_resolution(),
+                    info.counts()
+                );
+            }
         }
-        if let Some(device) = devices.next() {
-            return Dmm::new(device.open()?).map_err(Error::from);
+        OutputFormat::Json => {
+            let ranges = ranges
+                .iter()
+                .map(|info| {
+                    let mut j = info.to_json();
+                    j["current"] = serde_json::Value::from(info.step == current);
+                    j
+                })
+                .collect();
+            println!("{}", serde_json::Value::Array(ranges));
         }
     }
-    Err(ErrorKind::DmmIsNotFound.into())
 }
 
-fn set_mode(dmm: &mut Dmm, mode: Mode, verbose: bool) -> Result<()> {
+/// Runs script and prints its result, returns exit code.
+fn run_script(
+    dmm: &mut dyn Meter,
+    script: &Script,
+    format: OutputFormat,
+    report_path: Option<&str>,
+    verbose: bool,
+) -> Result<i32> {
+    dmm.monitor_off()?;
+    let report = script::run(dmm, script, format == OutputFormat::Json, verbose);
+    if format == OutputFormat::Json {
+        println!("{}", report.json);
+    }
+    if let Some(path) = report_path {
+        script::write_report(Path::new(path), &report)?;
+    }
+    if let Some(e) = report.error {
+        return Err(e);
+    }
+    Ok(if report.failed > 0 { CHECK_FAILED } else { 0 })
+}
+
+fn set_mode(dmm: &mut dyn Meter, mode: Mode, verbose: bool) -> Result<()> {
     if verbose {
-        println!("Sending 'SET MODE {}' command to DMM.", mode);
+        diagln!("Sending 'SET MODE {}' command to DMM.", mode);
     }
-    dmm.set_mode(mode).map_err(Error::from)
+    dmm.set_mode(mode)
 }
 
-fn run() -> Result<()> {
-    let cli = clap_app().get_matches();
+fn import_record(
+    db: &mut Db,
+    info: &RecordInfo,
+    items: &[RecordDataItem],
+    verbose: bool,
+) -> Result<()> {
+    let new = db.insert_record(info, items)?;
+    if verbose {
+        diagln!(
+            "Record '{}' is {}.",
+            info.name,
+            if new {
+                "stored in database"
+            } else {
+                "already in database"
+            }
+        );
+    }
+    Ok(())
+}
+
+/// Opens database given by `--db` option for `command`.
+fn open_db(cli: &ArgMatches, command: &str) -> Result<Option<Db>> {
+    match cli.value_of("db") {
+        Some(path) => Ok(Some(Db::open(Path::new(path), command)?)),
+        None => Ok(None),
+    }
+}
 
-    let manager = hid::init()?;
+/// Command writes JSON or CSV to stdout (verbose diagnostics must not mix into it).
+fn machine_output(cli: &ArgMatches, format: OutputFormat) -> bool {
+    if format == OutputFormat::Json {
+        return true;
+    }
+    match cli.subcommand() {
+        ("record", Some(record_matches)) => match record_matches.subcommand() {
+            ("read", Some(m)) => m.is_present("csv"),
+            ("export", Some(m)) => !m.is_present("output"),
+            ("start", Some(m)) => m.is_present("wait") && !m.is_present("output"),
+            _ => false,
+        },
+        _ => false,
+    }
+}
 
+/// Executes CLI command, returns exit code.
+fn dispatch(cli: &ArgMatches, device: &mut Device, config: &Config) -> Result<i32> {
     let verbose = cli.is_present("verbose");
+    let uncertainty = cli.is_present("uncertainty");
+    let format = output_format(cli);
+    diag::init(machine_output(cli, format));
     match cli.subcommand() {
-        ("list-devices", _) => for device in manager.find(Some(VENDOR_ID), Some(PRODUCT_ID)) {
-            println!("Found DMM at path '{}'.", device.path().to_string_lossy());
+        ("list-devices", _) => match device.manager {
+            Some(manager) => for device in manager.find(Some(VENDOR_ID), Some(PRODUCT_ID)) {
+                println!("Found DMM at path '{}'.", device.path().to_string_lossy());
+            },
+            None => println!("Found simulated DMM."),
         },
         ("hold", _) => {
-            let mut dmm = open_dmm(&manager, &cli)?;
+            let dmm = device.get(cli)?;
             dmm.monitor_off()?;
             if verbose {
-                println!("Sending 'HOLD' command to DMM.");
+                diagln!("Sending 'HOLD' command to DMM.");
             }
             dmm.toggle_hold()?;
         }
         (cmd @ "min-max-mode", Some(sub_matches)) => {
-            let mut dmm = open_dmm(&manager, &cli)?;
+            let dmm = device.get(cli)?;
             dmm.monitor_off()?;
             match sub_matches.subcommand() {
                 ("on", _) => {
                     if verbose {
-                        println!("Sending 'MIN/MAX ON' command to DMM.");
+                        diagln!("Sending 'MIN/MAX ON' command to DMM.");
                     }
                     dmm.set_min_max_mode(true)?;
                 }
                 ("off", _) => {
                     if verbose {
-                        println!("Sending 'MIN/MAX OFF' command to DMM.");
+                        diagln!("Sending 'MIN/MAX OFF' command to DMM.");
                     }
                     dmm.set_min_max_mode(false)?;
                 }
@@ -92,18 +292,18 @@ fn run() -> Result<()> {
             }
         }
         (cmd @ "save", Some(sub_matches)) => {
-            let mut dmm = open_dmm(&manager, &cli)?;
+            let dmm = device.get(cli)?;
             dmm.monitor_off()?;
             match sub_matches.subcommand() {
                 ("store", _) => {
                     if verbose {
-                        println!("Sending 'SAVE' command to DMM.");
+                        diagln!("Sending 'SAVE' command to DMM.");
                     }
                     dmm.save_measurement()?;
                 }
                 ("count", _) => {
                     if verbose {
-                        println!("Sending 'GET SAVE COUNT' command to DMM.");
+                        diagln!("Sending 'GET SAVE COUNT' command to DMM.");
                     }
                     let count = dmm.get_saved_measurement_count()?;
 
@@ -115,15 +315,39 @@ fn run() -> Result<()> {
                         .ok_or("Undefined index")?
                         .parse::<u16>()?;
                     if verbose {
-                        println!("Sending 'READ SAVE AT {}' command to DMM.", n);
+                        diagln!("Sending 'READ SAVE AT {}' command to DMM.", n);
                     }
                     let save = dmm.get_saved_measurement(n)?;
-                    println!("{}", save.0.format("%Y-%m-%d %H:%M:%S"));
-                    display_measurement(&save.1)?;
+                    if let Some(mut db) = open_db(cli, "save read")? {
+                        let new = db.insert_save(&save.0, &save.1)?;
+                        if verbose {
+                            diagln!(
+                                "Saved measurement is {}.",
+                                if new {
+                                    "stored in database"
+                                } else {
+                                    "already in database"
+                                }
+                            );
+                        }
+                    }
+                    match format {
+                        OutputFormat::Text => {
+                            println!("{}", save.0.format("%Y-%m-%d %H:%M:%S"));
+                            display_measurement(&save.1, uncertainty)?;
+                        }
+                        OutputFormat::Json => {
+                            let mut j = saved_measurement_json(n, &save.0, &save.1);
+                            if uncertainty {
+                                add_uncertainty_json(&mut j["measurement"], &save.1);
+                            }
+                            println!("{}", j);
+                        }
+                    }
                 }
                 ("delete-all", _) => {
                     if verbose {
-                        println!("Sending 'DELETE ALL SAVE' command to DMM.");
+                        diagln!("Sending 'DELETE ALL SAVE' command to DMM.");
                     }
                     dmm.delete_all_saved_measurement()?;
                 }
@@ -133,7 +357,7 @@ fn run() -> Result<()> {
                         .ok_or("Undefined entry index")?
                         .parse::<u16>()?;
                     if verbose {
-                        println!("Sending 'DELETE SAVE #{}' command to DMM.", index);
+                        diagln!("Sending 'DELETE SAVE #{}' command to DMM.", index);
                     }
                     dmm.delete_saved_measurement(index)?;
                 }
@@ -143,12 +367,12 @@ fn run() -> Result<()> {
             }
         }
         (cmd @ "record", Some(sub_matches)) => {
-            let mut dmm = open_dmm(&manager, &cli)?;
+            let dmm = device.get(cli)?;
             dmm.monitor_off()?;
             match sub_matches.subcommand() {
                 ("count", _) => {
                     if verbose {
-                        println!("Sending 'GET RECORD COUNT' command to DMM.");
+                        diagln!("Sending 'GET RECORD COUNT' command to DMM.");
                     }
                     let count = dmm.get_record_count()?;
 
@@ -156,27 +380,36 @@ fn run() -> Result<()> {
                 }
                 ("list", _) => {
                     if verbose {
-                        println!("Sending 'GET RECORD COUNT' command to DMM.");
+                        diagln!("Sending 'GET RECORD COUNT' command to DMM.");
                     }
                     let count = dmm.get_record_count()?;
 
+                    let mut infos = Vec::new();
                     for i in 1..(count + 1) {
                         if verbose {
-                            println!("Sending 'GET RECORD INFO #{}' command to DMM.", i);
+                            diagln!("Sending 'GET RECORD INFO #{}' command to DMM.", i);
                         }
                         let info = dmm.get_record_info(i)?;
-                        println!("RECORD #{}:", i);
-                        println!("\tName: {}", info.name);
-                        println!("\tUnit: {}", info.unit);
-                        println!("\tInterval: {}", format_duration(info.interval));
-                        println!("\tDuration: {}", format_duration(info.duration));
-                        println!("\tSample count: {}", info.sample_count);
-                        println!("\tMaximum value: {}", info.max);
-                        println!("\tAverage value: {}", info.average);
-                        println!("\tMinimum value: {}", info.min);
+                        match format {
+                            OutputFormat::Text => {
+                                println!("RECORD #{}:", i);
+                                println!("\tName: {}", info.name);
+                                println!("\tUnit: {}", info.unit);
+                                println!("\tInterval: {}", format_duration(info.interval));
+                                println!("\tDuration: {}", format_duration(info.duration));
+                                println!("\tSample count: {}", info.sample_count);
+                                println!("\tMaximum value: {}", info.max);
+                                println!("\tAverage value: {}", info.average);
+                                println!("\tMinimum value: {}", info.min);
+                            }
+                            OutputFormat::Json => infos.push(record_info_json(i, &info)),
+                        }
                     }
 
-                    println!("\nTotal record count: {}", count);
+                    match format {
+                        OutputFormat::Text => println!("\nTotal record count: {}", count),
+                        OutputFormat::Json => println!("{}", serde_json::Value::Array(infos)),
+                    }
                 }
                 ("read", Some(read_matches)) => {
                     let n = read_matches
@@ -184,32 +417,143 @@ fn run() -> Result<()> {
                         .ok_or("Undefined index")?
                         .parse::<u16>()?;
                     if verbose {
-                        println!("Sending 'GET RECORD DATA #{}' command to DMM.", n);
+                        diagln!("Sending 'GET RECORD DATA #{}' command to DMM.", n);
                     }
                     let items = dmm.get_record_data(n)?;
-                    for (i, item) in items.iter().enumerate() {
-                        println!("#{:06} {} {}", i + 1, item.timestamp, item.value);
+                    let mut db = open_db(cli, "record read")?;
+                    let info = if read_matches.is_present("csv") || db.is_some() {
+                        if verbose {
+                            diagln!("Sending 'GET RECORD INFO #{}' command to DMM.", n);
+                        }
+                        Some(dmm.get_record_info(n)?)
+                    } else {
+                        None
+                    };
+                    if let (Some(db), Some(info)) = (db.as_mut(), info.as_ref()) {
+                        import_record(db, info, &items, verbose)?;
+                    }
+                    if let (true, Some(info)) = (read_matches.is_present("csv"), info.as_ref()) {
+                        let stdout = io::stdout();
+                        write_record_csv(&mut stdout.lock(), info, &items, uncertainty)?;
+                    } else {
+                        for (i, item) in items.iter().enumerate() {
+                            let u = if uncertainty {
+                                value_uncertainty(&item.value)
+                            } else {
+                                None
+                            };
+                            println!(
+                                "#{:06} {} {}",
+                                i + 1,
+                                item.timestamp,
+                                format_with_uncertainty(&item.value, u)
+                            );
+                        }
+                        println!("Total sample count: {}", items.len());
                     }
-                    println!("Total sample count: {}", items.len());
                 }
-                ("start", Some(start_matches)) => {
-                    let name = start_matches.value_of("NAME").ok_or("Undefined name")?;
-                    let interval = start_matches
-                        .value_of("INTERVAL")
-                        .ok_or("Undefined interval")?
+                ("export", Some(export_matches)) => {
+                    let n = export_matches
+                        .value_of("INDEX")
+                        .ok_or("Undefined index")?
                         .parse::<u16>()?;
-                    let duration = start_matches
-                        .value_of("DURATION")
-                        .ok_or("Undefined duration")?
-                        .parse::<u32>()?;
                     if verbose {
-                        println!("Sending 'RECORD START' command to DMM.");
+                        diagln!("Sending 'GET RECORD INFO #{}' command to DMM.", n);
+                    }
+                    let info = dmm.get_record_info(n)?;
+                    if verbose {
+                        diagln!("Sending 'GET RECORD DATA #{}' command to DMM.", n);
+                    }
+                    let items = dmm.get_record_data(n)?;
+                    if let Some(mut db) = open_db(cli, "record export")? {
+                        import_record(&mut db, &info, &items, verbose)?;
+                    }
+                    if let Some(path) = export_matches.value_of("output") {
+                        let mut file = File::create(path)?;
+                        write_record_csv(&mut file, &info, &items, uncertainty)?;
+                        if verbose {
+                            diagln!("Written {} sample(s) to '{}'.", items.len(), path);
+                        }
+                    } else {
+                        let stdout = io::stdout();
+                        write_record_csv(&mut stdout.lock(), &info, &items, uncertainty)?;
+                    }
+                }
+                ("plot", Some(plot_matches)) => {
+                    let n = plot_matches
+                        .value_of("INDEX")
+                        .ok_or("Undefined index")?
+                        .parse::<u16>()?;
+                    let path = Path::new(plot_matches.value_of("output").ok_or("Undefined file")?);
+                    if verbose {
+                        diagln!("Sending 'GET RECORD INFO #{}' command to DMM.", n);
+                    }
+                    let info = dmm.get_record_info(n)?;
+                    if verbose {
+                        diagln!("Sending 'GET RECORD DATA #{}' command to DMM.", n);
+                    }
+                    let items = dmm.get_record_data(n)?;
+                    Chart::from_record(&info, &items).save(path)?;
+                    if verbose {
+                        diagln!("Plotted {} sample(s) to '{}'.", items.len(), path.display());
                     }
-                    dmm.start_record(name, interval, duration)?;
                 }
+                ("start", Some(start_matches)) => {
+                    let name = start_matches.value_of("NAME").ok_or("Undefined name")?;
+                    let interval = parse_record_interval(
+                        start_matches
+                            .value_of("INTERVAL")
+                            .ok_or("Undefined interval")?,
+                    )?;
+                    let duration = parse_record_duration(
+                        start_matches
+                            .value_of("DURATION")
+                            .ok_or("Undefined duration")?,
+                    )?;
+                    let plan = record::start(dmm, name, interval, duration, verbose)?;
+                    if start_matches.is_present("wait") {
+                        let status = match record::wait(dmm, &plan, verbose)? {
+                            Some(status) => status,
+                            None => return Ok(0),
+                        };
+                        if verbose {
+                            diagln!(
+                                "Sending 'GET RECORD DATA #{}' command to DMM.",
+                                status.index
+                            );
+                        }
+                        let items = dmm.get_record_data(status.index)?;
+                        if let Some(mut db) = open_db(cli, "record start")? {
+                            import_record(&mut db, &status.info, &items, verbose)?;
+                        }
+                        if let Some(path) = start_matches.value_of("output") {
+                            let mut file = File::create(path)?;
+                            write_record_csv(&mut file, &status.info, &items, uncertainty)?;
+                            diagln!("Written {} sample(s) to '{}'.", items.len(), path);
+                        } else if cli.value_of("db").is_none() {
+                            let stdout = io::stdout();
+                            write_record_csv(
+                                &mut stdout.lock(),
+                                &status.info,
+                                &items,
+                                uncertainty,
+                            )?;
+                        }
+                    }
+                }
+                ("status", _) => match record::status(dmm, verbose)? {
+                    Some(status) => match format {
+                        OutputFormat::Text => status.print(),
+                        OutputFormat::Json => println!("{}", status.to_json()),
+                    },
+                    None => match format {
+                        OutputFormat::Text => println!("No records."),
+                        OutputFormat::Json => println!("null"),
+                    },
+                },
                 ("stop", _) => {
                     if verbose {
-                        println!("Sending 'RECORD STOP' command to DMM.");
+                        diagln!("Sending 'RECORD STOP' command to DMM.");
                     }
                     dmm.stop_record()?;
                 }
@@ -218,73 +562,305 @@ fn run() -> Result<()> {
                 }
             }
         }
+        ("sync", Some(sync_matches)) => {
+            let dir = sync_matches.value_of("DIR").map(Path::new);
+            let mut db = open_db(cli, "sync")?;
+            if dir.is_none() && db.is_none() {
+                return Err("Undefined directory (use DIR or --db)".into());
+            }
+            let dmm = device.get(cli)?;
+            dmm.monitor_off()?;
+            sync::sync(dmm, dir, db.as_mut(), verbose)?;
+        }
+        ("log", Some(log_matches)) => {
+            let path = log_matches.value_of("FILE").map(Path::new);
+            let mut db = open_db(cli, "log")?;
+            if path.is_none() && db.is_none() {
+                return Err("Undefined file (use FILE or --db)".into());
+            }
+            let opts = LogOptions {
+                format: match (log_matches.value_of("type"), path) {
+                    (Some("csv"), _) => LogFormat::Csv,
+                    (Some(_), _) | (None, None) => LogFormat::Ndjson,
+                    (None, Some(path)) => LogFormat::from_path(path),
+                },
+                duration: match log_matches.value_of("duration") {
+                    Some(d) => Some(parse_duration(d, Duration::from_secs(1))?),
+                    None => None,
+                },
+                samples: match log_matches.value_of("samples") {
+                    Some(n) => Some(n.parse::<u64>()?),
+                    None => None,
+                },
+                every: match log_matches.value_of("every") {
+                    Some(d) => Some(log::parse_every(d)?),
+                    None => None,
+                },
+                uncertainty,
+            };
+            let dmm = device.get(cli)?;
+            let written = log::log(dmm, path, db.as_mut(), &opts, verbose)?;
+            match (path, cli.value_of("db")) {
+                (Some(path), _) => {
+                    println!("Logged {} sample(s) to '{}'.", written, path.display())
+                }
+                (None, Some(db_path)) => println!("Logged {} sample(s) to '{}'.", written, db_path),
+                (None, None) => {}
+            }
+        }
+        ("plot", Some(plot_matches)) => {
+            let input = Path::new(plot_matches.value_of("FILE").ok_or("Undefined file")?);
+            let path = Path::new(plot_matches.value_of("output").ok_or("Undefined file")?);
+            let chart = Chart::from_file(input)?;
+            chart.save(path)?;
+            if verbose {
+                diagln!(
+                    "Plotted {} sample(s) to '{}'.",
+                    chart.points.len(),
+                    path.display()
+                );
+            }
+        }
+        ("check", Some(check_matches)) => {
+            let name = check_matches.value_of("MODE").ok_or("Undefined mode")?;
+            let mode = parse_mode(name)?;
+            let (limits, unit) = match check_matches.value_of("EXPECTED") {
+                Some(expected) => {
+                    let expected = parse_quantity(expected)?;
+                    let tolerance = check_matches.value_of("TOLERANCE").unwrap_or("0");
+                    let limits = Limits::from_tolerance(expected.value, tolerance)?;
+                    (limits, expected.unit)
+                }
+                None => {
+                    let min = match check_matches.value_of("min") {
+                        Some(v) => Some(parse_quantity(v)?),
+                        None => None,
+                    };
+                    let max = match check_matches.value_of("max") {
+                        Some(v) => Some(parse_quantity(v)?),
+                        None => None,
+                    };
+                    let limits = Limits {
+                        min: min.as_ref().map_or(f64::NEG_INFINITY, |q| q.value),
+                        max: max.as_ref().map_or(f64::INFINITY, |q| q.value),
+                    };
+                    let unit = min.or(max).map(|q| q.unit).unwrap_or_default();
+                    (limits, unit)
+                }
+            };
+            let opts = CheckOptions {
+                mode: Some(mode),
+                limits,
+                unit,
+                samples: match check_matches.value_of("samples") {
+                    Some(n) => check::parse_samples(n)?,
+                    None => 1,
+                },
+                timeout: match check_matches.value_of("timeout") {
+                    Some(d) => parse_duration(d, Duration::from_secs(1))?,
+                    None => Duration::from_secs(10),
+                },
+            };
+            let dmm = device.get(cli)?;
+            if !check::check(dmm, &opts, verbose)? {
+                return Ok(CHECK_FAILED);
+            }
+        }
+        ("run", Some(run_matches)) => {
+            let script = Script::load(Path::new(
+                run_matches.value_of("SCRIPT").ok_or("Undefined script")?,
+            ))?;
+            let dmm = device.get(cli)?;
+            let report = run_matches.value_of("report");
+            return run_script(dmm, &script, format, report, verbose);
+        }
+        ("profile", Some(profile_matches)) => match profile_matches.value_of("NAME") {
+            Some(name) => {
+                let script = config.profile(name)?;
+                if verbose {
+                    diagln!("Apply profile '{}' of '{}'.", name, config.path.display());
+                }
+                let dmm = device.get(cli)?;
+                let report = profile_matches.value_of("report");
+                return run_script(dmm, &script, format, report, verbose);
+            }
+            None => match format {
+                OutputFormat::Text => {
+                    for name in config.profile_names() {
+                        println!("{}", name);
+                    }
+                }
+                OutputFormat::Json => println!("{}", json!(config.profile_names())),
+            },
+        },
+        ("serve-metrics", Some(metrics_matches)) => {
+            let listen = metrics_matches
+                .value_of("listen")
+                .ok_or("Undefined listen address")?;
+            metrics::serve(device, cli, listen, verbose)?;
+        }
+        ("http", Some(http_matches)) => {
+            let listen = http_matches
+                .value_of("listen")
+                .ok_or("Undefined listen address")?;
+            let dmm = device.get(cli)?;
+            dmm.monitor_off()?;
+            http::serve(dmm, listen, verbose)?;
+        }
+        ("scpi-server", Some(scpi_matches)) => {
+            let port = scpi_matches
+                .value_of("port")
+                .ok_or("Undefined TCP port")?
+                .parse::<u16>()?;
+            let bind = scpi_matches
+                .value_of("bind")
+                .ok_or("Undefined listen address")?;
+            let dmm = device.get(cli)?;
+            dmm.monitor_off()?;
+            scpi::serve(dmm, &format!("{}:{}", bind, port), verbose)?;
+        }
+        (cmd @ "publish", Some(publish_matches)) => match publish_matches.subcommand() {
+            ("mqtt", Some(mqtt_matches)) => {
+                let opts = mqtt::PublishOptions {
+                    broker: mqtt_matches.value_of("broker").ok_or("Undefined broker")?,
+                    prefix: mqtt_matches.value_of("prefix").ok_or("Undefined prefix")?,
+                    client_id: mqtt_matches
+                        .value_of("client-id")
+                        .ok_or("Undefined client ID")?,
+                    credentials: match mqtt_matches.value_of("username") {
+                        Some(user) => Some((user, mqtt_matches.value_of("password").unwrap_or(""))),
+                        None => None,
+                    },
+                };
+                let dmm = device.get(cli)?;
+                dmm.monitor_off()?;
+                mqtt::publish(dmm, &opts, verbose)?;
+            }
+            (subcmd, _) => {
+                return Err(ErrorKind::UnknownCliCommand(format!("{} {}", cmd, subcmd)).into());
+            }
+        },
+        (cmd @ "db", Some(db_matches)) => match db_matches.subcommand() {
+            ("query", Some(query_matches)) => {
+                let db = open_db(cli, "db query")?.ok_or("Undefined database (use --db FILE)")?;
+                let summary = query_matches
+                    .value_of("SUMMARY")
+                    .ok_or("Undefined summary")?;
+                let session = match query_matches.value_of("session") {
+                    Some(id) => Some(id.parse::<i64>()?),
+                    None => None,
+                };
+                let (columns, rows) = db.query(summary, session)?;
+                match format {
+                    OutputFormat::Text => db::print_table(&columns, &rows),
+                    OutputFormat::Json => {
+                        let rows = rows.iter().map(|row| db::row_json(&columns, row));
+                        println!("{}", serde_json::Value::Array(rows.collect()));
+                    }
+                }
+            }
+            (subcmd, _) => {
+                return Err(ErrorKind::UnknownCliCommand(format!("{} {}", cmd, subcmd)).into());
+            }
+        },
+        ("watch", _) => {
+            let dmm = device.get(cli)?;
+            dmm.monitor_off()?;
+            watch::watch(dmm)?;
+        }
+        ("daemon", _) => {
+            device.allow_daemon = false;
+            let dmm = device.get(cli)?;
+            daemon::serve(dmm, &daemon::socket_path(cli)?, verbose)?;
+        }
+        ("shell", _) => {
+            device.get(cli)?;
+            shell::shell(cli, |line_cli| dispatch(line_cli, device, config))?;
+        }
         ("ref", Some(submatches)) => {
             let val = submatches
                 .value_of("VALUE")
                 .ok_or("Undefined reference value")?
                 .parse::<f32>()?;
-            let mut dmm = open_dmm(&manager, &cli)?;
+            let dmm = device.get(cli)?;
             dmm.monitor_off()?;
             if verbose {
-                println!("Sending 'SET REFERENCE VALUE {}' command to DMM.", val);
+                diagln!("Sending 'SET REFERENCE VALUE {}' command to DMM.", val);
             }
             dmm.set_reference_value(val)?;
         }
         (cmd @ "range", Some(sub_matches)) => {
-            let mut dmm = open_dmm(&manager, &cli)?;
+            let dmm = device.get(cli)?;
             dmm.monitor_off()?;
             match sub_matches.subcommand() {
+                ("", None) => {
+                    let value = sub_matches.value_of("VALUE").ok_or("Undefined range")?;
+                    let measurement = read_measurement(dmm, verbose)?;
+                    let r = range_by_value(measurement::mode(&measurement), value)?;
+                    if verbose {
+                        diagln!("Sending 'SET RANGE {:?}' command to DMM.", r);
+                    }
+                    dmm.set_range(r)?;
+                }
+                ("list", _) => {
+                    let measurement = read_measurement(dmm, verbose)?;
+                    print_ranges(
+                        measurement::mode(&measurement),
+                        measurement::range(&measurement),
+                        format,
+                    );
+                }
                 ("auto", _) => {
                     if verbose {
-                        println!("Sending 'SET RANGE AUTO' command to DMM.");
+                        diagln!("Sending 'SET RANGE AUTO' command to DMM.");
                     }
                     dmm.set_range(Range::Auto)?;
                 }
                 ("step1", _) => {
                     if verbose {
-                        println!("Sending 'SET RANGE 1' command to DMM.");
+                        diagln!("Sending 'SET RANGE 1' command to DMM.");
                     }
                     dmm.set_range(Range::Step1)?;
                 }
                 ("step2", _) => {
                     if verbose {
-                        println!("Sending 'SET RANGE 2' command to DMM.");
+                        diagln!("Sending 'SET RANGE 2' command to DMM.");
                     }
                     dmm.set_range(Range::Step2)?;
                 }
                 ("step3", _) => {
                     if verbose {
-                        println!("Sending 'SET RANGE 3' command to DMM.");
+                        diagln!("Sending 'SET RANGE 3' command to DMM.");
                     }
                     dmm.set_range(Range::Step3)?;
                 }
                 ("step4", _) => {
                     if verbose {
-                        println!("Sending 'SET RANGE 4' command to DMM.");
+                        diagln!("Sending 'SET RANGE 4' command to DMM.");
                     }
                     dmm.set_range(Range::Step4)?;
                 }
                 ("step5", _) => {
                     if verbose {
-                        println!("Sending 'SET RANGE 5' command to DMM.");
+                        diagln!("Sending 'SET RANGE 5' command to DMM.");
                     }
                     dmm.set_range(Range::Step5)?;
                 }
                 ("step6", _) => {
                     if verbose {
-                        println!("Sending 'SET RANGE 6' command to DMM.");
+                        diagln!("Sending 'SET RANGE 6' command to DMM.");
                     }
                     dmm.set_range(Range::Step6)?;
                 }
                 ("step7", _) => {
                     if verbose {
-                        println!("Sending 'SET RANGE 7' command to DMM.");
+                        diagln!("Sending 'SET RANGE 7' command to DMM.");
                     }
                     dmm.set_range(Range::Step7)?;
                 }
                 ("step8", _) => {
                     if verbose {
-                        println!("Sending 'SET RANGE 8' command to DMM.");
+                        diagln!("Sending 'SET RANGE 8' command to DMM.");
                     }
                     dmm.set_range(Range::Step8)?;
                 }
@@ -293,148 +869,70 @@ fn run() -> Result<()> {
                 }
             }
         }
-        (cmd @ "mode", Some(submatches)) => {
-            let mut dmm = open_dmm(&manager, &cli)?;
-            dmm.monitor_off()?;
-            match submatches.subcommand() {
-                ("vac", _) => set_mode(&mut dmm, Mode::VAC_Normal, verbose)?,
-                ("vac-rel", _) => set_mode(&mut dmm, Mode::VAC_Normal_Rel, verbose)?,
-                ("vac-hz", _) => set_mode(&mut dmm, Mode::VAC_Hz, verbose)?,
-                ("vac-peak", _) => set_mode(&mut dmm, Mode::VAC_Peak, verbose)?,
-                ("vac-lowpass", _) => set_mode(&mut dmm, Mode::VAC_LowPass, verbose)?,
-                ("vac-lowpass-rel", _) => set_mode(&mut dmm, Mode::VAC_LowPass_Rel, verbose)?,
-                ("vac-dbv", _) => set_mode(&mut dmm, Mode::VAC_dBV, verbose)?,
-                ("vac-dbv-rel", _) => set_mode(&mut dmm, Mode::VAC_dBV_Rel, verbose)?,
-                ("vac-dbm", _) => set_mode(&mut dmm, Mode::VAC_dBm, verbose)?,
-                ("vac-dbm-rel", _) => set_mode(&mut dmm, Mode::VAC_dBm_Rel, verbose)?,
-
-                ("mvac", _) => set_mode(&mut dmm, Mode::mVAC_Normal, verbose)?,
-                ("mvac-rel", _) => set_mode(&mut dmm, Mode::mVAC_Normal_Rel, verbose)?,
-                ("mvac-hz", _) => set_mode(&mut dmm, Mode::mVAC_Hz, verbose)?,
-                ("mvac-peak", _) => set_mode(&mut dmm, Mode::mVAC_Peak, verbose)?,
-                ("mvac-acdc", _) => set_mode(&mut dmm, Mode::mVAC_AC_DC, verbose)?,
-                ("mvac-acdc-rel", _) => set_mode(&mut dmm, Mode::mVAC_AC_DC_Rel, verbose)?,
-
-                ("vdc", _) => set_mode(&mut dmm, Mode::VDC_Normal, verbose)?,
-                ("vdc-rel", _) => set_mode(&mut dmm, Mode::VDC_Normal_Rel, verbose)?,
-                ("vdc-acdc", _) => set_mode(&mut dmm, Mode::VDC_AC_DC, verbose)?,
-                ("vdc-acdc-rel", _) => set_mode(&mut dmm, Mode::VDC_AC_DC_Rel, verbose)?,
-                ("vdc-peak", _) => set_mode(&mut dmm, Mode::VDC_Peak, verbose)?,
-
-                ("mvdc", _) => set_mode(&mut dmm, Mode::mVDC_Normal, verbose)?,
-                ("mvdc-rel", _) => set_mode(&mut dmm, Mode::mVDC_Normal_Rel, verbose)?,
-                ("mvdc-peak", _) => set_mode(&mut dmm, Mode::mVDC_Peak, verbose)?,
-
-                ("temp-c-t1t2", _) => set_mode(&mut dmm, Mode::TempC_T1_T2, verbose)?,
-                ("temp-c-t1t2-rel", _) => set_mode(&mut dmm, Mode::TempC_T1_T2_Rel, verbose)?,
-                ("temp-c-t2t1", _) => set_mode(&mut dmm, Mode::TempC_T2_T1, verbose)?,
-                ("temp-c-t2t1-rel", _) => set_mode(&mut dmm, Mode::TempC_T2_T1_Rel, verbose)?,
-                ("temp-c-t1t2-diff", _) => set_mode(&mut dmm, Mode::TempC_T1_T2_Diff, verbose)?,
-                ("temp-c-t2t1-diff", _) => set_mode(&mut dmm, Mode::TempC_T2_T1_Diff, verbose)?,
-
-                ("temp-f-t1t2", _) => set_mode(&mut dmm, Mode::TempF_T1_T2, verbose)?,
-                ("temp-f-t1t2-rel", _) => set_mode(&mut dmm, Mode::TempF_T1_T2_Rel, verbose)?,
-                ("temp-f-t2t1", _) => set_mode(&mut dmm, Mode::TempF_T2_T1, verbose)?,
-                ("temp-f-t2t1-rel", _) => set_mode(&mut dmm, Mode::TempF_T2_T1_Rel, verbose)?,
-                ("temp-f-t1t2-diff", _) => set_mode(&mut dmm, Mode::TempF_T1_T2_Diff, verbose)?,
-                ("temp-f-t2t1-diff", _) => set_mode(&mut dmm, Mode::TempF_T2_T1_Diff, verbose)?,
-
-                ("res", _) => set_mode(&mut dmm, Mode::Resistance, verbose)?,
-                ("res-rel", _) => set_mode(&mut dmm, Mode::Resistance_Rel, verbose)?,
-
-                ("beeper-short", _) => set_mode(&mut dmm, Mode::Beeper_Short, verbose)?,
-                ("beeper-open", _) => set_mode(&mut dmm, Mode::Beeper_Open, verbose)?,
-
-                ("adm", _) => set_mode(&mut dmm, Mode::Admittance, verbose)?,
-                ("adm-rel", _) => set_mode(&mut dmm, Mode::Admittance_Rel, verbose)?,
-
-                ("diode", _) => set_mode(&mut dmm, Mode::Diode_Normal, verbose)?,
-                ("diode-alarm", _) => set_mode(&mut dmm, Mode::Diode_Alarm, verbose)?,
-
-                ("cap", _) => set_mode(&mut dmm, Mode::Capacitance, verbose)?,
-                ("cap-rel", _) => set_mode(&mut dmm, Mode::Capacitance_Rel, verbose)?,
-
-                ("freq", _) => set_mode(&mut dmm, Mode::Frequency, verbose)?,
-                ("freq-rel", _) => set_mode(&mut dmm, Mode::Frequency_Rel, verbose)?,
-
-                ("duty", _) => set_mode(&mut dmm, Mode::DutyCycle, verbose)?,
-                ("duty-rel", _) => set_mode(&mut dmm, Mode::DutyCycle_Rel, verbose)?,
-
-                ("pulse", _) => set_mode(&mut dmm, Mode::PulseWidth, verbose)?,
-                ("pulse-rel", _) => set_mode(&mut dmm, Mode::PulseWidth_Rel, verbose)?,
-
-                ("uadc", _) => set_mode(&mut dmm, Mode::uADC_Normal, verbose)?,
-                ("uadc-rel", _) => set_mode(&mut dmm, Mode::uADC_Normal_Rel, verbose)?,
-                ("uadc-acdc", _) => set_mode(&mut dmm, Mode::uADC_AC_DC, verbose)?,
-                ("uadc-acdc-rel", _) => set_mode(&mut dmm, Mode::uADC_AC_DC_Rel, verbose)?,
-                ("uadc-peak", _) => set_mode(&mut dmm, Mode::uADC_Peak, verbose)?,
-
-                ("madc", _) => set_mode(&mut dmm, Mode::mADC_Normal, verbose)?,
-                ("madc-rel", _) => set_mode(&mut dmm, Mode::mADC_Normal_Rel, verbose)?,
-                ("madc-acdc", _) => set_mode(&mut dmm, Mode::mADC_AC_DC, verbose)?,
-                ("madc-acdc-rel", _) => set_mode(&mut dmm, Mode::mADC_AC_DC_Rel, verbose)?,
-                ("madc-peak", _) => set_mode(&mut dmm, Mode::mADC_Peak, verbose)?,
-
-                ("adc", _) => set_mode(&mut dmm, Mode::ADC_Normal, verbose)?,
-                ("adc-rel", _) => set_mode(&mut dmm, Mode::ADC_Normal_Rel, verbose)?,
-                ("adc-acdc", _) => set_mode(&mut dmm, Mode::ADC_AC_DC, verbose)?,
-                ("adc-acdc-rel", _) => set_mode(&mut dmm, Mode::ADC_AC_DC_Rel, verbose)?,
-                ("adc-peak", _) => set_mode(&mut dmm, Mode::ADC_Peak, verbose)?,
-
-                ("uaac", _) => set_mode(&mut dmm, Mode::uAAC_Normal, verbose)?,
-                ("uaac-rel", _) => set_mode(&mut dmm, Mode::uAAC_Normal_Rel, verbose)?,
-                ("uaac-hz", _) => set_mode(&mut dmm, Mode::uAAC_Hz, verbose)?,
-                ("uaac-peak", _) => set_mode(&mut dmm, Mode::uAAC_Peak, verbose)?,
-
-                ("maac", _) => set_mode(&mut dmm, Mode::mAAC_Normal, verbose)?,
-                ("maac-rel", _) => set_mode(&mut dmm, Mode::mAAC_Normal_Rel, verbose)?,
-                ("maac-hz", _) => set_mode(&mut dmm, Mode::mAAC_Hz, verbose)?,
-                ("maac-peak", _) => set_mode(&mut dmm, Mode::mAAC_Peak, verbose)?,
-
-                ("aac", _) => set_mode(&mut dmm, Mode::AAC_Normal, verbose)?,
-                ("aac-rel", _) => set_mode(&mut dmm, Mode::AAC_Normal_Rel, verbose)?,
-                ("aac-hz", _) => set_mode(&mut dmm, Mode::AAC_Hz, verbose)?,
-                ("aac-peak", _) => set_mode(&mut dmm, Mode::AAC_Peak, verbose)?,
-
-                (subcmd, _) => {
-                    return Err(ErrorKind::UnknownCliCommand(format!("{} {}", cmd, subcmd)).into());
+        (cmd @ "mode", Some(submatches)) => match submatches.subcommand() {
+            ("", None) => {
+                let mode = parse_mode(submatches.value_of("MODE").ok_or("Undefined mode")?)?;
+                let dmm = device.get(cli)?;
+                dmm.monitor_off()?;
+                set_mode(dmm, mode, verbose)?;
+            }
+            ("list", _) => print_modes(format),
+            ("get", _) => {
+                let dmm = device.get(cli)?;
+                let measurement = read_measurement(dmm, verbose)?;
+                let mode = measurement::mode(&measurement);
+                match format {
+                    OutputFormat::Text => println!("{} ({})", mode, mode_name(mode)),
+                    OutputFormat::Json => println!(
+                        "{}",
+                        json!({"mode": mode.to_string(), "name": mode_name(mode)})
+                    ),
                 }
             }
-        }
+            (subcmd, _) => {
+                return Err(ErrorKind::UnknownCliCommand(format!("{} {}", cmd, subcmd)).into());
+            }
+        },
         (cmd @ "read", Some(read_matches)) => {
-            let mut dmm = open_dmm(&manager, &cli)?;
+            let dmm = device.get(cli)?;
 
             match read_matches.subcommand() {
                 ("once", _) => {
                     if verbose {
-                        println!("Sending 'MONITOR ON' command to DMM.");
+                        diagln!("Sending 'MONITOR ON' command to DMM.");
                     }
                     dmm.monitor_on()?;
                     if verbose {
-                        println!("Reading a message from DMM.");
+                        diagln!("Reading a message from DMM.");
                     }
 
                     let measurement = dmm.get_measurement()?;
-                    display_measurement(&measurement)?;
+                    print_measurement(&measurement, format, uncertainty)?;
 
                     if verbose {
-                        println!("Sending 'MONITOR OFF' command to DMM.");
+                        diagln!("Sending 'MONITOR OFF' command to DMM.");
                     }
                     dmm.monitor_off()?;
                 }
                 ("cont", _) => {
                     if verbose {
-                        println!("Sending 'MONITOR ON' command to DMM.");
+                        diagln!("Sending 'MONITOR ON' command to DMM.");
                     }
                     dmm.monitor_on()?;
 
-                    loop {
+                    interrupt::catch()?;
+                    while !interrupt::is_interrupted() {
                         if verbose {
-                            println!("Reading a message from DMM.");
+                            diagln!("Reading a message from DMM.");
                         }
                         let measurement = dmm.get_measurement()?;
-                        display_measurement(&measurement)?;
+                        print_measurement(&measurement, format, uncertainty)?;
+                    }
+
+                    if verbose {
+                        diagln!("Sending 'MONITOR OFF' command to DMM.");
                     }
+                    dmm.monitor_off()?;
                 }
                 (subcmd, _) => {
                     return Err(ErrorKind::UnknownCliCommand(format!("{} {}", cmd, subcmd)).into());
@@ -445,7 +943,43 @@ fn run() -> Result<()> {
             return Err(ErrorKind::UnknownCliCommand(cmd.to_owned()).into());
         }
     }
-    Ok(())
+    Ok(0)
+}
+
+fn run() -> Result<i32> {
+    let cli = clap_app().get_matches();
+    let config = Config::load(cli.value_of("config"))?;
+    let defaults = config.default_args(&cli);
+    let cli = if defaults.is_empty() {
+        cli
+    } else {
+        // config defaults are inserted before command line arguments
+        let mut args = env::args_os();
+        let argv: Vec<OsString> = args
+            .next()
+            .into_iter()
+            .chain(defaults.into_iter().map(OsString::from))
+            .chain(args)
+            .collect();
+        clap_app().get_matches_from(argv)
+    };
+    color::init(cli.value_of("color"));
+
+    let manager = if cli.is_present("simulate") || cli.is_present("replay") {
+        None
+    } else {
+        Some(hid::init()?)
+    };
+    let mut device = Device::new(manager.as_ref(), config.device.as_deref());
+    dispatch(&cli, &mut device, &config)
 }
 
-quick_main!(run);
+fn main() {
+    ::std::process::exit(match run() {
+        Ok(code) => code,
+        Err(ref e) => {
+            color::eprint_error(e.display_chain());
+            1
+        }
+    });
+}
@@ -0,0 +1,76 @@
+use ut181a::{Measurement, Mode, Range, UnitExp, Value};
+
+/// Returns the main display value of measurement.
+///
+/// It's relative value in relative mode and maximum in peak mode.
+pub(crate) fn main_value(m: &Measurement) -> &Value {
+    match *m {
+        Measurement::Normal(ref meas) => &meas.main,
+        Measurement::Relative(ref meas) => &meas.relative,
+        Measurement::MinMax(ref meas) => &meas.main,
+        Measurement::Peak(ref meas) => &meas.max,
+    }
+}
+
+pub(crate) fn mode(m: &Measurement) -> Mode {
+    match *m {
+        Measurement::Normal(ref meas) => meas.mode,
+        Measurement::Relative(ref meas) => meas.mode,
+        Measurement::MinMax(ref meas) => meas.mode,
+        Measurement::Peak(ref meas) => meas.mode,
+    }
+}
+
+pub(crate) fn range(m: &Measurement) -> Range {
+    match *m {
+        Measurement::Normal(ref meas) => meas.range,
+        Measurement::Relative(ref meas) => meas.range,
+        Measurement::MinMax(ref meas) => meas.range,
+        Measurement::Peak(ref meas) => meas.range,
+    }
+}
+
+pub(crate) fn is_holded(m: &Measurement) -> bool {
+    match *m {
+        Measurement::Normal(ref meas) => meas.is_holded,
+        Measurement::Relative(ref meas) => meas.is_holded,
+        Measurement::MinMax(ref meas) => meas.is_holded,
+        Measurement::Peak(ref meas) => meas.is_holded,
+    }
+}
+
+pub(crate) fn is_auto_range(m: &Measurement) -> bool {
+    match *m {
+        Measurement::Normal(ref meas) => meas.is_auto_range,
+        Measurement::Relative(ref meas) => meas.is_auto_range,
+        Measurement::MinMax(ref meas) => meas.is_auto_range,
+        Measurement::Peak(ref meas) => meas.is_auto_range,
+    }
+}
+
+/// Returns the fast (bar graph) value if measurement has it.
+pub(crate) fn fast_value(m: &Measurement) -> Option<&Value> {
+    match *m {
+        Measurement::Normal(ref meas) => meas.fast.as_ref(),
+        Measurement::Relative(ref meas) => meas.fast.as_ref(),
+        Measurement::MinMax(_) | Measurement::Peak(_) => None,
+    }
+}
+
+/// Returns value in base units (without SI prefix).
+pub(crate) fn base_value(v: &Value) -> f64 {
+    f64::from(v.value) * 10f64.powi(v.unit.exponent as i32)
+}
+
+/// Returns unit of value without SI prefix.
+pub(crate) fn base_unit(v: &Value) -> String {
+    UnitExp {
+        unit: v.unit.unit,
+        exponent: 0,
+    }
+    .to_string()
+}
+
+pub(crate) fn is_overload(v: &Value) -> bool {
+    v.overload_pos || v.overload_neg || !v.value.is_finite()
+}
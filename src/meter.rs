@@ -0,0 +1,139 @@
+use chrono::NaiveDateTime;
+
+use ut181a::{Dmm, Measurement, Mode, Range, RecordDataItem, RecordInfo};
+
+use error::*;
+
+/// DMM operations used by CLI commands.
+///
+/// It's implemented by real device (`ut181a::Dmm`) and by other backends
+/// (for example, simulated DMM).
+pub(crate) trait Meter {
+    /// Emulates 'Hold' button.
+    fn toggle_hold(&mut self) -> Result<()>;
+
+    /// Saves current measurement in DMM memory.
+    fn save_measurement(&mut self) -> Result<()>;
+
+    /// Returns count of saved measurements.
+    fn get_saved_measurement_count(&mut self) -> Result<u16>;
+
+    /// Returns saved measurement at index `n` (starting from 1).
+    fn get_saved_measurement(&mut self, n: u16) -> Result<(NaiveDateTime, Measurement)>;
+
+    /// Deletes saved measurement at `index` (starting from 1).
+    fn delete_saved_measurement(&mut self, index: u16) -> Result<()>;
+
+    /// Deletes all saved measurements.
+    fn delete_all_saved_measurement(&mut self) -> Result<()>;
+
+    /// Turns on (restarts) or turns off Min/Max mode.
+    fn set_min_max_mode(&mut self, on: bool) -> Result<()>;
+
+    /// Sets measuring range.
+    fn set_range(&mut self, range: Range) -> Result<()>;
+
+    /// Sets reference value for relative modes.
+    fn set_reference_value(&mut self, val: f32) -> Result<()>;
+
+    /// Sets mode and submode.
+    fn set_mode(&mut self, mode: Mode) -> Result<()>;
+
+    /// Returns count of records.
+    fn get_record_count(&mut self) -> Result<u16>;
+
+    /// Returns info of record `i` (starting from 1).
+    fn get_record_info(&mut self, i: u16) -> Result<RecordInfo>;
+
+    /// Returns samples of record `i` (starting from 1).
+    fn get_record_data(&mut self, i: u16) -> Result<Vec<RecordDataItem>>;
+
+    /// Starts new recording (`interval` in seconds, `duration` in minutes).
+    fn start_record(&mut self, name: &str, interval: u16, duration: u32) -> Result<()>;
+
+    /// Stops current recording.
+    fn stop_record(&mut self) -> Result<()>;
+
+    /// Turns on monitoring mode.
+    fn monitor_on(&mut self) -> Result<()>;
+
+    /// Turns off monitoring mode.
+    fn monitor_off(&mut self) -> Result<()>;
+
+    /// Returns next measurement (monitoring mode should be on).
+    fn get_measurement(&mut self) -> Result<Measurement>;
+}
+
+impl Meter for Dmm {
+    fn toggle_hold(&mut self) -> Result<()> {
+        Dmm::toggle_hold(self).map_err(Error::from)
+    }
+
+    fn save_measurement(&mut self) -> Result<()> {
+        Dmm::save_measurement(self).map_err(Error::from)
+    }
+
+    fn get_saved_measurement_count(&mut self) -> Result<u16> {
+        Dmm::get_saved_measurement_count(self).map_err(Error::from)
+    }
+
+    fn get_saved_measurement(&mut self, n: u16) -> Result<(NaiveDateTime, Measurement)> {
+        Dmm::get_saved_measurement(self, n).map_err(Error::from)
+    }
+
+    fn delete_saved_measurement(&mut self, index: u16) -> Result<()> {
+        Dmm::delete_saved_measurement(self, index).map_err(Error::from)
+    }
+
+    fn delete_all_saved_measurement(&mut self) -> Result<()> {
+        Dmm::delete_all_saved_measurement(self).map_err(Error::from)
+    }
+
+    fn set_min_max_mode(&mut self, on: bool) -> Result<()> {
+        Dmm::set_min_max_mode(self, on).map_err(Error::from)
+    }
+
+    fn set_range(&mut self, range: Range) -> Result<()> {
+        Dmm::set_range(self, range).map_err(Error::from)
+    }
+
+    fn set_reference_value(&mut self, val: f32) -> Result<()> {
+        Dmm::set_reference_value(self, val).map_err(Error::from)
+    }
+
+    fn set_mode(&mut self, mode: Mode) -> Result<()> {
+        Dmm::set_mode(self, mode).map_err(Error::from)
+    }
+
+    fn get_record_count(&mut self) -> Result<u16> {
+        Dmm::get_record_count(self).map_err(Error::from)
+    }
+
+    fn get_record_info(&mut self, i: u16) -> Result<RecordInfo> {
+        Dmm::get_record_info(self, i).map_err(Error::from)
+    }
+
+    fn get_record_data(&mut self, i: u16) -> Result<Vec<RecordDataItem>> {
+        Dmm::get_record_data(self, i).map_err(Error::from)
+    }
+
+    fn start_record(&mut self, name: &str, interval: u16, duration: u32) -> Result<()> {
+        Dmm::start_record(self, name, interval, duration).map_err(Error::from)
+    }
+
+    fn stop_record(&mut self) -> Result<()> {
+        Dmm::stop_record(self).map_err(Error::from)
+    }
+
+    fn monitor_on(&mut self) -> Result<()> {
+        Dmm::monitor_on(self).map_err(Error::from)
+    }
+
+    fn monitor_off(&mut self) -> Result<()> {
+        Dmm::monitor_off(self).map_err(Error::from)
+    }
+
+    fn get_measurement(&mut self) -> Result<Measurement> {
+        Dmm::get_measurement(self).map_err(Error::from)
+    }
+}
@@ -0,0 +1,254 @@
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::ArgMatches;
+use tiny_http::{Header, Response, Server};
+
+use ut181a::{Measurement, Value};
+
+use color;
+use device::Device;
+use error::*;
+use interrupt;
+use measurement::{base_unit, base_value, mode};
+use meter::Meter;
+
+/// Delay between reconnection attempts.
+const RECONNECT_PERIOD: u64 = 1; // second
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Gauge value of one measurement field (in base units).
+struct Reading {
+    name: &'static str,
+    unit: String,
+    value: f64,
+}
+
+/// Metrics shared between DMM reader and HTTP server.
+#[derive(Default)]
+struct Metrics {
+    up: bool,
+    mode: String,
+    readings: Vec<Reading>,
+    /// Count of received measurements.
+    total: u64,
+    /// UNIX time of last measurement.
+    timestamp: f64,
+}
+
+fn reading(name: &'static str, v: &Value) -> Reading {
+    let value = if v.overload_pos {
+        f64::INFINITY
+    } else if v.overload_neg {
+        f64::NEG_INFINITY
+    } else {
+        base_value(v)
+    };
+    Reading {
+        name,
+        unit: base_unit(v),
+        value,
+    }
+}
+
+fn readings(m: &Measurement) -> Vec<Reading> {
+    let mut r = Vec::new();
+    match *m {
+        Measurement::Normal(ref meas) => {
+            r.push(reading("main", &meas.main));
+            if let Some(ref v) = meas.aux1 {
+                r.push(reading("aux1", v));
+            }
+            if let Some(ref v) = meas.aux2 {
+                r.push(reading("aux2", v));
+            }
+            if let Some(ref v) = meas.fast {
+                r.push(reading("fast", v));
+            }
+        }
+        Measurement::Relative(ref meas) => {
+            r.push(reading("relative", &meas.relative));
+            r.push(reading("reference", &meas.reference));
+            r.push(reading("measurement", &meas.measurement));
+            if let Some(ref v) = meas.fast {
+                r.push(reading("fast", v));
+            }
+        }
+        Measurement::MinMax(ref meas) => {
+            r.push(reading("main", &meas.main));
+            r.push(reading("max", &meas.max));
+            r.push(reading("average", &meas.average));
+            r.push(reading("min", &meas.min));
+        }
+        Measurement::Peak(ref meas) => {
+            r.push(reading("peak_max", &meas.max));
+            r.push(reading("peak_min", &meas.min));
+        }
+    }
+    r
+}
+
+/// Escapes label value (Prometheus text format).
+fn label(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn gauge_value(v: f64) -> String {
+    if v == f64::INFINITY {
+        "+Inf".to_owned()
+    } else if v == f64::NEG_INFINITY {
+        "-Inf".to_owned()
+    } else {
+        v.to_string()
+    }
+}
+
+impl Metrics {
+    fn update(&mut self, m: &Measurement) {
+        self.up = true;
+        self.mode = mode(m).to_string();
+        self.readings = readings(m);
+        self.total += 1;
+        self.timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0);
+    }
+
+    fn down(&mut self) {
+        self.up = false;
+        self.readings.clear();
+    }
+
+    /// Renders metrics in Prometheus text format.
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP ut181a_up Whether DMM is connected and sends readings.\n\
+             # TYPE ut181a_up gauge\n\
+             ut181a_up {}",
+            if self.up { 1 } else { 0 }
+        );
+        let _ = writeln!(
+            out,
+            "# HELP ut181a_reading Latest reading in base units (overload is +Inf/-Inf).\n\
+             # TYPE ut181a_reading gauge"
+        );
+        for r in &self.readings {
+            let _ = writeln!(
+                out,
+                "ut181a_reading{{value=\"{}\",mode=\"{}\",unit=\"{}\"}} {}",
+                r.name,
+                label(&self.mode),
+                label(&r.unit),
+                gauge_value(r.value)
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP ut181a_readings_total Count of received measurements.\n\
+             # TYPE ut181a_readings_total counter\n\
+             ut181a_readings_total {}",
+            self.total
+        );
+        if self.total > 0 {
+            let _ = writeln!(
+                out,
+                "# HELP ut181a_last_reading_timestamp_seconds UNIX time of last measurement.\n\
+                 # TYPE ut181a_last_reading_timestamp_seconds gauge\n\
+                 ut181a_last_reading_timestamp_seconds {}",
+                self.timestamp
+            );
+        }
+        out
+    }
+}
+
+fn serve_http(server: &Server, metrics: &Mutex<Metrics>) {
+    for request in server.incoming_requests() {
+        let response = if request.url() == "/metrics" {
+            let body = match metrics.lock() {
+                Ok(metrics) => metrics.render(),
+                Err(_) => String::new(),
+            };
+            let header = Header::from_bytes("Content-Type", CONTENT_TYPE)
+                .expect("valid Content-Type header");
+            Response::from_string(body).with_header(header)
+        } else {
+            Response::from_string("Not found\n").with_status_code(404)
+        };
+        // Client can close connection before response.
+        let _ = request.respond(response);
+    }
+}
+
+fn read_measurements(dmm: &mut dyn Meter, metrics: &Mutex<Metrics>, verbose: bool) -> Result<()> {
+    if verbose {
+        diagln!("Sending 'MONITOR ON' command to DMM.");
+    }
+    dmm.monitor_on()?;
+    while !interrupt::is_interrupted() {
+        let m = dmm.get_measurement()?;
+        if let Ok(mut metrics) = metrics.lock() {
+            metrics.update(&m);
+        }
+    }
+    if verbose {
+        diagln!("Sending 'MONITOR OFF' command to DMM.");
+    }
+    dmm.monitor_off()
+}
+
+/// Serves live readings on `http://<listen>/metrics` until Ctrl-C is pressed.
+///
+/// DMM errors don't stop the server: `ut181a_up` gauge drops to 0 and
+/// the device is reopened.
+pub(crate) fn serve(
+    device: &mut Device,
+    cli: &ArgMatches,
+    listen: &str,
+    verbose: bool,
+) -> Result<()> {
+    let server = Arc::new(
+        Server::http(listen)
+            .map_err(|e| Error::from(format!("can't listen on '{}': {}", listen, e)))?,
+    );
+    let metrics = Arc::new(Mutex::new(Metrics::default()));
+    let http = {
+        let server = server.clone();
+        let metrics = metrics.clone();
+        thread::spawn(move || serve_http(&server, &metrics))
+    };
+    println!("Serving metrics on http://{}/metrics", listen);
+
+    interrupt::catch()?;
+    let mut last_error = String::new();
+    while !interrupt::is_interrupted() {
+        let result = device
+            .get(cli)
+            .and_then(|dmm| read_measurements(dmm, &metrics, verbose));
+        if let Err(e) = result {
+            // The same error is repeated on every reconnection attempt.
+            let error = e.to_string();
+            if error != last_error || verbose {
+                color::eprint_error(format!("DMM error: {}", error));
+                last_error = error;
+            }
+            if let Ok(mut metrics) = metrics.lock() {
+                metrics.down();
+            }
+            device.close();
+            thread::sleep(Duration::from_secs(RECONNECT_PERIOD));
+        }
+    }
+
+    server.unblock();
+    let _ = http.join();
+    Ok(())
+}
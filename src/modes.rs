@@ -0,0 +1,143 @@
+use ut181a::Mode;
+
+use error::*;
+
+/// CLI names of all measuring modes.
+pub(crate) const MODES: &[(&str, Mode)] = &[
+    ("vac", Mode::VAC_Normal),
+    ("vac-rel", Mode::VAC_Normal_Rel),
+    ("vac-hz", Mode::VAC_Hz),
+    ("vac-peak", Mode::VAC_Peak),
+    ("vac-lowpass", Mode::VAC_LowPass),
+    ("vac-lowpass-rel", Mode::VAC_LowPass_Rel),
+    ("vac-dbv", Mode::VAC_dBV),
+    ("vac-dbv-rel", Mode::VAC_dBV_Rel),
+    ("vac-dbm", Mode::VAC_dBm),
+    ("vac-dbm-rel", Mode::VAC_dBm_Rel),
+    ("mvac", Mode::mVAC_Normal),
+    ("mvac-rel", Mode::mVAC_Normal_Rel),
+    ("mvac-hz", Mode::mVAC_Hz),
+    ("mvac-peak", Mode::mVAC_Peak),
+    ("mvac-acdc", Mode::mVAC_AC_DC),
+    ("mvac-acdc-rel", Mode::mVAC_AC_DC_Rel),
+    ("vdc", Mode::VDC_Normal),
+    ("vdc-rel", Mode::VDC_Normal_Rel),
+    ("vdc-acdc", Mode::VDC_AC_DC),
+    ("vdc-acdc-rel", Mode::VDC_AC_DC_Rel),
+    ("vdc-peak", Mode::VDC_Peak),
+    ("mvdc", Mode::mVDC_Normal),
+    ("mvdc-rel", Mode::mVDC_Normal_Rel),
+    ("mvdc-peak", Mode::mVDC_Peak),
+    ("temp-c-t1t2", Mode::TempC_T1_T2),
+    ("temp-c-t1t2-rel", Mode::TempC_T1_T2_Rel),
+    ("temp-c-t2t1", Mode::TempC_T2_T1),
+    ("temp-c-t2t1-rel", Mode::TempC_T2_T1_Rel),
+    ("temp-c-t1t2-diff", Mode::TempC_T1_T2_Diff),
+    ("temp-c-t2t1-diff", Mode::TempC_T2_T1_Diff),
+    ("temp-f-t1t2", Mode::TempF_T1_T2),
+    ("temp-f-t1t2-rel", Mode::TempF_T1_T2_Rel),
+    ("temp-f-t2t1", Mode::TempF_T2_T1),
+    ("temp-f-t2t1-rel", Mode::TempF_T2_T1_Rel),
+    ("temp-f-t1t2-diff", Mode::TempF_T1_T2_Diff),
+    ("temp-f-t2t1-diff", Mode::TempF_T2_T1_Diff),
+    ("res", Mode::Resistance),
+    ("res-rel", Mode::Resistance_Rel),
+    ("beeper-short", Mode::Beeper_Short),
+    ("beeper-open", Mode::Beeper_Open),
+    ("adm", Mode::Admittance),
+    ("adm-rel", Mode::Admittance_Rel),
+    ("diode", Mode::Diode_Normal),
+    ("diode-alarm", Mode::Diode_Alarm),
+    ("cap", Mode::Capacitance),
+    ("cap-rel", Mode::Capacitance_Rel),
+    ("freq", Mode::Frequency),
+    ("freq-rel", Mode::Frequency_Rel),
+    ("duty", Mode::DutyCycle),
+    ("duty-rel", Mode::DutyCycle_Rel),
+    ("pulse", Mode::PulseWidth),
+    ("pulse-rel", Mode::PulseWidth_Rel),
+    ("uadc", Mode::uADC_Normal),
+    ("uadc-rel", Mode::uADC_Normal_Rel),
+    ("uadc-acdc", Mode::uADC_AC_DC),
+    ("uadc-acdc-rel", Mode::uADC_AC_DC_Rel),
+    ("uadc-peak", Mode::uADC_Peak),
+    ("madc", Mode::mADC_Normal),
+    ("madc-rel", Mode::mADC_Normal_Rel),
+    ("madc-acdc", Mode::mADC_AC_DC),
+    ("madc-acdc-rel", Mode::mADC_AC_DC_Rel),
+    ("madc-peak", Mode::mADC_Peak),
+    ("adc", Mode::ADC_Normal),
+    ("adc-rel", Mode::ADC_Normal_Rel),
+    ("adc-acdc", Mode::ADC_AC_DC),
+    ("adc-acdc-rel", Mode::ADC_AC_DC_Rel),
+    ("adc-peak", Mode::ADC_Peak),
+    ("uaac", Mode::uAAC_Normal),
+    ("uaac-rel", Mode::uAAC_Normal_Rel),
+    ("uaac-hz", Mode::uAAC_Hz),
+    ("uaac-peak", Mode::uAAC_Peak),
+    ("maac", Mode::mAAC_Normal),
+    ("maac-rel", Mode::mAAC_Normal_Rel),
+    ("maac-hz", Mode::mAAC_Hz),
+    ("maac-peak", Mode::mAAC_Peak),
+    ("aac", Mode::AAC_Normal),
+    ("aac-rel", Mode::AAC_Normal_Rel),
+    ("aac-hz", Mode::AAC_Hz),
+    ("aac-peak", Mode::AAC_Peak),
+];
+
+fn normalize(s: &str) -> String {
+    s.trim().to_lowercase().replace('/', "-")
+}
+
+/// Parses mode by its CLI name (`vac-dbm-rel`) or `Display` form (`VAC/dBm/Rel`).
+///
+/// Names are case-insensitive, `/` and `-` separators are interchangeable.
+pub(crate) fn parse_mode(s: &str) -> Result<Mode> {
+    let name = normalize(s);
+    MODES
+        .iter()
+        .find(|&&(n, _)| n == name)
+        .or_else(|| {
+            MODES
+                .iter()
+                .find(|&&(_, m)| normalize(&m.to_string()) == name)
+        })
+        .map(|&(_, m)| m)
+        .ok_or_else(|| ErrorKind::UnknownMode(s.to_owned()).into())
+}
+
+/// CLI name of mode (`vdc-rel`).
+pub(crate) fn mode_name(m: Mode) -> &'static str {
+    let display = m.to_string();
+    MODES
+        .iter()
+        .find(|&&(_, mode)| mode.to_string() == display)
+        .map_or("", |&(n, _)| n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_and_display_names() {
+        for s in &["vac-dbm-rel", "VAC/dBm/Rel", "Vac-DBM/rel", " vac/dbm-REL "] {
+            let m = parse_mode(s).unwrap();
+            assert_eq!(mode_name(m), "vac-dbm-rel", "'{}'", s);
+        }
+    }
+
+    #[test]
+    fn unknown_mode() {
+        assert!(parse_mode("vac-dbm-abs").is_err());
+        assert!(parse_mode("").is_err());
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for &(name, mode) in MODES {
+            assert_eq!(mode_name(parse_mode(name).unwrap()), name);
+            assert_eq!(mode_name(parse_mode(&mode.to_string()).unwrap()), name);
+        }
+    }
+}
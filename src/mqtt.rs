@@ -0,0 +1,213 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use rumqttc::{Client, Connection, Event, Incoming, LastWill, MqttOptions, QoS};
+
+use ut181a::Measurement;
+
+use color;
+use error::*;
+use interrupt;
+use json::{measurement_json, range_from_name, range_name};
+use measurement::{is_holded, mode, range};
+use meter::Meter;
+use modes::parse_mode;
+
+const DEFAULT_PORT: u16 = 1883;
+
+/// Capacity of outgoing message queue, measurements are dropped when it's full.
+const QUEUE_CAPACITY: usize = 64;
+
+pub(crate) struct PublishOptions<'a> {
+    /// Broker address (`host` or `host:port`).
+    pub(crate) broker: &'a str,
+    pub(crate) prefix: &'a str,
+    pub(crate) client_id: &'a str,
+    pub(crate) credentials: Option<(&'a str, &'a str)>,
+}
+
+/// Messages from MQTT connection thread.
+enum Message {
+    Command(String),
+    Error(String),
+}
+
+/// Last published retained state (mode, range, hold).
+#[derive(Default, PartialEq)]
+struct State {
+    mode: String,
+    range: String,
+    hold: bool,
+}
+
+fn broker_address(broker: &str) -> Result<(String, u16)> {
+    match broker.rfind(':') {
+        Some(i) => Ok((broker[..i].to_owned(), broker[i + 1..].parse::<u16>()?)),
+        None => Ok((broker.to_owned(), DEFAULT_PORT)),
+    }
+}
+
+/// Drives MQTT connection and forwards received commands to DMM thread.
+fn connection_loop(
+    client: Client,
+    mut connection: Connection,
+    command_topic: String,
+    tx: Sender<Message>,
+    stopped: Arc<AtomicBool>,
+) {
+    for event in connection.iter() {
+        if stopped.load(Ordering::SeqCst) {
+            break;
+        }
+        match event {
+            Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                // Subscription isn't kept by broker for clean sessions.
+                let _ = client.try_subscribe(command_topic.as_str(), QoS::AtLeastOnce);
+            }
+            Ok(Event::Incoming(Incoming::Publish(p))) => {
+                let cmd = String::from_utf8_lossy(&p.payload).into_owned();
+                let _ = tx.send(Message::Command(cmd));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                let _ = tx.send(Message::Error(e.to_string()));
+                thread::sleep(Duration::from_secs(1));
+            }
+        }
+    }
+}
+
+/// Executes text command (`mode vdc`, `range step2`, `hold`, `save`, `ref 1.5`).
+fn execute(dmm: &mut dyn Meter, cmd: &str, verbose: bool) -> Result<()> {
+    let words: Vec<&str> = cmd.split_whitespace().collect();
+    if verbose {
+        diagln!("Received command '{}'.", cmd);
+    }
+    dmm.monitor_off()?;
+    let result = match *words.as_slice() {
+        ["mode", name] => parse_mode(name).and_then(|mode| dmm.set_mode(mode)),
+        ["range", name] => range_from_name(name).and_then(|r| dmm.set_range(r)),
+        ["hold"] => dmm.toggle_hold(),
+        ["save"] => dmm.save_measurement(),
+        ["ref", value] => value
+            .parse::<f32>()
+            .map_err(Error::from)
+            .and_then(|v| dmm.set_reference_value(v)),
+        _ => Err(ErrorKind::UnknownCliCommand(cmd.to_owned()).into()),
+    };
+    dmm.monitor_on()?;
+    result
+}
+
+fn state(m: &Measurement) -> State {
+    State {
+        mode: mode(m).to_string(),
+        range: range_name(range(m)).to_owned(),
+        hold: is_holded(m),
+    }
+}
+
+/// Publishes readings to `<prefix>/measurement` and state to retained
+/// `<prefix>/state/{mode,range,hold}` topics until Ctrl-C is pressed.
+///
+/// Commands received on `<prefix>/command` are executed,
+/// results are published to `<prefix>/command/result`.
+pub(crate) fn publish(dmm: &mut dyn Meter, opts: &PublishOptions, verbose: bool) -> Result<()> {
+    let (host, port) = broker_address(opts.broker)?;
+    let status_topic = format!("{}/status", opts.prefix);
+    let command_topic = format!("{}/command", opts.prefix);
+    let mut mqtt_opts = MqttOptions::new(opts.client_id, host, port);
+    mqtt_opts.set_keep_alive(Duration::from_secs(30));
+    mqtt_opts.set_last_will(LastWill::new(
+        status_topic.as_str(),
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+    if let Some((user, password)) = opts.credentials {
+        mqtt_opts.set_credentials(user, password);
+    }
+
+    let (client, connection) = Client::new(mqtt_opts, QUEUE_CAPACITY);
+    let (tx, rx): (Sender<Message>, Receiver<Message>) = mpsc::channel();
+    let stopped = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let client = client.clone();
+        let stopped = stopped.clone();
+        thread::spawn(move || connection_loop(client, connection, command_topic, tx, stopped))
+    };
+    println!("Publishing to '{}/#' on {}.", opts.prefix, opts.broker);
+
+    let publish = |topic: &str, retain: bool, payload: String| {
+        client
+            .try_publish(
+                format!("{}/{}", opts.prefix, topic),
+                QoS::AtLeastOnce,
+                retain,
+                payload,
+            )
+            .is_ok()
+    };
+
+    interrupt::catch()?;
+    if verbose {
+        diagln!("Sending 'MONITOR ON' command to DMM.");
+    }
+    dmm.monitor_on()?;
+    publish("status", true, "online".to_owned());
+    let mut published = State::default();
+    let mut last_error = String::new();
+    let result = loop {
+        if interrupt::is_interrupted() {
+            break Ok(());
+        }
+        for msg in rx.try_iter() {
+            match msg {
+                Message::Command(cmd) => {
+                    let reply = match execute(dmm, &cmd, verbose) {
+                        Ok(_) => json!({"command": cmd, "ok": true}),
+                        Err(e) => json!({"command": cmd, "ok": false, "error": e.to_string()}),
+                    };
+                    publish("command/result", false, reply.to_string());
+                }
+                Message::Error(e) => {
+                    if e != last_error {
+                        color::eprint_error(format!("MQTT error: {}", e));
+                        last_error = e;
+                    }
+                }
+            }
+        }
+
+        let m = match dmm.get_measurement() {
+            Ok(m) => m,
+            Err(e) => break Err(e),
+        };
+        // Measurements are dropped while broker is unavailable.
+        publish("measurement", false, measurement_json(&m).to_string());
+        let current = state(&m);
+        if current != published {
+            let sent = [
+                publish("state/mode", true, current.mode.clone()),
+                publish("state/range", true, current.range.clone()),
+                publish("state/hold", true, current.hold.to_string()),
+            ];
+            if sent.iter().all(|&ok| ok) {
+                published = current;
+            }
+        }
+    };
+
+    publish("status", true, "offline".to_owned());
+    stopped.store(true, Ordering::SeqCst);
+    let _ = client.try_disconnect();
+    let _ = thread.join();
+    if verbose {
+        diagln!("Sending 'MONITOR OFF' command to DMM.");
+    }
+    dmm.monitor_off()?;
+    result
+}
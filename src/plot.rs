@@ -0,0 +1,436 @@
+use std::fmt::Write as FmtWrite;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{Options, Tree};
+use serde_json::{self, Value as Json};
+
+use ut181a::{RecordDataItem, RecordInfo, UnitExp, Value};
+
+use error::*;
+use json::{measurement_from_json, parse_timestamp, unit_from_str};
+use measurement::{is_overload, main_value};
+
+const WIDTH: f64 = 960.0;
+const HEIGHT: f64 = 540.0;
+const MARGIN_LEFT: f64 = 90.0;
+const MARGIN_RIGHT: f64 = 120.0;
+const MARGIN_TOP: f64 = 50.0;
+const MARGIN_BOTTOM: f64 = 60.0;
+
+/// Generic `sans-serif` alone can't be resolved by `usvg` if Arial is missing.
+const FONT_FAMILY: &str = "DejaVu Sans, Liberation Sans, Arial, Helvetica, sans-serif";
+
+/// Approximate count of axis ticks.
+const TICKS: f64 = 6.0;
+
+/// Samples are marked with dots if there are less of them.
+const MAX_MARKED_SAMPLES: usize = 100;
+
+/// Candidate steps of time axis (in seconds).
+const TIME_STEPS: &[i64] = &[
+    1, 2, 5, 10, 15, 30, 60, 120, 300, 600, 900, 1800, 3600, 7200, 10800, 21600, 43200, 86400,
+    172_800, 604_800,
+];
+
+/// Time series with reference lines.
+pub(crate) struct Chart {
+    pub(crate) title: String,
+    /// Unit of values (Y axis label).
+    pub(crate) unit: String,
+    /// Samples, `None` is overload.
+    pub(crate) points: Vec<(NaiveDateTime, Option<f64>)>,
+    /// Horizontal lines (`min`, `average`, `max`).
+    pub(crate) lines: Vec<(&'static str, f64)>,
+}
+
+/// Value in `unit` (`None` on overload).
+fn value_in(v: &Value, unit: UnitExp) -> Option<f64> {
+    if is_overload(v) {
+        None
+    } else {
+        Some(f64::from(v.value) * 10f64.powi((v.unit.exponent - unit.exponent) as i32))
+    }
+}
+
+impl Chart {
+    /// Chart of record data with min/average/max reported by DMM.
+    pub(crate) fn from_record(info: &RecordInfo, items: &[RecordDataItem]) -> Chart {
+        let points = items
+            .iter()
+            .map(|item| (item.timestamp, value_in(&item.value, info.unit)))
+            .collect();
+        let lines = [
+            ("min", &info.min),
+            ("average", &info.average),
+            ("max", &info.max),
+        ]
+        .iter()
+        .filter_map(|&(label, v)| value_in(v, info.unit).map(|v| (label, v)))
+        .collect();
+        Chart {
+            title: info.name.clone(),
+            unit: info.unit.to_string(),
+            points,
+            lines,
+        }
+    }
+
+    /// Chart of file written by `log` (CSV or NDJSON) or `record export` (CSV).
+    ///
+    /// Min/average/max lines are calculated from samples.
+    pub(crate) fn from_file(path: &Path) -> Result<Chart> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let mut title = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        // Record CSV starts with `# key=value` lines of record info.
+        let first = loop {
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return Err(format!("file '{}' is empty", path.display()).into()),
+            };
+            match line.strip_prefix('#') {
+                Some(meta) => {
+                    if let Some(name) = meta.trim_start().strip_prefix("name=") {
+                        title = name.to_owned();
+                    }
+                }
+                None => break line,
+            }
+        };
+        let mut unit: Option<UnitExp> = None;
+        let mut points = Vec::new();
+        let mut add = |time: NaiveDateTime, v: Value| {
+            let u = *unit.get_or_insert(v.unit);
+            // Samples of other quantity (mode is changed) are skipped.
+            if u.unit == v.unit.unit {
+                points.push((time, value_in(&v, u)));
+            }
+        };
+
+        if first.trim_start().starts_with('{') {
+            for line in Some(Ok(first)).into_iter().chain(lines) {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let j: Json = serde_json::from_str(&line)?;
+                let time = parse_log_time(j["time"].as_str().unwrap_or(""))?;
+                add(time, main_value(&measurement_from_json(&j)?).clone());
+            }
+        } else {
+            let header = split_csv_line(&first);
+            let column = |name: &str| {
+                header.iter().position(|c| c == name).ok_or_else(|| {
+                    Error::from(format!("no '{}' column in '{}'", name, path.display()))
+                })
+            };
+            // Record CSV has `timestamp` column, log CSV has `time` column.
+            let time_col = column("timestamp").or_else(|_| column("time"))?;
+            let (value_col, unit_col, flag_col) =
+                (column("value")?, column("unit")?, column("flag")?);
+            for line in lines {
+                let row = split_csv_line(&line?);
+                if row.len() < header.len() {
+                    continue;
+                }
+                let time = parse_log_time(&row[time_col])?;
+                let unit = unit_from_str(&row[unit_col])?;
+                let overload = !row[flag_col].is_empty();
+                let v = Value {
+                    value: if overload {
+                        f32::NAN
+                    } else {
+                        row[value_col].parse::<f32>()?
+                    },
+                    unit,
+                    precision: None,
+                    overload_pos: overload,
+                    overload_neg: false,
+                };
+                add(time, v);
+            }
+        }
+
+        let values: Vec<f64> = points.iter().filter_map(|&(_, v)| v).collect();
+        let mut stats = Vec::new();
+        if !values.is_empty() {
+            let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let average = values.iter().sum::<f64>() / values.len() as f64;
+            stats = vec![("min", min), ("average", average), ("max", max)];
+        }
+        Ok(Chart {
+            title,
+            unit: unit.map(|u| u.to_string()).unwrap_or_default(),
+            points,
+            lines: stats,
+        })
+    }
+}
+
+/// Parses time of log (with milliseconds) or record timestamp.
+fn parse_log_time(s: &str) -> Result<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f").or_else(|_| parse_timestamp(s))
+}
+
+/// Splits CSV line into fields (quoted fields can contain commas and `""`).
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches(['\r', '\n']).chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Nice axis step (1, 2 or 5 times power of 10) for `span`.
+fn nice_step(span: f64) -> f64 {
+    let rough = span / TICKS;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|&s| s >= rough)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Formats tick label with enough decimals for `step`.
+fn format_tick(v: f64, step: f64) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    format!("{:.*}", decimals, v)
+}
+
+impl Chart {
+    /// Renders chart as SVG document.
+    pub(crate) fn to_svg(&self) -> String {
+        let plot_w = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+        let plot_h = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+
+        let values = self
+            .points
+            .iter()
+            .filter_map(|&(_, v)| v)
+            .chain(self.lines.iter().map(|&(_, v)| v));
+        let (mut lo, mut hi) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+            (lo.min(v), hi.max(v))
+        });
+        if !lo.is_finite() {
+            lo = 0.0;
+            hi = 1.0;
+        }
+        if hi - lo < 1e-12 {
+            let pad = if lo == 0.0 { 1.0 } else { lo.abs() * 0.01 };
+            lo -= pad;
+            hi += pad;
+        }
+        let y_step = nice_step(hi - lo);
+        let (y_min, y_max) = (
+            (lo / y_step).floor() * y_step,
+            (hi / y_step).ceil() * y_step,
+        );
+
+        let t0 = self.points.first().map(|&(t, _)| t);
+        let t1 = self.points.last().map(|&(t, _)| t);
+        let span = match (t0, t1) {
+            (Some(t0), Some(t1)) => (t1 - t0).num_milliseconds() as f64 / 1000.0,
+            _ => 0.0,
+        }
+        .max(1.0);
+        let secs = |t: NaiveDateTime| match t0 {
+            Some(t0) => (t - t0).num_milliseconds() as f64 / 1000.0,
+            None => 0.0,
+        };
+        let x = |s: f64| MARGIN_LEFT + s / span * plot_w;
+        let y = |v: f64| MARGIN_TOP + (y_max - v) / (y_max - y_min) * plot_h;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 0 {w} {h}\" font-family=\"{font}\" font-size=\"12\">",
+            font = FONT_FAMILY,
+            w = WIDTH,
+            h = HEIGHT
+        );
+        let _ = writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>");
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"30\" text-anchor=\"middle\" font-size=\"18\">{}</text>",
+            WIDTH / 2.0,
+            escape(&self.title)
+        );
+
+        // Y grid and labels.
+        let mut v = y_min;
+        while v <= y_max + y_step / 2.0 {
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{x1}\" y1=\"{y:.1}\" x2=\"{x2}\" y2=\"{y:.1}\" stroke=\"#ddd\"/>\
+                 <text x=\"{tx}\" y=\"{ty:.1}\" text-anchor=\"end\">{label}</text>",
+                x1 = MARGIN_LEFT,
+                x2 = MARGIN_LEFT + plot_w,
+                y = y(v),
+                tx = MARGIN_LEFT - 6.0,
+                ty = y(v) + 4.0,
+                label = format_tick(v, y_step)
+            );
+            v += y_step;
+        }
+
+        // X grid and labels.
+        if let Some(t0) = t0 {
+            let step = TIME_STEPS
+                .iter()
+                .cloned()
+                .find(|&s| span / s as f64 <= TICKS + 2.0)
+                .unwrap_or(604_800);
+            let time_format = if span < 86400.0 {
+                "%H:%M:%S"
+            } else {
+                "%m-%d %H:%M"
+            };
+            let mut s = 0;
+            while s as f64 <= span {
+                let t = t0 + chrono::Duration::seconds(s);
+                let _ = writeln!(
+                    svg,
+                    "<line x1=\"{x:.1}\" y1=\"{y1}\" x2=\"{x:.1}\" y2=\"{y2}\" stroke=\"#ddd\"/>\
+                     <text x=\"{x:.1}\" y=\"{ty}\" text-anchor=\"middle\">{label}</text>",
+                    x = x(s as f64),
+                    y1 = MARGIN_TOP,
+                    y2 = MARGIN_TOP + plot_h,
+                    ty = MARGIN_TOP + plot_h + 18.0,
+                    label = t.format(time_format)
+                );
+                s += step;
+            }
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">Time (from {})</text>",
+                MARGIN_LEFT + plot_w / 2.0,
+                HEIGHT - 15.0,
+                t0.format("%Y-%m-%d %H:%M:%S")
+            );
+        }
+
+        // Axes.
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>",
+            MARGIN_LEFT, MARGIN_TOP, plot_w, plot_h
+        );
+        let _ = writeln!(
+            svg,
+            "<text transform=\"translate(20 {}) rotate(-90)\" text-anchor=\"middle\">{}</text>",
+            MARGIN_TOP + plot_h / 2.0,
+            escape(&self.unit)
+        );
+
+        // Reference lines.
+        for &(label, v) in &self.lines {
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{x1}\" y1=\"{y:.1}\" x2=\"{x2}\" y2=\"{y:.1}\" stroke=\"#d62728\" \
+                 stroke-dasharray=\"6 4\"/>\
+                 <text x=\"{tx}\" y=\"{ty:.1}\" fill=\"#d62728\">{label} {value}</text>",
+                x1 = MARGIN_LEFT,
+                x2 = MARGIN_LEFT + plot_w,
+                y = y(v),
+                tx = MARGIN_LEFT + plot_w + 6.0,
+                ty = y(v) + 4.0,
+                label = label,
+                value = format_tick(v, y_step / 100.0)
+            );
+        }
+
+        // Samples, overloads break the line.
+        let mut segments: Vec<Vec<(f64, f64)>> = vec![Vec::new()];
+        for &(t, v) in &self.points {
+            match v {
+                Some(v) => segments
+                    .last_mut()
+                    .expect("segment")
+                    .push((x(secs(t)), y(v))),
+                None => segments.push(Vec::new()),
+            }
+        }
+        for segment in segments.iter().filter(|s| !s.is_empty()) {
+            let points: Vec<String> = segment
+                .iter()
+                .map(|&(x, y)| format!("{:.1},{:.1}", x, y))
+                .collect();
+            let _ = writeln!(
+                svg,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"#1f77b4\" stroke-width=\"1.5\"/>",
+                points.join(" ")
+            );
+        }
+        if self.points.len() < MAX_MARKED_SAMPLES {
+            for &(x, y) in segments.iter().flatten() {
+                let _ = writeln!(
+                    svg,
+                    "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"2.5\" fill=\"#1f77b4\"/>",
+                    x, y
+                );
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Writes chart to SVG or PNG file (by extension).
+    pub(crate) fn save(&self, path: &Path) -> Result<()> {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match ext.as_deref() {
+            Some("svg") => fs::write(path, self.to_svg())?,
+            Some("png") => {
+                let mut opts = Options::default();
+                opts.fontdb_mut().load_system_fonts();
+                let tree = Tree::from_str(&self.to_svg(), &opts)
+                    .map_err(|e| Error::from(format!("can't render chart: {}", e)))?;
+                let size = tree.size().to_int_size();
+                let mut pixmap =
+                    Pixmap::new(size.width(), size.height()).ok_or("can't allocate image")?;
+                resvg::render(&tree, Transform::default(), &mut pixmap.as_mut());
+                pixmap
+                    .save_png(path)
+                    .map_err(|e| Error::from(format!("can't write '{}': {}", path.display(), e)))?;
+            }
+            _ => {
+                return Err(format!(
+                    "unsupported chart file '{}' (use .svg or .png)",
+                    path.display()
+                )
+                .into())
+            }
+        }
+        Ok(())
+    }
+}
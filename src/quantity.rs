@@ -0,0 +1,125 @@
+use error::*;
+
+/// Physical quantity parsed from user input (`3.3V`, `600mV`, `60k`, `6uF`).
+#[derive(Clone, Debug)]
+pub(crate) struct Quantity {
+    /// Value in base units (without SI prefix).
+    pub(crate) value: f64,
+    /// Unit without SI prefix (it can be empty).
+    pub(crate) unit: String,
+}
+
+fn prefix_exponent(c: char) -> Option<i32> {
+    match c {
+        'p' => Some(-12),
+        'n' => Some(-9),
+        'u' | 'µ' => Some(-6),
+        'm' => Some(-3),
+        'k' | 'K' => Some(3),
+        'M' => Some(6),
+        'G' => Some(9),
+        _ => None,
+    }
+}
+
+/// Parses number with optional SI prefix and unit.
+pub(crate) fn parse_quantity(s: &str) -> Result<Quantity> {
+    let s = s.trim();
+    let invalid = || Error::from(ErrorKind::InvalidQuantity(s.to_owned()));
+    let is_sign = |c: char| c == '-' || c == '+';
+    let is_exponent = |c: char| c == 'e' || c == 'E';
+    // `e` starts exponent only if (signed) digits follow, otherwise it's unit
+    let exponent_follows = |i: usize| {
+        let rest = &s[i + 1..];
+        rest.strip_prefix(is_sign)
+            .unwrap_or(rest)
+            .starts_with(|c: char| c.is_ascii_digit())
+    };
+    let number_len = s
+        .char_indices()
+        .find(|&(i, c)| {
+            !(c.is_ascii_digit()
+                || c == '.'
+                || (is_sign(c) && (i == 0 || s[..i].ends_with(is_exponent)))
+                || (is_exponent(c) && exponent_follows(i)))
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(s.len());
+    let value = s[..number_len].parse::<f64>().map_err(|_| invalid())?;
+    let rest = s[number_len..].trim_start();
+
+    let mut chars = rest.chars();
+    let (exponent, unit) = match chars.next().and_then(prefix_exponent) {
+        Some(exp) => (exp, chars.as_str()),
+        None => (0, rest),
+    };
+    if unit.contains(|c: char| c.is_ascii_digit() || c.is_whitespace()) {
+        return Err(invalid());
+    }
+    Ok(Quantity {
+        value: value * 10f64.powi(exponent),
+        unit: unit.to_owned(),
+    })
+}
+
+/// Formats value in base units with SI prefix (`3.234 V`, `4.7 kOhm`).
+pub(crate) fn format_quantity(value: f64, unit: &str) -> String {
+    const PREFIXES: &[(i32, &str)] = &[
+        (9, "G"),
+        (6, "M"),
+        (3, "k"),
+        (0, ""),
+        (-3, "m"),
+        (-6, "u"),
+        (-9, "n"),
+        (-12, "p"),
+    ];
+    let &(exp, prefix) = PREFIXES
+        .iter()
+        .find(|&&(exp, _)| value.abs() >= 10f64.powi(exp))
+        .unwrap_or(&(0, ""));
+    let number = format!("{:.4}", value / 10f64.powi(exp));
+    let number = number.trim_end_matches('0').trim_end_matches('.');
+    format!("{} {}{}", number, prefix, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_quantity(s: &str, value: f64, unit: &str) {
+        let q = parse_quantity(s).unwrap();
+        assert!(
+            (q.value - value).abs() <= value.abs() * 1e-12,
+            "'{}': {}",
+            s,
+            q.value
+        );
+        assert_eq!(q.unit, unit, "'{}'", s);
+    }
+
+    #[test]
+    fn prefix_and_unit() {
+        assert_quantity("3.3V", 3.3, "V");
+        assert_quantity("600mV", 0.6, "V");
+        assert_quantity("60k", 60e3, "");
+        assert_quantity("6uF", 6e-6, "F");
+        assert_quantity("-5 V", -5.0, "V");
+        assert_quantity("4.7kOhm", 4700.0, "Ohm");
+    }
+
+    #[test]
+    fn exponent() {
+        assert_quantity("1e-3", 1e-3, "");
+        assert_quantity("1E-3V", 1e-3, "V");
+        assert_quantity("2.5e+1", 25.0, "");
+        assert_quantity("1e3mV", 1.0, "V");
+    }
+
+    #[test]
+    fn invalid_quantity() {
+        for s in &["", "V", "abc", "5 V 3", "1.2.3V"] {
+            assert!(parse_quantity(s).is_err(), "'{}' is accepted", s);
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use std::fmt;
+
+use serde_json::Value as Json;
+
+use ut181a::Range::{Step1, Step2, Step3, Step4, Step5, Step6, Step7, Step8};
+use ut181a::{Mode, Range};
+
+use error::*;
+use json::range_name;
+use quantity::parse_quantity;
+
+/// Measuring range of mode.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RangeInfo {
+    pub(crate) step: Range,
+    /// Lower limit (in `unit`).
+    pub(crate) lower: f64,
+    /// Upper limit (in `unit`).
+    pub(crate) upper: f64,
+    /// Display unit with SI prefix (`mV`, `kOhm`).
+    pub(crate) unit: &'static str,
+    /// Decimal exponent of SI prefix of `unit`.
+    pub(crate) exponent: isize,
+    /// Value of one count (in `unit`).
+    pub(crate) resolution: f64,
+}
+
+const fn r(
+    step: Range,
+    lower: f64,
+    upper: f64,
+    unit: &'static str,
+    exponent: isize,
+    resolution: f64,
+) -> RangeInfo {
+    RangeInfo {
+        step,
+        lower,
+        upper,
+        unit,
+        exponent,
+        resolution,
+    }
+}
+
+impl RangeInfo {
+    /// Count of display digits at full scale (e.g. 60000).
+    pub(crate) fn counts(&self) -> u32 {
+        (self.upper.max(-self.lower) / self.resolution).round() as u32
+    }
+
+    /// Value of one count in base units (V, A, Ohm, ...).
+    pub(crate) fn base_resolution(&self) -> f64 {
+        self.resolution * 10f64.powi(self.exponent as i32)
+    }
+
+    /// Upper limit in base units.
+    pub(crate) fn base_upper(&self) -> f64 {
+        self.upper * 10f64.powi(self.exponent as i32)
+    }
+
+    /// Unit without SI prefix (`V`, `Ohm`).
+    pub(crate) fn base_unit(&self) -> &'static str {
+        if self.exponent == 0 {
+            self.unit
+        } else {
+            &self.unit[1..]
+        }
+    }
+
+    /// Resolution formatted with its decimals (`0.0001 V`).
+    pub(crate) fn format_resolution(&self) -> String {
+        let decimals = (-self.resolution.log10().floor()).max(0.0) as usize;
+        format!("{:.*} {}", decimals, self.resolution, self.unit)
+    }
+
+    pub(crate) fn to_json(self) -> Json {
+        json!({
+            "step": range_name(self.step),
+            "lower": self.lower,
+            "upper": self.upper,
+            "unit": self.unit,
+            "resolution": self.resolution,
+            "counts": self.counts(),
+        })
+    }
+}
+
+impl fmt::Display for RangeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}...{} {}", self.lower, self.upper, self.unit)
+    }
+}
+
+const MVDC: &[RangeInfo] = &[
+    r(Step1, -60.0, 60.0, "mV", -3, 0.001),
+    r(Step2, -600.0, 600.0, "mV", -3, 0.01),
+];
+const MVAC: &[RangeInfo] = &[
+    r(Step1, 0.0, 60.0, "mV", -3, 0.001),
+    r(Step2, 0.0, 600.0, "mV", -3, 0.01),
+];
+const VDC: &[RangeInfo] = &[
+    r(Step1, -6.0, 6.0, "V", 0, 0.0001),
+    r(Step2, -60.0, 60.0, "V", 0, 0.001),
+    r(Step3, -600.0, 600.0, "V", 0, 0.01),
+    r(Step4, -1000.0, 1000.0, "V", 0, 0.1),
+];
+const VAC: &[RangeInfo] = &[
+    r(Step1, 0.0, 6.0, "V", 0, 0.0001),
+    r(Step2, 0.0, 60.0, "V", 0, 0.001),
+    r(Step3, 0.0, 600.0, "V", 0, 0.01),
+    r(Step4, 0.0, 1000.0, "V", 0, 0.1),
+];
+const OHM: &[RangeInfo] = &[
+    r(Step1, 0.0, 600.0, "Ohm", 0, 0.01),
+    r(Step2, 0.0, 6.0, "kOhm", 3, 0.0001),
+    r(Step3, 0.0, 60.0, "kOhm", 3, 0.001),
+    r(Step4, 0.0, 600.0, "kOhm", 3, 0.01),
+    r(Step5, 0.0, 6.0, "MOhm", 6, 0.0001),
+    r(Step6, 0.0, 60.0, "MOhm", 6, 0.001),
+];
+const SIEMENS: &[RangeInfo] = &[r(Step1, 0.0, 60.0, "nS", -9, 0.001)];
+const DIODE: &[RangeInfo] = &[r(Step1, 0.0, 3.0, "V", 0, 0.0001)];
+const FARAD: &[RangeInfo] = &[
+    r(Step1, 0.0, 6.0, "nF", -9, 0.0001),
+    r(Step2, 0.0, 60.0, "nF", -9, 0.001),
+    r(Step3, 0.0, 600.0, "nF", -9, 0.01),
+    r(Step4, 0.0, 6.0, "uF", -6, 0.0001),
+    r(Step5, 0.0, 60.0, "uF", -6, 0.001),
+    r(Step6, 0.0, 600.0, "uF", -6, 0.01),
+    r(Step7, 0.0, 6.0, "mF", -3, 0.0001),
+    r(Step8, 0.0, 60.0, "mF", -3, 0.001),
+];
+const HZ: &[RangeInfo] = &[
+    r(Step1, 0.0, 60.0, "Hz", 0, 0.001),
+    r(Step2, 0.0, 600.0, "Hz", 0, 0.01),
+    r(Step3, 0.0, 6.0, "kHz", 3, 0.0001),
+    r(Step4, 0.0, 60.0, "kHz", 3, 0.001),
+    r(Step5, 0.0, 600.0, "kHz", 3, 0.01),
+    r(Step6, 0.0, 6.0, "MHz", 6, 0.0001),
+    r(Step7, 0.0, 60.0, "MHz", 6, 0.001),
+];
+const UADC: &[RangeInfo] = &[
+    r(Step1, -600.0, 600.0, "uA", -6, 0.01),
+    r(Step2, -6000.0, 6000.0, "uA", -6, 0.1),
+];
+const UAAC: &[RangeInfo] = &[
+    r(Step1, 0.0, 600.0, "uA", -6, 0.01),
+    r(Step2, 0.0, 6000.0, "uA", -6, 0.1),
+];
+const MADC: &[RangeInfo] = &[
+    r(Step1, -60.0, 60.0, "mA", -3, 0.001),
+    r(Step2, -600.0, 600.0, "mA", -3, 0.01),
+];
+const MAAC: &[RangeInfo] = &[
+    r(Step1, 0.0, 60.0, "mA", -3, 0.001),
+    r(Step2, 0.0, 600.0, "mA", -3, 0.01),
+];
+const ADC: &[RangeInfo] = &[r(Step1, -20.0, 20.0, "A", 0, 0.001)];
+const AAC: &[RangeInfo] = &[r(Step1, 0.0, 20.0, "A", 0, 0.001)];
+
+const TABLES: &[&[RangeInfo]] = &[
+    MVDC, MVAC, VDC, VAC, OHM, SIEMENS, DIODE, FARAD, HZ, UADC, UAAC, MADC, MAAC, ADC, AAC,
+];
+
+/// Upper limits of all modes at `step` (`60 mV/6 V/...`) for help of range commands.
+pub(crate) fn step_summary(step: Range) -> String {
+    let mut limits: Vec<String> = Vec::new();
+    for info in TABLES
+        .iter()
+        .flat_map(|t| t.iter())
+        .filter(|info| info.step == step)
+    {
+        let limit = format!("{} {}", info.upper, info.unit);
+        if !limits.contains(&limit) {
+            limits.push(limit);
+        }
+    }
+    limits.join("/")
+}
+
+/// Ranges of mode (empty for temperature modes, they have no selectable range).
+pub(crate) fn mode_ranges(m: Mode) -> &'static [RangeInfo] {
+    match m {
+        Mode::mVDC_Normal | Mode::mVDC_Normal_Rel | Mode::mVDC_Peak => MVDC,
+
+        Mode::mVAC_Normal
+        | Mode::mVAC_Normal_Rel
+        | Mode::mVAC_Peak
+        | Mode::mVAC_Hz
+        | Mode::mVAC_AC_DC
+        | Mode::mVAC_AC_DC_Rel => MVAC,
+
+        Mode::VDC_Normal
+        | Mode::VDC_Normal_Rel
+        | Mode::VDC_Peak
+        | Mode::VDC_AC_DC
+        | Mode::VDC_AC_DC_Rel => VDC,
+
+        Mode::VAC_Normal
+        | Mode::VAC_Normal_Rel
+        | Mode::VAC_Peak
+        | Mode::VAC_Hz
+        | Mode::VAC_LowPass
+        | Mode::VAC_LowPass_Rel
+        | Mode::VAC_dBV
+        | Mode::VAC_dBV_Rel
+        | Mode::VAC_dBm
+        | Mode::VAC_dBm_Rel => VAC,
+
+        Mode::TempC_T1_T2
+        | Mode::TempC_T1_T2_Rel
+        | Mode::TempC_T2_T1
+        | Mode::TempC_T2_T1_Rel
+        | Mode::TempC_T1_T2_Diff
+        | Mode::TempC_T2_T1_Diff
+        | Mode::TempF_T1_T2
+        | Mode::TempF_T1_T2_Rel
+        | Mode::TempF_T2_T1
+        | Mode::TempF_T2_T1_Rel
+        | Mode::TempF_T1_T2_Diff
+        | Mode::TempF_T2_T1_Diff => &[],
+
+        Mode::Resistance | Mode::Resistance_Rel | Mode::Beeper_Open | Mode::Beeper_Short => OHM,
+
+        Mode::Admittance | Mode::Admittance_Rel => SIEMENS,
+
+        Mode::Diode_Normal | Mode::Diode_Alarm => DIODE,
+
+        Mode::Capacitance | Mode::Capacitance_Rel => FARAD,
+
+        Mode::Frequency
+        | Mode::Frequency_Rel
+        | Mode::DutyCycle
+        | Mode::DutyCycle_Rel
+        | Mode::PulseWidth
+        | Mode::PulseWidth_Rel => HZ,
+
+        Mode::uADC_Normal
+        | Mode::uADC_Normal_Rel
+        | Mode::uADC_Peak
+        | Mode::uADC_AC_DC
+        | Mode::uADC_AC_DC_Rel => UADC,
+
+        Mode::uAAC_Normal | Mode::uAAC_Normal_Rel | Mode::uAAC_Peak | Mode::uAAC_Hz => UAAC,
+
+        Mode::mADC_Normal
+        | Mode::mADC_Normal_Rel
+        | Mode::mADC_Peak
+        | Mode::mADC_AC_DC
+        | Mode::mADC_AC_DC_Rel => MADC,
+
+        Mode::mAAC_Normal | Mode::mAAC_Normal_Rel | Mode::mAAC_Peak | Mode::mAAC_Hz => MAAC,
+
+        Mode::ADC_Normal
+        | Mode::ADC_Normal_Rel
+        | Mode::ADC_Peak
+        | Mode::ADC_AC_DC
+        | Mode::ADC_AC_DC_Rel => ADC,
+
+        Mode::AAC_Normal | Mode::AAC_Normal_Rel | Mode::AAC_Peak | Mode::AAC_Hz => AAC,
+    }
+}
+
+/// Range of mode at `step` (`None` if mode hasn't such range, e.g. it's `Auto`).
+pub(crate) fn range_info(m: Mode, step: Range) -> Option<RangeInfo> {
+    mode_ranges(m)
+        .iter()
+        .find(|info| info.step == step)
+        .cloned()
+}
+
+/// Index of range in `mode_ranges`.
+pub(crate) fn range_index(m: Mode, step: Range) -> Option<usize> {
+    mode_ranges(m).iter().position(|info| info.step == step)
+}
+
+/// Finds range of mode by its full scale (`600mV`, `60k`, `6uF`).
+///
+/// Unit can be omitted, otherwise it must be unit of mode (case-insensitive).
+pub(crate) fn range_by_value(m: Mode, s: &str) -> Result<Range> {
+    let q = parse_quantity(s)?;
+    let ranges = mode_ranges(m);
+    ranges
+        .iter()
+        .find(|info| {
+            (q.unit.is_empty() || q.unit.eq_ignore_ascii_case(info.base_unit()))
+                && (q.value - info.base_upper()).abs() <= info.base_upper() * 1e-6
+        })
+        .map(|info| info.step)
+        .ok_or_else(|| {
+            let valid = if ranges.is_empty() {
+                "auto".to_owned()
+            } else {
+                ranges
+                    .iter()
+                    .map(|info| format!("{}{}", info.upper, info.unit))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            ErrorKind::RangeIsNotAvailable(s.to_owned(), m.to_string(), valid).into()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_ranges(m: Mode, s: &str) -> String {
+        match *range_by_value(m, s).unwrap_err().kind() {
+            ErrorKind::RangeIsNotAvailable(_, _, ref valid) => valid.clone(),
+            ref kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn full_scale_with_unit() {
+        assert!(range_by_value(Mode::mVDC_Normal, "600mV").unwrap() == Step2);
+        assert!(range_by_value(Mode::Resistance, "60kOhm").unwrap() == Step3);
+        assert!(range_by_value(Mode::Capacitance, "6uF").unwrap() == Step4);
+    }
+
+    #[test]
+    fn full_scale_without_unit() {
+        assert!(range_by_value(Mode::Resistance, "60k").unwrap() == Step3);
+        assert!(range_by_value(Mode::mVDC_Normal, "60m").unwrap() == Step1);
+    }
+
+    #[test]
+    fn wrong_unit_or_value() {
+        assert_eq!(valid_ranges(Mode::mVDC_Normal, "600mA"), "60mV, 600mV");
+        assert_eq!(valid_ranges(Mode::mVDC_Normal, "6V"), "60mV, 600mV");
+    }
+
+    #[test]
+    fn temperature_has_only_auto() {
+        assert_eq!(valid_ranges(Mode::TempC_T1_T2, "600mV"), "auto");
+    }
+}
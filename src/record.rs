@@ -0,0 +1,248 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveDateTime};
+use serde_json::{self, Value as Json};
+
+use ut181a::RecordInfo;
+
+use duration::format_short_duration;
+use error::*;
+use interrupt;
+use json::{format_timestamp, parse_timestamp, record_info_json};
+use meter::Meter;
+
+const STATE_FILE: &str = "record.json";
+
+/// Record is considered stopped if no sample is taken for 2 intervals and this time.
+const STALL_SLACK: Duration = Duration::from_secs(5);
+
+/// Recording started by `record start` (DMM doesn't report planned duration).
+pub(crate) struct Plan {
+    pub(crate) index: u16,
+    pub(crate) name: String,
+    /// Host time of start.
+    pub(crate) start: NaiveDateTime,
+    pub(crate) interval: u16,
+    /// Duration in minutes.
+    pub(crate) duration: u32,
+}
+
+impl Plan {
+    pub(crate) fn samples(&self) -> u64 {
+        expected_samples(self.interval, self.duration)
+    }
+
+    pub(crate) fn end(&self) -> NaiveDateTime {
+        self.start + chrono::Duration::minutes(i64::from(self.duration))
+    }
+
+    fn to_json(&self) -> Json {
+        json!({
+            "index": self.index,
+            "name": self.name,
+            "start": format_timestamp(&self.start),
+            "interval": self.interval,
+            "duration": self.duration,
+        })
+    }
+
+    fn from_json(j: &Json) -> Option<Plan> {
+        Some(Plan {
+            index: j["index"].as_u64()? as u16,
+            name: j["name"].as_str()?.to_owned(),
+            start: parse_timestamp(j["start"].as_str()?).ok()?,
+            interval: j["interval"].as_u64()? as u16,
+            duration: j["duration"].as_u64()? as u32,
+        })
+    }
+}
+
+/// Count of samples in record of `duration` minutes (first one is taken at start).
+pub(crate) fn expected_samples(interval: u16, duration: u32) -> u64 {
+    u64::from(duration) * 60 / u64::from(interval) + 1
+}
+
+fn now() -> NaiveDateTime {
+    Local::now().naive_local()
+}
+
+/// `$XDG_STATE_HOME/ut181a-cli/record.json` (`~/.local/state` by default).
+fn state_path() -> PathBuf {
+    let dir = match (env::var_os("XDG_STATE_HOME"), env::var_os("HOME")) {
+        (Some(dir), _) => PathBuf::from(dir),
+        (None, Some(home)) => PathBuf::from(home).join(".local").join("state"),
+        (None, None) => env::temp_dir(),
+    };
+    dir.join("ut181a-cli").join(STATE_FILE)
+}
+
+fn save_plan(plan: &Plan) -> Result<()> {
+    let path = state_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, plan.to_json().to_string())?;
+    Ok(())
+}
+
+fn load_plan() -> Option<Plan> {
+    let s = fs::read_to_string(state_path()).ok()?;
+    Plan::from_json(&serde_json::from_str(&s).ok()?)
+}
+
+/// Starts recording and remembers its plan for `record status`.
+pub(crate) fn start(
+    dmm: &mut dyn Meter,
+    name: &str,
+    interval: u16,
+    duration: u32,
+    verbose: bool,
+) -> Result<Plan> {
+    if verbose {
+        diagln!("Sending 'GET RECORD COUNT' command to DMM.");
+    }
+    let index = dmm.get_record_count()? + 1;
+    if verbose {
+        diagln!("Sending 'RECORD START' command to DMM.");
+    }
+    dmm.start_record(name, interval, duration)?;
+    let plan = Plan {
+        index,
+        name: name.to_owned(),
+        start: now(),
+        interval,
+        duration,
+    };
+    if let Err(e) = save_plan(&plan) {
+        if verbose {
+            diagln!(
+                "Can't save record plan to '{}': {}",
+                state_path().display(),
+                e
+            );
+        }
+    }
+    diagln!(
+        "Recording #{} '{}' every {} for {}: about {} sample(s), finishes at {}.",
+        index,
+        name,
+        format_short_duration(Duration::from_secs(u64::from(interval))),
+        format_short_duration(Duration::from_secs(u64::from(duration) * 60)),
+        plan.samples(),
+        plan.end().format("%Y-%m-%d %H:%M:%S")
+    );
+    Ok(plan)
+}
+
+/// State of last record.
+pub(crate) struct Status {
+    pub(crate) index: u16,
+    pub(crate) info: RecordInfo,
+    /// Plan saved by `record start` (if it's the same record).
+    pub(crate) plan: Option<Plan>,
+    pub(crate) running: bool,
+}
+
+impl Status {
+    pub(crate) fn print(&self) {
+        println!(
+            "RECORD #{} '{}': {}",
+            self.index,
+            self.info.name,
+            if self.running {
+                "recording"
+            } else {
+                "finished"
+            }
+        );
+        println!("\tElapsed: {}", format_short_duration(self.info.duration));
+        match self.plan {
+            Some(ref plan) => {
+                println!(
+                    "\tSample count: {}/{}",
+                    self.info.sample_count,
+                    plan.samples()
+                );
+                println!("\tEnd: {}", plan.end().format("%Y-%m-%d %H:%M:%S"));
+            }
+            None => println!("\tSample count: {}", self.info.sample_count),
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> Json {
+        let mut j = record_info_json(self.index, &self.info);
+        j["running"] = Json::from(self.running);
+        if let Some(ref plan) = self.plan {
+            j["planned_sample_count"] = Json::from(plan.samples());
+            j["end"] = Json::from(format_timestamp(&plan.end()));
+        }
+        j
+    }
+}
+
+/// Gets state of last record (`None` if there are no records).
+///
+/// Record is running if its last sample is taken at most 2 intervals ago
+/// (and it hasn't reached planned sample count). Time of last sample is
+/// `start + duration`: host time of start is taken from plan, DMM clock is used without it.
+pub(crate) fn status(dmm: &mut dyn Meter, verbose: bool) -> Result<Option<Status>> {
+    if verbose {
+        diagln!("Sending 'GET RECORD COUNT' command to DMM.");
+    }
+    let index = dmm.get_record_count()?;
+    if index == 0 {
+        return Ok(None);
+    }
+    if verbose {
+        diagln!("Sending 'GET RECORD INFO #{}' command to DMM.", index);
+    }
+    let info = dmm.get_record_info(index)?;
+    let plan = load_plan().filter(|plan| plan.index == index && plan.name == info.name);
+    let start = plan.as_ref().map_or(info.start, |plan| plan.start);
+    let last = start + chrono::Duration::seconds(info.duration.as_secs() as i64);
+    let slack = chrono::Duration::seconds((info.interval * 2 + STALL_SLACK).as_secs() as i64);
+    let running = now() <= last + slack
+        && plan
+            .as_ref()
+            .is_none_or(|plan| u64::from(info.sample_count) < plan.samples());
+    Ok(Some(Status {
+        index,
+        info,
+        plan,
+        running,
+    }))
+}
+
+/// Polls last record until it's finished and returns its final state.
+///
+/// Returns `None` if Ctrl-C is pressed.
+pub(crate) fn wait(dmm: &mut dyn Meter, plan: &Plan, verbose: bool) -> Result<Option<Status>> {
+    interrupt::catch()?;
+    let poll = Duration::from_secs(u64::from(plan.interval).min(10));
+    let mut count = None;
+    loop {
+        let status = status(dmm, verbose)?.ok_or("record is not found")?;
+        if count != Some(status.info.sample_count) {
+            count = Some(status.info.sample_count);
+            diagln!(
+                "Sample count: {}/{}",
+                status.info.sample_count,
+                plan.samples()
+            );
+        }
+        if !status.running {
+            return Ok(Some(status));
+        }
+        let started = Instant::now();
+        while started.elapsed() < poll {
+            if interrupt::is_interrupted() {
+                return Ok(None);
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
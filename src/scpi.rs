@@ -0,0 +1,777 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::result;
+use std::time::{Duration, Instant};
+
+use ut181a::{Mode, Range};
+
+use color;
+use error::*;
+use measurement::{base_value, is_auto_range, is_overload, main_value, mode, range};
+use meter::Meter;
+use quantity::parse_quantity;
+use ranges::{mode_ranges, RangeInfo};
+
+/// Reply to `*IDN?` (manufacturer, model, serial number, firmware).
+const IDENTITY: &str = concat!("UNI-T,UT181A,0,ut181a-cli ", env!("CARGO_PKG_VERSION"));
+
+/// Reading returned on overload (as bench DMMs do).
+const OVERLOAD: f64 = 9.9e37;
+
+/// Reading returned when DMM failed to measure (SCPI "not a number").
+const NOT_A_NUMBER: f64 = 9.91e37;
+
+/// Maximum time to wait for reading in configured mode.
+const READ_TIMEOUT: u64 = 5; // seconds
+
+/// Capacity of error queue.
+const ERROR_QUEUE_SIZE: usize = 20;
+
+/// DMM mode with its range steps.
+struct Scale {
+    mode: Mode,
+    /// Relative mode used while null (`CALC:FUNC NULL`) is on.
+    null_mode: Option<Mode>,
+}
+
+impl Scale {
+    /// Ranges of `Step1`, `Step2`...
+    fn ranges(&self) -> &'static [RangeInfo] {
+        mode_ranges(self.mode)
+    }
+
+    /// Upper limit of range step (in base units).
+    fn upper(&self, step: usize) -> Option<f64> {
+        self.ranges().get(step).map(|r| r.base_upper())
+    }
+}
+
+/// SCPI measuring function.
+struct Function {
+    /// Header nodes: short form in upper case, optional nodes in brackets.
+    header: &'static str,
+    /// Name in replies to `CONF?` and `FUNC?`.
+    name: &'static str,
+    /// Scales in ascending order of ranges.
+    scales: &'static [Scale],
+    /// Scale set by `CONF` without range.
+    default: usize,
+}
+
+const FUNCTIONS: &[Function] = &[
+    Function {
+        header: "VOLTage:AC",
+        name: "VOLT:AC",
+        scales: &[
+            Scale {
+                mode: Mode::mVAC_Normal,
+                null_mode: Some(Mode::mVAC_Normal_Rel),
+            },
+            Scale {
+                mode: Mode::VAC_Normal,
+                null_mode: Some(Mode::VAC_Normal_Rel),
+            },
+        ],
+        default: 1,
+    },
+    Function {
+        header: "VOLTage:[DC]",
+        name: "VOLT",
+        scales: &[
+            Scale {
+                mode: Mode::mVDC_Normal,
+                null_mode: Some(Mode::mVDC_Normal_Rel),
+            },
+            Scale {
+                mode: Mode::VDC_Normal,
+                null_mode: Some(Mode::VDC_Normal_Rel),
+            },
+        ],
+        default: 1,
+    },
+    Function {
+        header: "CURRent:AC",
+        name: "CURR:AC",
+        scales: &[
+            Scale {
+                mode: Mode::uAAC_Normal,
+                null_mode: Some(Mode::uAAC_Normal_Rel),
+            },
+            Scale {
+                mode: Mode::mAAC_Normal,
+                null_mode: Some(Mode::mAAC_Normal_Rel),
+            },
+            Scale {
+                mode: Mode::AAC_Normal,
+                null_mode: Some(Mode::AAC_Normal_Rel),
+            },
+        ],
+        default: 1,
+    },
+    Function {
+        header: "CURRent:[DC]",
+        name: "CURR",
+        scales: &[
+            Scale {
+                mode: Mode::uADC_Normal,
+                null_mode: Some(Mode::uADC_Normal_Rel),
+            },
+            Scale {
+                mode: Mode::mADC_Normal,
+                null_mode: Some(Mode::mADC_Normal_Rel),
+            },
+            Scale {
+                mode: Mode::ADC_Normal,
+                null_mode: Some(Mode::ADC_Normal_Rel),
+            },
+        ],
+        default: 1,
+    },
+    Function {
+        header: "RESistance",
+        name: "RES",
+        scales: &[Scale {
+            mode: Mode::Resistance,
+            null_mode: Some(Mode::Resistance_Rel),
+        }],
+        default: 0,
+    },
+    Function {
+        header: "FREQuency",
+        name: "FREQ",
+        scales: &[Scale {
+            mode: Mode::Frequency,
+            null_mode: Some(Mode::Frequency_Rel),
+        }],
+        default: 0,
+    },
+    Function {
+        header: "CAPacitance",
+        name: "CAP",
+        scales: &[Scale {
+            mode: Mode::Capacitance,
+            null_mode: Some(Mode::Capacitance_Rel),
+        }],
+        default: 0,
+    },
+    Function {
+        header: "TEMPerature",
+        name: "TEMP",
+        scales: &[Scale {
+            mode: Mode::TempC_T1_T2,
+            null_mode: Some(Mode::TempC_T1_T2_Rel),
+        }],
+        default: 0,
+    },
+    Function {
+        header: "DIODe",
+        name: "DIOD",
+        scales: &[Scale {
+            mode: Mode::Diode_Normal,
+            null_mode: None,
+        }],
+        default: 0,
+    },
+    Function {
+        header: "CONTinuity",
+        name: "CONT",
+        scales: &[Scale {
+            mode: Mode::Beeper_Short,
+            null_mode: None,
+        }],
+        default: 0,
+    },
+];
+
+const STEPS: &[Range] = &[
+    Range::Step1,
+    Range::Step2,
+    Range::Step3,
+    Range::Step4,
+    Range::Step5,
+    Range::Step6,
+    Range::Step7,
+    Range::Step8,
+];
+
+/// SCPI error queue entry.
+struct ScpiError {
+    code: i32,
+    message: String,
+}
+
+impl ScpiError {
+    fn new(code: i32, message: &str) -> ScpiError {
+        ScpiError {
+            code,
+            message: message.to_owned(),
+        }
+    }
+
+    fn undefined_header() -> ScpiError {
+        ScpiError::new(-113, "Undefined header")
+    }
+
+    fn illegal_parameter() -> ScpiError {
+        ScpiError::new(-224, "Illegal parameter value")
+    }
+}
+
+impl From<Error> for ScpiError {
+    fn from(e: Error) -> ScpiError {
+        ScpiError {
+            code: -240,
+            message: format!("Hardware error;{}", e),
+        }
+    }
+}
+
+/// Reply to query (`None` for commands).
+type Reply = result::Result<Option<String>, ScpiError>;
+
+/// Keyword matches its short (upper case part) or long form, case-insensitive.
+fn keyword_matches(word: &str, keyword: &str) -> bool {
+    let short: String = keyword
+        .chars()
+        .take_while(|c| !c.is_ascii_lowercase())
+        .collect();
+    word.eq_ignore_ascii_case(&short) || word.eq_ignore_ascii_case(keyword)
+}
+
+/// Matches beginning of `nodes` with `pattern`, returns count of matched nodes.
+fn match_nodes(nodes: &[&str], pattern: &[&str]) -> Option<usize> {
+    let (first, rest) = match pattern.split_first() {
+        Some(split) => split,
+        None => return Some(0),
+    };
+    let optional = first.strip_prefix('[').and_then(|k| k.strip_suffix(']'));
+    let keyword = optional.unwrap_or(first);
+    if let Some(node) = nodes.first() {
+        if keyword_matches(node, keyword) {
+            if let Some(n) = match_nodes(&nodes[1..], rest) {
+                return Some(n + 1);
+            }
+        }
+    }
+    if optional.is_some() {
+        match_nodes(nodes, rest)
+    } else {
+        None
+    }
+}
+
+/// Returns the whole `nodes` match `pattern` (`"CALCulate:[STATe]"`).
+fn is_header(nodes: &[&str], pattern: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split(':').collect();
+    match_nodes(nodes, &pattern) == Some(nodes.len())
+}
+
+/// Finds function at beginning of `nodes`, returns it and count of its nodes.
+fn find_function(nodes: &[&str]) -> Option<(&'static Function, usize)> {
+    FUNCTIONS
+        .iter()
+        .filter_map(|f| {
+            let pattern: Vec<&str> = f.header.split(':').collect();
+            match_nodes(nodes, &pattern).map(|n| (f, n))
+        })
+        .filter(|&(_, n)| n > 0)
+        .max_by_key(|&(_, n)| n)
+}
+
+/// Finds function by its name in `FUNC "VOLT:DC"`.
+fn function_by_name(name: &str) -> Option<&'static Function> {
+    let name = name.trim_matches(|c| c == '"' || c == '\'');
+    let nodes: Vec<&str> = name.split(':').collect();
+    match find_function(&nodes) {
+        Some((f, n)) if n == nodes.len() => Some(f),
+        _ => None,
+    }
+}
+
+/// Finds function and scale of DMM mode, `true` if it's null (relative) mode.
+fn scale_of(m: Mode) -> Option<(&'static Function, usize, bool)> {
+    let name = m.to_string();
+    for f in FUNCTIONS {
+        for (i, scale) in f.scales.iter().enumerate() {
+            if scale.mode.to_string() == name {
+                return Some((f, i, false));
+            }
+            if scale.null_mode.is_some_and(|n| n.to_string() == name) {
+                return Some((f, i, true));
+            }
+        }
+    }
+    None
+}
+
+/// Formats number in SCPI NR3 form (`+4.99930000E+00`).
+fn format_number(v: f64) -> String {
+    let s = format!("{:.8E}", v.abs());
+    let (mantissa, exponent) = s.split_at(s.find('E').unwrap_or(s.len()));
+    let exponent = exponent.trim_start_matches('E').parse::<i32>().unwrap_or(0);
+    format!(
+        "{}{}E{}{:02}",
+        if v < 0.0 { '-' } else { '+' },
+        mantissa,
+        if exponent < 0 { '-' } else { '+' },
+        exponent.abs()
+    )
+}
+
+fn parse_number(s: &str) -> result::Result<f64, ScpiError> {
+    s.trim()
+        .parse::<f64>()
+        .or_else(|_| parse_quantity(s).map(|q| q.value))
+        .map_err(|_| ScpiError::new(-104, "Data type error"))
+}
+
+fn parse_bool(s: &str) -> result::Result<bool, ScpiError> {
+    match s.trim().to_ascii_uppercase().as_str() {
+        "ON" | "1" => Ok(true),
+        "OFF" | "0" => Ok(false),
+        _ => Err(ScpiError::illegal_parameter()),
+    }
+}
+
+fn bool_reply(b: bool) -> Reply {
+    Ok(Some(if b { "1" } else { "0" }.to_owned()))
+}
+
+/// Range parameter of `CONF`, `MEAS` and `RANGE`.
+enum RangeParam {
+    Auto,
+    Min,
+    Max,
+    Value(f64),
+}
+
+fn parse_range(s: Option<&str>) -> result::Result<RangeParam, ScpiError> {
+    // Resolution parameter is ignored: it's fixed by range.
+    let s = s.and_then(|s| s.split(',').next()).map(str::trim);
+    match s.map(str::to_ascii_uppercase).as_deref() {
+        None | Some("") | Some("AUTO") | Some("DEF") | Some("DEFAULT") => Ok(RangeParam::Auto),
+        Some("MIN") | Some("MINIMUM") => Ok(RangeParam::Min),
+        Some("MAX") | Some("MAXIMUM") => Ok(RangeParam::Max),
+        Some(_) => parse_number(s.unwrap_or_default()).map(RangeParam::Value),
+    }
+}
+
+/// Connection state of one SCPI client.
+struct Session<'a> {
+    dmm: &'a mut dyn Meter,
+    verbose: bool,
+    errors: VecDeque<ScpiError>,
+    /// Current DMM mode (unknown until configured or read).
+    mode: Option<Mode>,
+    range: Range,
+    auto_range: bool,
+    /// Reading taken by `INIT` for `FETC?`.
+    fetched: Option<f64>,
+}
+
+impl<'a> Session<'a> {
+    fn new(dmm: &'a mut dyn Meter, verbose: bool) -> Session<'a> {
+        Session {
+            dmm,
+            verbose,
+            errors: VecDeque::new(),
+            mode: None,
+            range: Range::Auto,
+            auto_range: true,
+            fetched: None,
+        }
+    }
+
+    fn push_error(&mut self, e: ScpiError) {
+        if self.verbose {
+            diagln!("SCPI error {}: {}", e.code, e.message);
+        }
+        if self.errors.len() >= ERROR_QUEUE_SIZE - 1 {
+            self.errors.truncate(ERROR_QUEUE_SIZE - 1);
+            self.errors
+                .push_back(ScpiError::new(-350, "Queue overflow"));
+        } else {
+            self.errors.push_back(e);
+        }
+    }
+
+    /// Executes program message (commands separated by `;`), returns replies to queries.
+    fn execute_line(&mut self, line: &str) -> Option<String> {
+        let mut replies = Vec::new();
+        for cmd in line.split(';').map(str::trim).filter(|c| !c.is_empty()) {
+            if self.verbose {
+                diagln!("SCPI: {}", cmd);
+            }
+            match self.execute(cmd) {
+                Ok(Some(reply)) => replies.push(reply),
+                Ok(None) => {}
+                Err(e) => self.push_error(e),
+            }
+        }
+        if replies.is_empty() {
+            None
+        } else {
+            Some(replies.join(";"))
+        }
+    }
+
+    fn execute(&mut self, cmd: &str) -> Reply {
+        let (header, params) = match cmd.find(char::is_whitespace) {
+            Some(i) => (&cmd[..i], Some(cmd[i..].trim())),
+            None => (cmd, None),
+        };
+        let query = header.ends_with('?');
+        let header = header.trim_start_matches(':').trim_end_matches('?');
+        if header.starts_with('*') {
+            return self.execute_common(&header.to_ascii_uppercase(), query);
+        }
+        let nodes: Vec<&str> = header.split(':').collect();
+
+        if is_header(&nodes, "SYSTem:ERRor:[NEXT]") && query {
+            let e = self
+                .errors
+                .pop_front()
+                .unwrap_or_else(|| ScpiError::new(0, "No error"));
+            return Ok(Some(format!("{:+},\"{}\"", e.code, e.message)));
+        }
+        if is_header(&nodes, "SYSTem:VERSion") && query {
+            return Ok(Some("1999.0".to_owned()));
+        }
+        if is_header(&nodes, "READ") && query {
+            return Ok(Some(self.read_reply()));
+        }
+        if is_header(&nodes, "INITiate:[IMMediate]") && !query {
+            self.fetched = Some(self.read_value());
+            return Ok(None);
+        }
+        if is_header(&nodes, "FETCh") && query {
+            let v = self
+                .fetched
+                .ok_or_else(|| ScpiError::new(-230, "Data corrupt or stale"))?;
+            return Ok(Some(format_number(v)));
+        }
+        if is_header(&nodes, "CONFigure") && query {
+            return self.configuration();
+        }
+        if keyword_matches(nodes[0], "CONFigure") || keyword_matches(nodes[0], "MEASure") {
+            let f = match find_function(&nodes[1..]) {
+                Some((f, n)) if n + 1 == nodes.len() => f,
+                _ => return Err(ScpiError::undefined_header()),
+            };
+            self.configure(f, parse_range(params)?)?;
+            if keyword_matches(nodes[0], "MEASure") && query {
+                return Ok(Some(self.read_reply()));
+            }
+            return Ok(None);
+        }
+        if is_header(&nodes, "[SENSe]:FUNCtion:[ON]") {
+            if query {
+                let f = self.function()?.0;
+                return Ok(Some(format!("\"{}\"", f.name)));
+            }
+            let name = params.ok_or_else(|| ScpiError::new(-109, "Missing parameter"))?;
+            let f = function_by_name(name).ok_or_else(ScpiError::illegal_parameter)?;
+            return self.configure(f, RangeParam::Auto);
+        }
+        if keyword_matches(nodes[0], "CALCulate") {
+            return self.execute_calculate(&nodes, params, query);
+        }
+        self.execute_range(&nodes, params, query)
+    }
+
+    fn execute_common(&mut self, header: &str, query: bool) -> Reply {
+        match (header, query) {
+            ("*IDN", true) => Ok(Some(IDENTITY.to_owned())),
+            ("*RST", false) => {
+                self.errors.clear();
+                let f = function_by_name("VOLT:DC").ok_or_else(ScpiError::undefined_header)?;
+                self.configure(f, RangeParam::Auto)
+            }
+            ("*CLS", false) => {
+                self.errors.clear();
+                Ok(None)
+            }
+            ("*OPC", true) => Ok(Some("1".to_owned())),
+            ("*OPC", false) | ("*WAI", false) => Ok(None),
+            ("*TST", true) => Ok(Some("0".to_owned())),
+            _ => Err(ScpiError::undefined_header()),
+        }
+    }
+
+    /// `[SENSe:]<function>:RANGe[:UPPer]` and `[SENSe:]<function>:RANGe:AUTO`,
+    /// function can be omitted.
+    fn execute_range(&mut self, nodes: &[&str], params: Option<&str>, query: bool) -> Reply {
+        let sense = match_nodes(nodes, &["[SENSe]"]).unwrap_or(0);
+        let nodes = &nodes[sense..];
+        let (f, n) = match find_function(nodes) {
+            Some((f, n)) => (f, n),
+            None => (self.function()?.0, 0),
+        };
+        let nodes = &nodes[n..];
+        if is_header(nodes, "RANGe:AUTO") {
+            if query {
+                self.function_of(f)?;
+                return bool_reply(self.auto_range);
+            }
+            let on = parse_bool(params.unwrap_or(""))?;
+            let range = if on {
+                RangeParam::Auto
+            } else {
+                let (_, scale) = self.function_of(f)?;
+                let step = STEPS.iter().position(|&r| r == self.range).unwrap_or(0);
+                RangeParam::Value(f.scales[scale].upper(step).unwrap_or(0.0))
+            };
+            return self.set_range(f, range);
+        }
+        if is_header(nodes, "RANGe:[UPPer]") {
+            if query {
+                let (_, scale) = self.function_of(f)?;
+                let step = STEPS.iter().position(|&r| r == self.range);
+                return match step.and_then(|i| f.scales[scale].upper(i)) {
+                    Some(v) => Ok(Some(format_number(v))),
+                    None => Ok(Some(format_number(NOT_A_NUMBER))),
+                };
+            }
+            return self.set_range(f, parse_range(params)?);
+        }
+        Err(ScpiError::undefined_header())
+    }
+
+    /// `CALCulate:FUNCtion NULL` and `CALCulate:STATe`.
+    fn execute_calculate(&mut self, nodes: &[&str], params: Option<&str>, query: bool) -> Reply {
+        if is_header(nodes, "CALCulate:FUNCtion") {
+            if query {
+                return Ok(Some("NULL".to_owned()));
+            }
+            return match params.map(str::to_ascii_uppercase).as_deref() {
+                Some("NULL") => self.set_null(true),
+                _ => Err(ScpiError::illegal_parameter()),
+            };
+        }
+        if is_header(nodes, "CALCulate:STATe") {
+            if query {
+                let null = self.function()?.2;
+                return bool_reply(null);
+            }
+            return self.set_null(parse_bool(params.unwrap_or(""))?);
+        }
+        Err(ScpiError::undefined_header())
+    }
+
+    /// Current function, scale and null state (reads measurement if mode is unknown).
+    fn function(&mut self) -> result::Result<(&'static Function, usize, bool), ScpiError> {
+        let m = match self.mode {
+            Some(m) => m,
+            None => {
+                self.read()?;
+                self.mode.ok_or_else(ScpiError::undefined_header)?
+            }
+        };
+        scale_of(m).ok_or_else(|| ScpiError::new(-221, "Settings conflict;unsupported DMM mode"))
+    }
+
+    /// Scale of current mode if it belongs to function `f`.
+    fn function_of(&mut self, f: &Function) -> result::Result<(bool, usize), ScpiError> {
+        let (current, scale, null) = self.function()?;
+        if current.name != f.name {
+            return Err(ScpiError::new(
+                -221,
+                "Settings conflict;function isn't selected",
+            ));
+        }
+        Ok((null, scale))
+    }
+
+    fn set_mode(&mut self, m: Mode) -> result::Result<(), ScpiError> {
+        if self.verbose {
+            diagln!("Sending 'SET MODE {}' command to DMM.", m);
+        }
+        self.dmm.set_mode(m)?;
+        self.mode = Some(m);
+        Ok(())
+    }
+
+    fn send_range(&mut self, r: Range) -> result::Result<(), ScpiError> {
+        if self.verbose {
+            diagln!("Sending 'SET RANGE {:?}' command to DMM.", r);
+        }
+        self.dmm.set_range(r)?;
+        self.auto_range = r == Range::Auto;
+        self.range = r;
+        Ok(())
+    }
+
+    /// Finds scale and range step for range parameter.
+    fn select(f: &Function, range: &RangeParam) -> result::Result<(usize, Range), ScpiError> {
+        let last = f.scales.len() - 1;
+        match *range {
+            RangeParam::Auto => Ok((f.default, Range::Auto)),
+            RangeParam::Min if f.scales[0].ranges().is_empty() => Ok((0, Range::Auto)),
+            RangeParam::Min => Ok((0, Range::Step1)),
+            RangeParam::Max => match f.scales[last].ranges().len() {
+                0 => Ok((last, Range::Auto)),
+                n => Ok((last, STEPS[n - 1])),
+            },
+            RangeParam::Value(v) => {
+                for (i, scale) in f.scales.iter().enumerate() {
+                    if let Some(step) = scale
+                        .ranges()
+                        .iter()
+                        .position(|r| v.abs() <= r.base_upper())
+                    {
+                        return Ok((i, STEPS[step]));
+                    }
+                }
+                Err(ScpiError::new(-222, "Data out of range"))
+            }
+        }
+    }
+
+    /// Sets mode and range of function (null is turned off).
+    fn configure(&mut self, f: &Function, range: RangeParam) -> Reply {
+        let (scale, r) = Session::select(f, &range)?;
+        self.set_mode(f.scales[scale].mode)?;
+        self.send_range(r)?;
+        Ok(None)
+    }
+
+    /// Sets range of selected function (switching between scales keeps null state).
+    fn set_range(&mut self, f: &Function, range: RangeParam) -> Reply {
+        let (null, current) = self.function_of(f)?;
+        let (scale, r) = match range {
+            // Auto range works within current scale.
+            RangeParam::Auto => (current, Range::Auto),
+            _ => Session::select(f, &range)?,
+        };
+        if scale != current {
+            let s = &f.scales[scale];
+            let m = if null {
+                s.null_mode.unwrap_or(s.mode)
+            } else {
+                s.mode
+            };
+            self.set_mode(m)?;
+        }
+        self.send_range(r)?;
+        Ok(None)
+    }
+
+    fn set_null(&mut self, on: bool) -> Reply {
+        let (f, scale, null) = self.function()?;
+        if null == on {
+            return Ok(None);
+        }
+        let s = &f.scales[scale];
+        let m = if on {
+            s.null_mode
+                .ok_or_else(|| ScpiError::new(-221, "Settings conflict;no null in this function"))?
+        } else {
+            s.mode
+        };
+        self.set_mode(m).map(|_| None)
+    }
+
+    /// Current configuration (`"VOLT +6.00000000E+00"`).
+    fn configuration(&mut self) -> Reply {
+        let (f, scale, _) = self.function()?;
+        let step = STEPS.iter().position(|&r| r == self.range);
+        let range = match step.and_then(|i| f.scales[scale].upper(i)) {
+            Some(v) if !self.auto_range => format_number(v),
+            _ => "DEF".to_owned(),
+        };
+        Ok(Some(format!("\"{} {}\"", f.name, range)))
+    }
+
+    /// Reading for query reply, error is queued and replaced with NaN.
+    fn read_reply(&mut self) -> String {
+        format_number(self.read_value())
+    }
+
+    fn read_value(&mut self) -> f64 {
+        match self.read() {
+            Ok(v) => v,
+            Err(e) => {
+                self.push_error(e);
+                NOT_A_NUMBER
+            }
+        }
+    }
+
+    /// Takes one reading in current mode (in base units).
+    fn read(&mut self) -> result::Result<f64, ScpiError> {
+        if self.verbose {
+            diagln!("Sending 'MONITOR ON' command to DMM.");
+        }
+        self.dmm.monitor_on()?;
+        let result = self.read_measurement();
+        if self.verbose {
+            diagln!("Sending 'MONITOR OFF' command to DMM.");
+        }
+        self.dmm.monitor_off()?;
+        result
+    }
+
+    fn read_measurement(&mut self) -> result::Result<f64, ScpiError> {
+        let mode_name = self.mode.map(|m| m.to_string());
+        let started = Instant::now();
+        loop {
+            let m = self.dmm.get_measurement()?;
+            // Skip readings sent before mode is switched.
+            if mode_name
+                .as_ref()
+                .is_none_or(|name| *name == mode(&m).to_string())
+            {
+                self.mode = Some(mode(&m));
+                self.range = range(&m);
+                self.auto_range = is_auto_range(&m);
+                let v = main_value(&m);
+                return Ok(match (is_overload(v), v.overload_neg) {
+                    (true, true) => -OVERLOAD,
+                    (true, false) => OVERLOAD,
+                    (false, _) => base_value(v),
+                });
+            }
+            if started.elapsed() > Duration::from_secs(READ_TIMEOUT) {
+                return Err(ScpiError::new(
+                    -230,
+                    "Data corrupt or stale;no reading in selected mode",
+                ));
+            }
+        }
+    }
+}
+
+fn serve_client(dmm: &mut dyn Meter, stream: TcpStream, verbose: bool) -> Result<()> {
+    let mut out = stream.try_clone()?;
+    let mut session = Session::new(dmm, verbose);
+    for line in BufReader::new(stream).lines() {
+        if let Some(reply) = session.execute_line(&line?) {
+            writeln!(out, "{}", reply)?;
+        }
+    }
+    Ok(())
+}
+
+/// Accepts SCPI clients on `address` one by one and executes their commands with DMM.
+pub(crate) fn serve(dmm: &mut dyn Meter, address: &str, verbose: bool) -> Result<()> {
+    let listener = TcpListener::bind(address)?;
+    println!("Listening on {}.", address);
+    for stream in listener.incoming() {
+        // Failed connection doesn't stop the server.
+        let (stream, peer) = match stream.and_then(|s| s.peer_addr().map(|peer| (s, peer))) {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                color::eprint_error(format!("Can't accept client: {}", e));
+                continue;
+            }
+        };
+        println!("Client {} is connected.", peer);
+        // Connection errors only close the connection.
+        if let Err(e) = serve_client(dmm, stream, verbose) {
+            color::eprint_error(format!("Client {}: {}", peer, e));
+        }
+        println!("Client {} is disconnected.", peer);
+    }
+    Ok(())
+}
@@ -0,0 +1,369 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use chrono::Local;
+use serde_json::{self, Map, Value as Json};
+use serde_yaml;
+use toml;
+
+use ut181a::{Mode, Range};
+
+use check::{read_checked, CheckOptions, Limits, Verdict};
+use duration::parse_duration;
+use error::*;
+use json::{range_from_name, range_name};
+use meter::Meter;
+use modes::parse_mode;
+use quantity::parse_quantity;
+
+/// Default time to wait for stable reading in `read` step.
+const READ_TIMEOUT: u64 = 10; // seconds
+
+/// Step of test sequence.
+enum Action {
+    SetMode(Mode),
+    SetRange(Range),
+    SetReference(f32),
+    SetMinMax(bool),
+    ToggleHold,
+    Save,
+    Wait(Duration),
+    Prompt(String),
+    Read(CheckOptions),
+}
+
+struct Step {
+    /// Step label (defaults to action description).
+    name: String,
+    action: Action,
+}
+
+/// Test sequence loaded from TOML or YAML file.
+///
+/// The file contains `steps` array, every step is a table with one action key
+/// (`mode`, `range`, `reference`, `min_max`, `hold`, `save`, `wait`, `prompt`, `read`)
+/// and optional `name`.
+pub(crate) struct Script {
+    steps: Vec<Step>,
+}
+
+fn invalid(index: usize, msg: &str) -> Error {
+    ErrorKind::InvalidScript(format!("step {}: {}", index, msg)).into()
+}
+
+/// Quantity can be written as number or as string with unit (`3.3V`).
+fn quantity_field(j: &Json, index: usize, name: &str) -> Result<Option<f64>> {
+    match *j {
+        Json::Null => Ok(None),
+        Json::Number(ref n) => Ok(n.as_f64()),
+        Json::String(ref s) => Ok(Some(parse_quantity(s)?.value)),
+        _ => Err(invalid(
+            index,
+            &format!("'{}' should be number or string", name),
+        )),
+    }
+}
+
+fn unit_field(j: &Json) -> String {
+    match j.as_str() {
+        Some(s) => parse_quantity(s).map(|q| q.unit).unwrap_or_default(),
+        None => String::new(),
+    }
+}
+
+fn parse_read(j: &Json, index: usize) -> Result<CheckOptions> {
+    let empty = Json::Object(Map::new());
+    let j = match *j {
+        Json::Bool(true) => &empty,
+        Json::Object(_) => j,
+        _ => return Err(invalid(index, "'read' should be true or table")),
+    };
+    let mut limits = Limits::unbounded();
+    let mut unit = String::new();
+    if let Some(expected) = quantity_field(&j["expect"], index, "expect")? {
+        let tolerance = match j["tolerance"] {
+            Json::Null => "0".to_owned(),
+            Json::String(ref s) => s.clone(),
+            ref t => t.to_string(),
+        };
+        limits = Limits::from_tolerance(expected, &tolerance)?;
+        unit = unit_field(&j["expect"]);
+    } else {
+        if let Some(min) = quantity_field(&j["min"], index, "min")? {
+            limits.min = min;
+            unit = unit_field(&j["min"]);
+        }
+        if let Some(max) = quantity_field(&j["max"], index, "max")? {
+            limits.max = max;
+            if unit.is_empty() {
+                unit = unit_field(&j["max"]);
+            }
+        }
+    }
+    Ok(CheckOptions {
+        mode: None,
+        limits,
+        unit,
+        samples: match j["samples"] {
+            Json::Null => 1,
+            ref n => match n.as_u64() {
+                Some(n) if n > 0 => n as usize,
+                _ => return Err(invalid(index, "'samples' should be positive integer")),
+            },
+        },
+        timeout: match j["timeout"].as_str() {
+            Some(d) => parse_duration(d, Duration::from_secs(1))?,
+            None => Duration::from_secs(READ_TIMEOUT),
+        },
+    })
+}
+
+fn parse_step(j: &Json, index: usize) -> Result<Step> {
+    let obj = j
+        .as_object()
+        .ok_or_else(|| invalid(index, "step should be table"))?;
+    let actions: Vec<&String> = obj.keys().filter(|k| *k != "name").collect();
+    if actions.len() != 1 {
+        return Err(invalid(index, "step should have exactly one action"));
+    }
+    let key = actions[0].as_str();
+    let v = &obj[key];
+    let as_str = |what: &str| {
+        v.as_str()
+            .ok_or_else(|| invalid(index, &format!("'{}' should be {}", key, what)))
+    };
+    let (action, description) = match key {
+        "mode" => {
+            let name = as_str("mode name")?;
+            let mode = parse_mode(name)?;
+            (Action::SetMode(mode), format!("mode {}", name))
+        }
+        "range" => {
+            let name = as_str("range name")?;
+            (
+                Action::SetRange(range_from_name(name)?),
+                format!("range {}", name),
+            )
+        }
+        "reference" => {
+            let val = v
+                .as_f64()
+                .ok_or_else(|| invalid(index, "'reference' should be number"))?;
+            (
+                Action::SetReference(val as f32),
+                format!("reference {}", val),
+            )
+        }
+        "min_max" => {
+            let on = v
+                .as_bool()
+                .ok_or_else(|| invalid(index, "'min_max' should be true or false"))?;
+            let description = format!("min-max {}", if on { "on" } else { "off" });
+            (Action::SetMinMax(on), description)
+        }
+        "hold" | "save" => {
+            if *v != Json::Bool(true) {
+                return Err(invalid(index, &format!("'{}' should be true", key)));
+            }
+            let action = if key == "hold" {
+                Action::ToggleHold
+            } else {
+                Action::Save
+            };
+            (action, key.to_owned())
+        }
+        "wait" => {
+            let d = as_str("duration")?;
+            let duration = parse_duration(d, Duration::from_secs(1))?;
+            (Action::Wait(duration), format!("wait {}", d))
+        }
+        "prompt" => {
+            let msg = as_str("message")?;
+            (Action::Prompt(msg.to_owned()), "prompt".to_owned())
+        }
+        "read" => (Action::Read(parse_read(v, index)?), "read".to_owned()),
+        _ => return Err(invalid(index, &format!("unknown action '{}'", key))),
+    };
+    Ok(Step {
+        name: obj
+            .get("name")
+            .and_then(|n| n.as_str())
+            .map_or(description, |n| n.to_owned()),
+        action,
+    })
+}
+
+impl Script {
+    /// Loads script, format is detected by file extension (`.toml`, `.yaml`, `.yml`).
+    pub(crate) fn load(path: &Path) -> Result<Script> {
+        let text = fs::read_to_string(path)?;
+        let j: Json = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&text)?,
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&text)?,
+            _ => {
+                return Err(ErrorKind::InvalidScript(
+                    "unknown file type (use .toml, .yaml or .yml)".to_owned(),
+                )
+                .into())
+            }
+        };
+        Script::from_json(&j)
+    }
+
+    /// Builds script from parsed document with `steps` array.
+    pub(crate) fn from_json(j: &Json) -> Result<Script> {
+        let steps = match j["steps"] {
+            Json::Array(ref steps) => steps
+                .iter()
+                .enumerate()
+                .map(|(i, s)| parse_step(s, i + 1))
+                .collect::<Result<Vec<Step>>>()?,
+            _ => {
+                return Err(ErrorKind::InvalidScript("'steps' array is missing".to_owned()).into())
+            }
+        };
+        Ok(Script { steps })
+    }
+}
+
+/// Result of script execution.
+pub(crate) struct Report {
+    pub(crate) passed: usize,
+    pub(crate) failed: usize,
+    pub(crate) json: Json,
+    /// Error stopped the sequence.
+    pub(crate) error: Option<Error>,
+}
+
+fn prompt(msg: &str) -> Result<()> {
+    print!("{} [press Enter] ", msg);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Err("no operator input (stdin is closed)".into());
+    }
+    Ok(())
+}
+
+fn execute(dmm: &mut dyn Meter, action: &Action, verbose: bool) -> Result<Option<Verdict>> {
+    match *action {
+        Action::SetMode(mode) => {
+            if verbose {
+                diagln!("Sending 'SET MODE {}' command to DMM.", mode);
+            }
+            dmm.set_mode(mode)?;
+        }
+        Action::SetRange(range) => {
+            if verbose {
+                diagln!("Sending 'SET RANGE {}' command to DMM.", range_name(range));
+            }
+            dmm.set_range(range)?;
+        }
+        Action::SetReference(val) => {
+            if verbose {
+                diagln!("Sending 'SET REFERENCE VALUE {}' command to DMM.", val);
+            }
+            dmm.set_reference_value(val)?;
+        }
+        Action::SetMinMax(on) => {
+            if verbose {
+                diagln!(
+                    "Sending 'MIN/MAX {}' command to DMM.",
+                    if on { "ON" } else { "OFF" }
+                );
+            }
+            dmm.set_min_max_mode(on)?;
+        }
+        Action::ToggleHold => {
+            if verbose {
+                diagln!("Sending 'TOGGLE HOLD' command to DMM.");
+            }
+            dmm.toggle_hold()?;
+        }
+        Action::Save => {
+            if verbose {
+                diagln!("Sending 'SAVE MEASUREMENT' command to DMM.");
+            }
+            dmm.save_measurement()?;
+        }
+        Action::Wait(duration) => thread::sleep(duration),
+        Action::Prompt(ref msg) => prompt(msg)?,
+        Action::Read(ref opts) => return read_checked(dmm, opts, verbose).map(Some),
+    }
+    Ok(None)
+}
+
+/// Executes script steps in order and reports result of every step.
+///
+/// Failed read doesn't stop the sequence, but error does.
+/// Progress is printed unless `quiet` is set.
+pub(crate) fn run(dmm: &mut dyn Meter, script: &Script, quiet: bool, verbose: bool) -> Report {
+    let started = Local::now();
+    let mut report = Report {
+        passed: 0,
+        failed: 0,
+        json: Json::Null,
+        error: None,
+    };
+    let mut steps = Vec::new();
+    let total = script.steps.len();
+    for (i, step) in script.steps.iter().enumerate() {
+        let mut entry = Map::new();
+        entry.insert("step".into(), Json::from(i + 1));
+        entry.insert("name".into(), Json::from(step.name.as_str()));
+        let (status, detail) = match execute(dmm, &step.action, verbose) {
+            Ok(None) => ("ok", String::new()),
+            Ok(Some(verdict)) => {
+                entry.insert("value".into(), json!(verdict.value));
+                entry.insert("unit".into(), Json::from(verdict.unit.as_str()));
+                if verdict.pass {
+                    report.passed += 1;
+                    ("pass", verdict.to_string())
+                } else {
+                    report.failed += 1;
+                    ("fail", verdict.to_string())
+                }
+            }
+            Err(e) => {
+                let detail = e.to_string();
+                report.error = Some(e);
+                ("error", detail)
+            }
+        };
+        if !quiet {
+            if detail.is_empty() {
+                println!("[{}/{}] {}: {}", i + 1, total, step.name, status);
+            } else {
+                println!("[{}/{}] {}: {}", i + 1, total, step.name, detail);
+            }
+        }
+        entry.insert("status".into(), Json::from(status));
+        entry.insert("detail".into(), Json::from(detail));
+        steps.push(Json::Object(entry));
+        if report.error.is_some() {
+            break;
+        }
+    }
+    if !quiet {
+        println!("Passed: {}, failed: {}.", report.passed, report.failed);
+    }
+    report.json = json!({
+        "started": started.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        "steps": steps,
+        "passed": report.passed,
+        "failed": report.failed,
+        "completed": report.error.is_none(),
+    });
+    report
+}
+
+/// Writes JSON report to file.
+pub(crate) fn write_report(path: &Path, report: &Report) -> Result<()> {
+    let mut file = File::create(path)?;
+    serde_json::to_writer_pretty(&mut file, &report.json)?;
+    writeln!(file)?;
+    Ok(())
+}
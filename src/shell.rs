@@ -0,0 +1,223 @@
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use clap::{AppSettings, ArgMatches};
+use error_chain::ChainedError;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use cli::{clap_app, completions};
+use color;
+use error::*;
+use interrupt;
+
+const PROMPT: &str = "ut181a> ";
+const HISTORY_FILE: &str = ".ut181a-cli_history";
+
+/// Global options selecting DMM, inherited only all together.
+const DEVICE_OPTIONS: &[&str] = &["device", "simulate", "replay"];
+const VALUE_OPTIONS: &[&str] = &[
+    "device", "config", "capture", "replay", "db", "socket", "format", "color",
+];
+const FLAGS: &[&str] = &["verbose", "simulate", "no-daemon", "uncertainty"];
+
+/// Completes command names, mode and range names.
+struct ShellHelper;
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let start = line.rfind(' ').map_or(0, |i| i + 1);
+        let words: Vec<&str> = line[..start]
+            .split_whitespace()
+            .filter(|w| !w.starts_with('-'))
+            .collect();
+        let prefix = &line[start..];
+        let candidates = completions(&words)
+            .into_iter()
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/// Splits line into words, single and double quotes group words with spaces.
+fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_word = true;
+            }
+            None if c.is_whitespace() => {
+                if in_word {
+                    words.push(word.clone());
+                    word.clear();
+                    in_word = false;
+                }
+            }
+            None => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err("unterminated quote".into());
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Global options of `ut181a-cli shell` (config defaults included) which
+/// aren't given in typed command.
+fn inherited_args(outer: &ArgMatches, line: &ArgMatches) -> Vec<OsString> {
+    let selects_device = DEVICE_OPTIONS.iter().any(|name| line.is_present(name));
+    let inherited = |name: &str| {
+        outer.occurrences_of(name) > 0
+            && line.occurrences_of(name) == 0
+            && !(selects_device && DEVICE_OPTIONS.contains(&name))
+    };
+    let mut args = Vec::new();
+    for name in VALUE_OPTIONS.iter().filter(|name| inherited(name)) {
+        if let Some(value) = outer.value_of_os(name) {
+            let mut arg = OsString::from(format!("--{}=", name));
+            arg.push(value);
+            args.push(arg);
+        }
+    }
+    for name in FLAGS.iter().filter(|name| inherited(name)) {
+        args.push(OsString::from(format!("--{}", name)));
+    }
+    args
+}
+
+fn parse_line<I, T>(args: I) -> ::std::result::Result<ArgMatches<'static>, ::clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    clap_app()
+        .setting(AppSettings::NoBinaryName)
+        .get_matches_from_safe(args)
+}
+
+/// Reads commands with line editing and history and passes them to `execute`.
+///
+/// Typed commands inherit global options of `outer` command line unless they
+/// override them. All commands are executed against DMM opened when the shell
+/// is started, `--device`, `--simulate`, ... are used only to reopen it.
+pub(crate) fn shell<F>(outer: &ArgMatches, mut execute: F) -> Result<()>
+where
+    F: FnMut(&ArgMatches) -> Result<i32>,
+{
+    // Ctrl-C stops current command (e.g. `read cont`) instead of the shell.
+    interrupt::catch()?;
+
+    let mut editor: Editor<ShellHelper, DefaultHistory> =
+        Editor::new().map_err(|e| Error::from(e.to_string()))?;
+    editor.set_helper(Some(ShellHelper));
+    let history = history_path();
+    if let Some(ref path) = history {
+        // History file doesn't exist on first run.
+        let _ = editor.load_history(path);
+    }
+
+    println!("Type 'help' to list commands, 'exit' or Ctrl-D to quit.");
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.to_string().into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        if line == "exit" || line == "quit" {
+            break;
+        }
+
+        let words = match split_words(line) {
+            Ok(words) => words,
+            Err(e) => {
+                color::eprint_error(format!("Error: {}", e));
+                continue;
+            }
+        };
+        let matches = parse_line(&words).and_then(|matches| {
+            let inherited = inherited_args(outer, &matches);
+            if inherited.is_empty() {
+                Ok(matches)
+            } else {
+                parse_line(
+                    inherited
+                        .into_iter()
+                        .chain(words.into_iter().map(OsString::from)),
+                )
+            }
+        });
+        let matches = match matches {
+            Ok(matches) => matches,
+            Err(e) => {
+                // Help and version are reported as errors too.
+                println!("{}", e.message);
+                continue;
+            }
+        };
+        if matches.subcommand_name() == Some("shell") {
+            color::eprint_error("Error: already in shell");
+            continue;
+        }
+        interrupt::catch()?;
+        match execute(&matches) {
+            Ok(0) => {}
+            Ok(code) => println!("(exit code {})", code),
+            Err(e) => color::eprint_error(e.display_chain()),
+        }
+    }
+
+    if let Some(ref path) = history {
+        editor
+            .save_history(path)
+            .map_err(|e| Error::from(e.to_string()))?;
+    }
+    Ok(())
+}
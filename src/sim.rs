@@ -0,0 +1,690 @@
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use chrono::{self, Local, NaiveDateTime, Timelike};
+
+use ut181a::{
+    self, Measurement, MinMaxMeasurement, Mode, NormalMeasurement, PeakMeasurement, Range,
+    RecordDataItem, RecordInfo, RelativeMeasurement, Unit, UnitExp, Value,
+};
+
+use error::*;
+use meter::Meter;
+use ranges::{mode_ranges, range_index};
+
+/// Delay between two measurements in monitoring mode.
+const MEASUREMENT_PERIOD: u64 = 250; // ms
+
+/// Simulated input signal of measuring mode.
+struct Signal {
+    /// Nominal value in base units (V, A, Ohm, ...).
+    value: f64,
+    /// Relative noise amplitude.
+    noise: f64,
+    unit: Unit,
+    /// Main display value replacing input signal (value, unit, precision),
+    /// the input signal is shown in AUX1 then.
+    main: Option<(f64, UnitExp, usize)>,
+}
+
+impl Signal {
+    fn new(value: f64, noise: f64, unit: Unit) -> Signal {
+        Signal {
+            value,
+            noise,
+            unit,
+            main: None,
+        }
+    }
+
+    fn with_main(mut self, value: f64, unit: Unit, exponent: isize, precision: usize) -> Signal {
+        self.main = Some((value, UnitExp { unit, exponent }, precision));
+        self
+    }
+}
+
+fn signal(mode: Mode) -> Signal {
+    let vac = Signal::new(230.0, 0.002, Unit::VAC);
+    let dbv = 20.0 * 230.0f64.log10();
+    let dbm = 10.0 * (230.0f64 * 230.0 / 600.0 / 0.001).log10();
+    let hz = Signal::new(1000.0, 0.0005, Unit::Hz);
+    match mode {
+        Mode::VAC_Normal
+        | Mode::VAC_Normal_Rel
+        | Mode::VAC_Peak
+        | Mode::VAC_LowPass
+        | Mode::VAC_LowPass_Rel => vac,
+        Mode::VAC_Hz => vac.with_main(50.0, Unit::Hz, 0, 2),
+        Mode::VAC_dBV | Mode::VAC_dBV_Rel => vac.with_main(dbv, Unit::dBV, 0, 2),
+        Mode::VAC_dBm | Mode::VAC_dBm_Rel => vac.with_main(dbm, Unit::dBm, 0, 2),
+
+        Mode::mVAC_Normal | Mode::mVAC_Normal_Rel | Mode::mVAC_Peak => {
+            Signal::new(0.0125, 0.01, Unit::VAC)
+        }
+        Mode::mVAC_Hz => Signal::new(0.0125, 0.01, Unit::VAC).with_main(50.0, Unit::Hz, 0, 2),
+        Mode::mVAC_AC_DC | Mode::mVAC_AC_DC_Rel => Signal::new(0.0125, 0.01, Unit::VAcDc),
+
+        Mode::VDC_Normal | Mode::VDC_Normal_Rel | Mode::VDC_Peak => {
+            Signal::new(5.0, 0.0005, Unit::VDC)
+        }
+        Mode::VDC_AC_DC | Mode::VDC_AC_DC_Rel => Signal::new(5.0, 0.0005, Unit::VAcDc),
+
+        Mode::mVDC_Normal | Mode::mVDC_Normal_Rel | Mode::mVDC_Peak => {
+            Signal::new(0.00174, 0.02, Unit::VDC)
+        }
+
+        Mode::TempC_T1_T2 | Mode::TempC_T1_T2_Rel | Mode::TempC_T2_T1 | Mode::TempC_T2_T1_Rel => {
+            Signal::new(23.5, 0.002, Unit::Celsius)
+        }
+        Mode::TempC_T1_T2_Diff | Mode::TempC_T2_T1_Diff => Signal::new(0.3, 0.1, Unit::Celsius),
+        Mode::TempF_T1_T2 | Mode::TempF_T1_T2_Rel | Mode::TempF_T2_T1 | Mode::TempF_T2_T1_Rel => {
+            Signal::new(74.3, 0.002, Unit::Fahrenheit)
+        }
+        Mode::TempF_T1_T2_Diff | Mode::TempF_T2_T1_Diff => Signal::new(0.5, 0.1, Unit::Fahrenheit),
+
+        Mode::Resistance | Mode::Resistance_Rel => Signal::new(4700.0, 0.0002, Unit::Ohm),
+        Mode::Beeper_Short | Mode::Beeper_Open => Signal::new(12.0, 0.005, Unit::Ohm),
+
+        Mode::Admittance | Mode::Admittance_Rel => Signal::new(12.5e-9, 0.002, Unit::S),
+
+        Mode::Diode_Normal | Mode::Diode_Alarm => Signal::new(0.62, 0.0005, Unit::VDC),
+
+        Mode::Capacitance | Mode::Capacitance_Rel => Signal::new(100e-9, 0.002, Unit::F),
+
+        Mode::Frequency | Mode::Frequency_Rel => hz,
+        Mode::DutyCycle | Mode::DutyCycle_Rel => hz.with_main(50.0, Unit::Percent, 0, 2),
+        Mode::PulseWidth | Mode::PulseWidth_Rel => hz.with_main(0.5, Unit::s, -3, 3),
+
+        Mode::uADC_Normal | Mode::uADC_Normal_Rel | Mode::uADC_Peak => {
+            Signal::new(150e-6, 0.002, Unit::ADC)
+        }
+        Mode::uADC_AC_DC | Mode::uADC_AC_DC_Rel => Signal::new(150e-6, 0.002, Unit::AAcDc),
+        Mode::uAAC_Normal | Mode::uAAC_Normal_Rel | Mode::uAAC_Peak => {
+            Signal::new(150e-6, 0.002, Unit::AAC)
+        }
+        Mode::uAAC_Hz => Signal::new(150e-6, 0.002, Unit::AAC).with_main(50.0, Unit::Hz, 0, 2),
+
+        Mode::mADC_Normal | Mode::mADC_Normal_Rel | Mode::mADC_Peak => {
+            Signal::new(25e-3, 0.002, Unit::ADC)
+        }
+        Mode::mADC_AC_DC | Mode::mADC_AC_DC_Rel => Signal::new(25e-3, 0.002, Unit::AAcDc),
+        Mode::mAAC_Normal | Mode::mAAC_Normal_Rel | Mode::mAAC_Peak => {
+            Signal::new(25e-3, 0.002, Unit::AAC)
+        }
+        Mode::mAAC_Hz => Signal::new(25e-3, 0.002, Unit::AAC).with_main(50.0, Unit::Hz, 0, 2),
+
+        Mode::ADC_Normal | Mode::ADC_Normal_Rel | Mode::ADC_Peak => {
+            Signal::new(1.5, 0.002, Unit::ADC)
+        }
+        Mode::ADC_AC_DC | Mode::ADC_AC_DC_Rel => Signal::new(1.5, 0.002, Unit::AAcDc),
+        Mode::AAC_Normal | Mode::AAC_Normal_Rel | Mode::AAC_Peak => {
+            Signal::new(1.5, 0.002, Unit::AAC)
+        }
+        Mode::AAC_Hz => Signal::new(1.5, 0.002, Unit::AAC).with_main(50.0, Unit::Hz, 0, 2),
+    }
+}
+
+fn is_relative(mode: Mode) -> bool {
+    matches!(
+        mode,
+        Mode::VAC_Normal_Rel
+            | Mode::VAC_LowPass_Rel
+            | Mode::VAC_dBV_Rel
+            | Mode::VAC_dBm_Rel
+            | Mode::mVAC_Normal_Rel
+            | Mode::mVAC_AC_DC_Rel
+            | Mode::VDC_Normal_Rel
+            | Mode::VDC_AC_DC_Rel
+            | Mode::mVDC_Normal_Rel
+            | Mode::TempC_T1_T2_Rel
+            | Mode::TempC_T2_T1_Rel
+            | Mode::TempF_T1_T2_Rel
+            | Mode::TempF_T2_T1_Rel
+            | Mode::Resistance_Rel
+            | Mode::Admittance_Rel
+            | Mode::Capacitance_Rel
+            | Mode::Frequency_Rel
+            | Mode::DutyCycle_Rel
+            | Mode::PulseWidth_Rel
+            | Mode::uADC_Normal_Rel
+            | Mode::uADC_AC_DC_Rel
+            | Mode::uAAC_Normal_Rel
+            | Mode::mADC_Normal_Rel
+            | Mode::mADC_AC_DC_Rel
+            | Mode::mAAC_Normal_Rel
+            | Mode::ADC_Normal_Rel
+            | Mode::ADC_AC_DC_Rel
+            | Mode::AAC_Normal_Rel
+    )
+}
+
+fn is_peak(mode: Mode) -> bool {
+    matches!(
+        mode,
+        Mode::VAC_Peak
+            | Mode::mVAC_Peak
+            | Mode::VDC_Peak
+            | Mode::mVDC_Peak
+            | Mode::uADC_Peak
+            | Mode::uAAC_Peak
+            | Mode::mADC_Peak
+            | Mode::mAAC_Peak
+            | Mode::ADC_Peak
+            | Mode::AAC_Peak
+    )
+}
+
+/// Number of digits after decimal point for range `resolution` (0.0001 -> 4).
+fn precision(resolution: f64) -> usize {
+    (-resolution.log10()).round().max(0.0) as usize
+}
+
+fn dmm_error(kind: ut181a::ErrorKind) -> Error {
+    ut181a::Error::from_kind(kind).into()
+}
+
+fn now() -> NaiveDateTime {
+    let t = Local::now().naive_local();
+    t.with_nanosecond(0).unwrap_or(t)
+}
+
+/// Returns pseudo-random number in -1.0...1.0 range for `seed` (SplitMix64).
+fn noise_at(seed: u64) -> f64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+}
+
+fn set_holded(m: &mut Measurement, holded: bool) {
+    match *m {
+        Measurement::Normal(ref mut meas) => meas.is_holded = holded,
+        Measurement::Relative(ref mut meas) => meas.is_holded = holded,
+        Measurement::MinMax(ref mut meas) => meas.is_holded = holded,
+        Measurement::Peak(ref mut meas) => meas.is_holded = holded,
+    }
+}
+
+struct Reading {
+    main: Value,
+    aux1: Option<Value>,
+    fast: Value,
+    range: Range,
+}
+
+/// Reads simulated `mode` signal with `range`, `noise` is in -1.0...1.0 range.
+fn read_signal(mode: Mode, range: Range, noise: f64) -> Reading {
+    let sig = signal(mode);
+    let x = sig.value * (1.0 + sig.noise * noise);
+    let ranges = mode_ranges(mode);
+    let (fs, exp, prc, step) = if ranges.is_empty() {
+        // temperature has only auto range, it's displayed with 0.1 degree resolution
+        (f64::INFINITY, 0, 1, Range::Step1)
+    } else {
+        let last = ranges.len() - 1;
+        let i = if range == Range::Auto {
+            ranges
+                .iter()
+                .position(|info| x.abs() <= info.base_upper())
+                .unwrap_or(last)
+        } else {
+            range_index(mode, range).unwrap_or(last)
+        };
+        let info = ranges[i];
+        (
+            info.upper,
+            info.exponent,
+            precision(info.resolution),
+            info.step,
+        )
+    };
+    let scaled = x / 10f64.powi(exp as i32);
+    let overload = scaled.abs() > fs;
+    let input = Value {
+        overload_neg: overload && scaled < 0.0,
+        overload_pos: overload && scaled >= 0.0,
+        value: scaled as f32,
+        precision: Some(prc),
+        unit: UnitExp {
+            unit: sig.unit,
+            exponent: exp,
+        },
+    };
+    let fast = Value {
+        overload_neg: false,
+        overload_pos: false,
+        value: (scaled * (1.0 + sig.noise * noise * 3.0)) as f32,
+        precision: None,
+        unit: input.unit,
+    };
+    match sig.main {
+        Some((value, unit, prc)) => Reading {
+            main: Value {
+                overload_neg: false,
+                overload_pos: false,
+                value: (value * (1.0 + sig.noise * noise)) as f32,
+                precision: Some(prc),
+                unit,
+            },
+            aux1: Some(input),
+            fast,
+            range: step,
+        },
+        None => Reading {
+            main: input,
+            aux1: None,
+            fast,
+            range: step,
+        },
+    }
+}
+
+struct MinMaxState {
+    started: Instant,
+    max: Value,
+    max_time: Duration,
+    min: Value,
+    min_time: Duration,
+    sum: f64,
+    count: u32,
+}
+
+struct SimRecord {
+    name: String,
+    mode: Mode,
+    interval: u16,
+    /// Maximum sample count (limited by recording duration).
+    max_samples: u32,
+    start: NaiveDateTime,
+    started: Instant,
+    /// Sample count of finished recording.
+    samples: Option<u32>,
+    seed: u64,
+}
+
+impl SimRecord {
+    fn sample_count(&self) -> u32 {
+        match self.samples {
+            Some(n) => n,
+            None => {
+                let elapsed = self.started.elapsed().as_secs();
+                let n = elapsed / u64::from(self.interval) + 1;
+                n.min(u64::from(self.max_samples)) as u32
+            }
+        }
+    }
+
+    fn items(&self) -> Vec<RecordDataItem> {
+        (0..self.sample_count())
+            .map(|k| RecordDataItem {
+                value: read_signal(self.mode, Range::Auto, noise_at(self.seed ^ u64::from(k))).main,
+                timestamp: self.start
+                    + chrono::Duration::seconds(i64::from(k) * i64::from(self.interval)),
+            })
+            .collect()
+    }
+
+    fn info(&self) -> RecordInfo {
+        let items = self.items();
+        let unit = items
+            .first()
+            .map(|item| item.value.unit)
+            .unwrap_or_else(|| read_signal(self.mode, Range::Auto, 0.0).main.unit);
+        let mut max: Option<Value> = None;
+        let mut min: Option<Value> = None;
+        for item in &items {
+            if max.as_ref().is_none_or(|m| item.value.value > m.value) {
+                max = Some(item.value.clone());
+            }
+            if min.as_ref().is_none_or(|m| item.value.value < m.value) {
+                min = Some(item.value.clone());
+            }
+        }
+        let empty = Value {
+            overload_neg: false,
+            overload_pos: false,
+            value: 0.0,
+            precision: Some(0),
+            unit,
+        };
+        let max = max.unwrap_or_else(|| empty.clone());
+        let min = min.unwrap_or(empty);
+        let mut average = max.clone();
+        average.value = if items.is_empty() {
+            0.0
+        } else {
+            items.iter().map(|item| item.value.value).sum::<f32>() / items.len() as f32
+        };
+        let count = self.sample_count();
+        RecordInfo {
+            name: self.name.clone(),
+            unit,
+            interval: Duration::from_secs(u64::from(self.interval)),
+            duration: Duration::from_secs(u64::from(count) * u64::from(self.interval)),
+            sample_count: count,
+            max,
+            average,
+            min,
+            start: self.start,
+        }
+    }
+}
+
+/// Simulated DMM.
+///
+/// It keeps its state (mode, range, saves, records) in memory,
+/// so it's reset every time the CLI is started.
+pub(crate) struct SimulatedDmm {
+    mode: Mode,
+    range: Range,
+    reference: f32,
+    monitor: bool,
+    held: Option<Measurement>,
+    min_max: Option<MinMaxState>,
+    saves: Vec<(NaiveDateTime, Measurement)>,
+    records: Vec<SimRecord>,
+    seed: u64,
+}
+
+impl SimulatedDmm {
+    pub(crate) fn new() -> SimulatedDmm {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() ^ u64::from(d.subsec_nanos()))
+            .unwrap_or(0);
+        let mut dmm = SimulatedDmm {
+            mode: Mode::VDC_Normal,
+            range: Range::Auto,
+            reference: 0.0,
+            monitor: false,
+            held: None,
+            min_max: None,
+            saves: Vec::new(),
+            records: Vec::new(),
+            seed,
+        };
+
+        // Demo content of DMM memory.
+        let today = now().date();
+        let start = today.and_hms_opt(8, 0, 0).unwrap_or_else(now);
+        for i in 0..2 {
+            let m = dmm.read();
+            dmm.saves
+                .push((start + chrono::Duration::minutes(i * 10), m));
+        }
+        dmm.records.push(SimRecord {
+            name: "DEMO".into(),
+            mode: Mode::VDC_Normal,
+            interval: 10,
+            max_samples: 60,
+            start,
+            started: Instant::now(),
+            samples: Some(60),
+            seed: seed.wrapping_add(1),
+        });
+        dmm
+    }
+
+    fn next_noise(&mut self) -> f64 {
+        self.seed = self.seed.wrapping_add(1);
+        noise_at(self.seed)
+    }
+
+    fn is_recording(&self) -> bool {
+        self.records
+            .last()
+            .is_some_and(|r| r.samples.is_none() && r.sample_count() < r.max_samples)
+    }
+
+    /// Takes new simulated measurement.
+    fn read(&mut self) -> Measurement {
+        if let Some(ref held) = self.held {
+            return held.clone();
+        }
+
+        let noise = self.next_noise();
+        let r = read_signal(self.mode, self.range, noise);
+        let mode = self.mode;
+        let is_auto_range = self.range == Range::Auto && mode_ranges(mode).len() > 1;
+
+        if is_relative(mode) {
+            let reference = Value {
+                overload_neg: false,
+                overload_pos: false,
+                value: self.reference,
+                precision: r.main.precision,
+                unit: r.main.unit,
+            };
+            let mut relative = r.main.clone();
+            relative.value = r.main.value - self.reference;
+            return Measurement::Relative(RelativeMeasurement {
+                mode,
+                is_holded: false,
+                is_auto_range,
+                range: r.range,
+                relative,
+                reference,
+                measurement: r.main,
+                fast: Some(r.fast),
+            });
+        }
+
+        if is_peak(mode) {
+            let mut max = r.main.clone();
+            max.value *= ::std::f32::consts::SQRT_2;
+            let mut min = r.main.clone();
+            min.value *= -::std::f32::consts::SQRT_2;
+            return Measurement::Peak(PeakMeasurement {
+                mode,
+                is_holded: false,
+                is_auto_range,
+                range: r.range,
+                max,
+                min,
+            });
+        }
+
+        if let Some(ref mut mm) = self.min_max {
+            let elapsed = mm.started.elapsed();
+            if r.main.value > mm.max.value {
+                mm.max = r.main.clone();
+                mm.max_time = elapsed;
+            }
+            if r.main.value < mm.min.value {
+                mm.min = r.main.clone();
+                mm.min_time = elapsed;
+            }
+            mm.sum += f64::from(r.main.value);
+            mm.count += 1;
+            let mut average = r.main.clone();
+            average.value = (mm.sum / f64::from(mm.count)) as f32;
+            return Measurement::MinMax(MinMaxMeasurement {
+                mode,
+                is_holded: false,
+                is_auto_range,
+                range: r.range,
+                main: r.main,
+                max: mm.max.clone(),
+                max_time: mm.max_time,
+                average,
+                average_time: elapsed,
+                min: mm.min.clone(),
+                min_time: mm.min_time,
+            });
+        }
+
+        Measurement::Normal(NormalMeasurement {
+            mode,
+            is_holded: false,
+            is_auto_range,
+            range: r.range,
+            main: r.main,
+            aux1: r.aux1,
+            aux2: None,
+            fast: Some(r.fast),
+        })
+    }
+}
+
+impl Meter for SimulatedDmm {
+    fn toggle_hold(&mut self) -> Result<()> {
+        if self.held.is_some() {
+            self.held = None;
+        } else {
+            let mut m = self.read();
+            set_holded(&mut m, true);
+            self.held = Some(m);
+        }
+        Ok(())
+    }
+
+    fn save_measurement(&mut self) -> Result<()> {
+        let m = self.read();
+        self.saves.push((now(), m));
+        Ok(())
+    }
+
+    fn get_saved_measurement_count(&mut self) -> Result<u16> {
+        Ok(self.saves.len() as u16)
+    }
+
+    fn get_saved_measurement(&mut self, n: u16) -> Result<(NaiveDateTime, Measurement)> {
+        if n < 1 || usize::from(n) > self.saves.len() {
+            return Err(dmm_error(ut181a::ErrorKind::OutOfRange));
+        }
+        Ok(self.saves[usize::from(n) - 1].clone())
+    }
+
+    fn delete_saved_measurement(&mut self, index: u16) -> Result<()> {
+        if index < 1 || usize::from(index) > self.saves.len() {
+            return Err(dmm_error(ut181a::ErrorKind::OutOfRange));
+        }
+        self.saves.remove(usize::from(index) - 1);
+        Ok(())
+    }
+
+    fn delete_all_saved_measurement(&mut self) -> Result<()> {
+        self.saves.clear();
+        Ok(())
+    }
+
+    fn set_min_max_mode(&mut self, on: bool) -> Result<()> {
+        self.min_max = None;
+        if on {
+            let r = read_signal(self.mode, self.range, 0.0);
+            self.min_max = Some(MinMaxState {
+                started: Instant::now(),
+                max: r.main.clone(),
+                max_time: Duration::from_secs(0),
+                min: r.main,
+                min_time: Duration::from_secs(0),
+                sum: 0.0,
+                count: 0,
+            });
+        }
+        Ok(())
+    }
+
+    fn set_range(&mut self, range: Range) -> Result<()> {
+        if range != Range::Auto && range_index(self.mode, range).is_none() {
+            return Err(dmm_error(ut181a::ErrorKind::CommandError));
+        }
+        self.range = range;
+        self.held = None;
+        Ok(())
+    }
+
+    fn set_reference_value(&mut self, val: f32) -> Result<()> {
+        self.reference = val;
+        Ok(())
+    }
+
+    fn set_mode(&mut self, mode: Mode) -> Result<()> {
+        self.mode = mode;
+        self.range = Range::Auto;
+        self.held = None;
+        self.min_max = None;
+        Ok(())
+    }
+
+    fn get_record_count(&mut self) -> Result<u16> {
+        Ok(self.records.len() as u16)
+    }
+
+    fn get_record_info(&mut self, i: u16) -> Result<RecordInfo> {
+        if i < 1 || usize::from(i) > self.records.len() {
+            return Err(dmm_error(ut181a::ErrorKind::OutOfRange));
+        }
+        Ok(self.records[usize::from(i) - 1].info())
+    }
+
+    fn get_record_data(&mut self, i: u16) -> Result<Vec<RecordDataItem>> {
+        if i < 1 || usize::from(i) > self.records.len() {
+            return Err(dmm_error(ut181a::ErrorKind::OutOfRange));
+        }
+        Ok(self.records[usize::from(i) - 1].items())
+    }
+
+    fn start_record(&mut self, name: &str, interval: u16, duration: u32) -> Result<()> {
+        if name.chars().any(|c| !(c.is_ascii_graphic() || c == ' ')) {
+            return Err(dmm_error(ut181a::ErrorKind::InvalidRecordName(name.into())));
+        }
+        if name.len() > 10 {
+            return Err(dmm_error(ut181a::ErrorKind::RecordNameTooLong(name.into())));
+        }
+        if !(1..=3600).contains(&interval) {
+            return Err(dmm_error(ut181a::ErrorKind::RecordIntervalIsOutOfRange(
+                interval,
+            )));
+        }
+        if !(1..=143_999).contains(&duration) {
+            return Err(dmm_error(ut181a::ErrorKind::RecordDurationIsOutOfRange(
+                duration,
+            )));
+        }
+        if self.is_recording() {
+            return Err(dmm_error(ut181a::ErrorKind::CommandError));
+        }
+        let seed = self.seed.wrapping_add(u64::from(interval));
+        self.records.push(SimRecord {
+            name: name.into(),
+            mode: self.mode,
+            interval,
+            max_samples: duration * 60 / u32::from(interval) + 1,
+            start: now(),
+            started: Instant::now(),
+            samples: None,
+            seed,
+        });
+        Ok(())
+    }
+
+    fn stop_record(&mut self) -> Result<()> {
+        if !self.is_recording() {
+            return Err(dmm_error(ut181a::ErrorKind::CommandError));
+        }
+        if let Some(r) = self.records.last_mut() {
+            r.samples = Some(r.sample_count());
+        }
+        Ok(())
+    }
+
+    fn monitor_on(&mut self) -> Result<()> {
+        self.monitor = true;
+        Ok(())
+    }
+
+    fn monitor_off(&mut self) -> Result<()> {
+        self.monitor = false;
+        Ok(())
+    }
+
+    fn get_measurement(&mut self) -> Result<Measurement> {
+        if !self.monitor {
+            return Err(dmm_error(ut181a::ErrorKind::WaitTimeout));
+        }
+        thread::sleep(Duration::from_millis(MEASUREMENT_PERIOD));
+        Ok(self.read())
+    }
+}
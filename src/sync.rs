@@ -0,0 +1,164 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use serde_json::{self, Value as Json};
+
+use ut181a::RecordInfo;
+
+use csv::write_record_csv;
+use db::Db;
+use error::*;
+use json::{format_timestamp, record_info_json, saved_measurement_json};
+use meter::Meter;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const SAVES_FILE: &str = "saves.json";
+const RECORDS_DIR: &str = "records";
+
+/// Record identity: name, start time and sample count.
+fn same_record(entry: &Json, info: &RecordInfo) -> bool {
+    entry["name"].as_str() == Some(info.name.as_str())
+        && entry["start"].as_str() == Some(format_timestamp(&info.start).as_str())
+        && entry["sample_count"].as_u64() == Some(u64::from(info.sample_count))
+}
+
+/// `START-NAME-SAMPLES.csv`, unique for manifest key of record.
+fn record_file_name(info: &RecordInfo) -> String {
+    let name: String = info
+        .name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!(
+        "{}-{}-{}.csv",
+        info.start.format("%Y%m%dT%H%M%S"),
+        name,
+        info.sample_count
+    )
+}
+
+fn read_manifest(path: &Path) -> Result<Vec<Json>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let manifest: Json = serde_json::from_reader(File::open(path)?)?;
+    match manifest["records"] {
+        Json::Array(ref records) => Ok(records.clone()),
+        _ => Err(format!("invalid manifest file '{}'", path.display()).into()),
+    }
+}
+
+fn write_json(path: &Path, value: &Json) -> Result<()> {
+    let mut file = File::create(path)?;
+    serde_json::to_writer_pretty(&mut file, value)?;
+    writeln!(file)?;
+    Ok(())
+}
+
+fn write_manifest(path: &Path, records: &[Json]) -> Result<()> {
+    let mut manifest = serde_json::Map::new();
+    manifest.insert("records".into(), Json::Array(records.to_vec()));
+    write_json(path, &Json::Object(manifest))
+}
+
+/// Downloads all records and saved measurements into `dir` and/or `db`.
+///
+/// Records already listed in the manifest (or imported into database) are skipped.
+pub(crate) fn sync(
+    dmm: &mut dyn Meter,
+    dir: Option<&Path>,
+    mut db: Option<&mut Db>,
+    verbose: bool,
+) -> Result<()> {
+    let mut records = match dir {
+        Some(dir) => {
+            fs::create_dir_all(dir.join(RECORDS_DIR))?;
+            read_manifest(&dir.join(MANIFEST_FILE))?
+        }
+        None => Vec::new(),
+    };
+
+    if verbose {
+        diagln!("Sending 'GET RECORD COUNT' command to DMM.");
+    }
+    let count = dmm.get_record_count()?;
+    let mut fetched = 0;
+    for i in 1..(count + 1) {
+        if verbose {
+            diagln!("Sending 'GET RECORD INFO #{}' command to DMM.", i);
+        }
+        let info = dmm.get_record_info(i)?;
+        let in_dir = dir.is_none() || records.iter().any(|entry| same_record(entry, &info));
+        let in_db = match db {
+            Some(ref db) => db.has_record(&info)?,
+            None => true,
+        };
+        if in_dir && in_db {
+            continue;
+        }
+
+        if verbose {
+            diagln!("Sending 'GET RECORD DATA #{}' command to DMM.", i);
+        }
+        let items = dmm.get_record_data(i)?;
+        if let (Some(dir), false) = (dir, in_dir) {
+            let file_name = record_file_name(&info);
+            let mut file = File::create(dir.join(RECORDS_DIR).join(&file_name))?;
+            write_record_csv(&mut file, &info, &items, false)?;
+
+            let mut entry = record_info_json(i, &info);
+            if let Json::Object(ref mut obj) = entry {
+                obj.remove("index");
+                obj.insert(
+                    "file".into(),
+                    Json::from(format!("{}/{}", RECORDS_DIR, file_name)),
+                );
+            }
+            records.push(entry);
+            write_manifest(&dir.join(MANIFEST_FILE), &records)?;
+            println!("Downloaded record '{}' to '{}'.", info.name, file_name);
+        }
+        if let (Some(ref mut db), false) = (db.as_mut(), in_db) {
+            db.insert_record(&info, &items)?;
+            println!("Imported record '{}' into database.", info.name);
+        }
+        fetched += 1;
+    }
+    if let Some(dir) = dir {
+        write_manifest(&dir.join(MANIFEST_FILE), &records)?;
+    }
+
+    if verbose {
+        diagln!("Sending 'GET SAVE COUNT' command to DMM.");
+    }
+    let save_count = dmm.get_saved_measurement_count()?;
+    let mut saves = Vec::with_capacity(usize::from(save_count));
+    for i in 1..(save_count + 1) {
+        if verbose {
+            diagln!("Sending 'READ SAVE AT {}' command to DMM.", i);
+        }
+        let save = dmm.get_saved_measurement(i)?;
+        if let Some(ref mut db) = db {
+            db.insert_save(&save.0, &save.1)?;
+        }
+        saves.push(saved_measurement_json(i, &save.0, &save.1));
+    }
+    if let Some(dir) = dir {
+        write_json(&dir.join(SAVES_FILE), &Json::Array(saves))?;
+    }
+
+    println!(
+        "New records: {}, already present: {}, saved measurements: {}.",
+        fetched,
+        usize::from(count) - fetched,
+        save_count
+    );
+    Ok(())
+}
@@ -0,0 +1,373 @@
+use std::collections::VecDeque;
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use ut181a::{Measurement, Range, Value};
+
+use display_measurement::{format_duration, format_number};
+use error::*;
+use json::range_name;
+use measurement::{
+    base_unit, base_value, fast_value, is_auto_range, is_holded, is_overload, main_value, mode,
+    range,
+};
+use meter::Meter;
+use quantity::format_quantity;
+
+const HELP: &str =
+    "q quit  h hold  m min/max on  n min/max off  s save  +/- range  a auto range  r reset";
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+const RANGE_STEPS: [Range; 8] = [
+    Range::Step1,
+    Range::Step2,
+    Range::Step3,
+    Range::Step4,
+    Range::Step5,
+    Range::Step6,
+    Range::Step7,
+    Range::Step8,
+];
+
+/// Big 5-row glyphs for main reading (`#` is a filled cell).
+fn glyph(c: char) -> [&'static str; 5] {
+    match c {
+        '0' | 'O' => ["###", "# #", "# #", "# #", "###"],
+        '1' => ["  #", "  #", "  #", "  #", "  #"],
+        '2' => ["###", "  #", "###", "#  ", "###"],
+        '3' => ["###", "  #", "###", "  #", "###"],
+        '4' => ["# #", "# #", "###", "  #", "  #"],
+        '5' => ["###", "#  ", "###", "  #", "###"],
+        '6' => ["###", "#  ", "###", "# #", "###"],
+        '7' => ["###", "  #", "  #", "  #", "  #"],
+        '8' => ["###", "# #", "###", "# #", "###"],
+        '9' => ["###", "# #", "###", "  #", "###"],
+        'L' => ["#  ", "#  ", "#  ", "#  ", "###"],
+        '-' => ["   ", "   ", "###", "   ", "   "],
+        '.' => [" ", " ", " ", " ", "#"],
+        _ => ["   ", "   ", "   ", "   ", "   "],
+    }
+}
+
+fn big_text(s: &str) -> Vec<String> {
+    (0..5)
+        .map(|row| {
+            s.chars()
+                .map(|c| glyph(c)[row].replace('#', "█"))
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+fn display_number(v: &Value) -> String {
+    if v.overload_neg {
+        "-OL".to_owned()
+    } else if v.overload_pos {
+        "OL".to_owned()
+    } else {
+        format_number(v)
+    }
+}
+
+/// Values shown below the main reading.
+fn secondary_values(m: &Measurement) -> Vec<String> {
+    let mut values = Vec::new();
+    match *m {
+        Measurement::Normal(ref meas) => {
+            if let Some(ref v) = meas.aux1 {
+                values.push(format!("AUX1: {}", v));
+            }
+            if let Some(ref v) = meas.aux2 {
+                values.push(format!("AUX2: {}", v));
+            }
+        }
+        Measurement::Relative(ref meas) => {
+            values.push(format!("REFERENCE: {}", meas.reference));
+            values.push(format!("MEASUREMENT: {}", meas.measurement));
+        }
+        Measurement::MinMax(ref meas) => {
+            values.push(format!(
+                "MAX: {} {}",
+                meas.max,
+                format_duration(meas.max_time)
+            ));
+            values.push(format!(
+                "AVG: {} {}",
+                meas.average,
+                format_duration(meas.average_time)
+            ));
+            values.push(format!(
+                "MIN: {} {}",
+                meas.min,
+                format_duration(meas.min_time)
+            ));
+        }
+        Measurement::Peak(ref meas) => {
+            values.push(format!("PEAK MIN: {}", meas.min));
+        }
+    }
+    if let Some(v) = fast_value(m) {
+        values.push(format!("FAST: {}", v));
+    }
+    values
+}
+
+/// Running statistics and history of main reading (in base units).
+struct Stats {
+    mode: String,
+    unit: String,
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+    history: VecDeque<f64>,
+}
+
+impl Stats {
+    fn new() -> Stats {
+        Stats {
+            mode: String::new(),
+            unit: String::new(),
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sum: 0.0,
+            count: 0,
+            history: VecDeque::new(),
+        }
+    }
+
+    fn add(&mut self, m: &Measurement, capacity: usize) {
+        let v = main_value(m);
+        let mode = mode(m).to_string();
+        if mode != self.mode || base_unit(v) != self.unit {
+            *self = Stats::new();
+            self.mode = mode;
+            self.unit = base_unit(v);
+        }
+        if is_overload(v) {
+            return;
+        }
+        let x = base_value(v);
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        self.sum += x;
+        self.count += 1;
+        self.history.push_back(x);
+        while self.history.len() > capacity {
+            self.history.pop_front();
+        }
+    }
+
+    fn summary(&self) -> String {
+        if self.count == 0 {
+            return "MIN: -  AVG: -  MAX: -".to_owned();
+        }
+        format!(
+            "MIN: {}  AVG: {}  MAX: {}  ({} readings)",
+            format_quantity(self.min, &self.unit),
+            format_quantity(self.sum / self.count as f64, &self.unit),
+            format_quantity(self.max, &self.unit),
+            self.count
+        )
+    }
+
+    fn sparkline(&self) -> String {
+        let lo = self.history.iter().cloned().fold(f64::INFINITY, f64::min);
+        let hi = self
+            .history
+            .iter()
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max);
+        self.history
+            .iter()
+            .map(|&x| {
+                let level = if hi > lo {
+                    ((x - lo) / (hi - lo) * (SPARKS.len() - 1) as f64).round() as usize
+                } else {
+                    0
+                };
+                SPARKS[level.min(SPARKS.len() - 1)]
+            })
+            .collect()
+    }
+}
+
+/// Restores terminal on drop (also on error).
+struct Screen {
+    out: Stdout,
+}
+
+impl Screen {
+    fn open() -> Result<Screen> {
+        let mut out = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(out, EnterAlternateScreen, Hide)?;
+        Ok(Screen { out })
+    }
+
+    fn draw(&mut self, m: &Measurement, stats: &Stats, status: &str) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let width = usize::from(width);
+        let v = main_value(m);
+        let mut lines = vec![
+            format!(
+                "Mode: {}  Range: {}  {}  {}",
+                mode(m),
+                range_name(range(m)),
+                if is_holded(m) { "HOLD" } else { "    " },
+                if is_auto_range(m) { "AUTO" } else { "    " }
+            ),
+            String::new(),
+        ];
+        let big = big_text(&display_number(v));
+        for (row, text) in big.into_iter().enumerate() {
+            if row == 4 {
+                lines.push(format!("{}  {}", text, v.unit));
+            } else {
+                lines.push(text);
+            }
+        }
+        lines.push(String::new());
+        lines.push(secondary_values(m).join("  "));
+        lines.push(stats.summary());
+        lines.push(String::new());
+        lines.push(stats.sparkline());
+
+        // Lines are overwritten in place to avoid flicker.
+        lines.resize(usize::from(height.saturating_sub(2)), String::new());
+        lines.push(status.to_owned());
+        lines.push(HELP.to_owned());
+        for (row, line) in lines.iter().enumerate() {
+            let line: String = line.chars().take(width).collect();
+            queue!(
+                self.out,
+                MoveTo(0, row as u16),
+                Print(line),
+                Clear(ClearType::UntilNewLine)
+            )?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+enum Command {
+    Quit,
+    Hold,
+    MinMax(bool),
+    Save,
+    RangeUp,
+    RangeDown,
+    AutoRange,
+    Reset,
+}
+
+fn read_command() -> Result<Option<Command>> {
+    while event::poll(Duration::from_millis(0))? {
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let cmd = match key.code {
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    Command::Quit
+                }
+                KeyCode::Char('q') | KeyCode::Esc => Command::Quit,
+                KeyCode::Char('h') => Command::Hold,
+                KeyCode::Char('m') => Command::MinMax(true),
+                KeyCode::Char('n') => Command::MinMax(false),
+                KeyCode::Char('s') => Command::Save,
+                KeyCode::Char('+') | KeyCode::Up => Command::RangeUp,
+                KeyCode::Char('-') | KeyCode::Down => Command::RangeDown,
+                KeyCode::Char('a') => Command::AutoRange,
+                KeyCode::Char('r') => Command::Reset,
+                _ => continue,
+            };
+            return Ok(Some(cmd));
+        }
+    }
+    Ok(None)
+}
+
+/// Range step next to the current one (`None` if there is no such step).
+fn next_range(current: Range, up: bool) -> Option<Range> {
+    let i = RANGE_STEPS.iter().position(|&r| r == current).unwrap_or(0);
+    if up {
+        RANGE_STEPS.get(i + 1).cloned()
+    } else {
+        i.checked_sub(1).map(|i| RANGE_STEPS[i])
+    }
+}
+
+/// Sends command to DMM, monitoring is paused meanwhile.
+fn execute_command(dmm: &mut dyn Meter, cmd: &Command, m: &Measurement) -> Result<String> {
+    dmm.monitor_off()?;
+    let status = match *cmd {
+        Command::Hold => dmm.toggle_hold().map(|_| "Hold is toggled.".to_owned()),
+        Command::MinMax(on) => dmm
+            .set_min_max_mode(on)
+            .map(|_| format!("Min/Max mode is {}.", if on { "on" } else { "off" })),
+        Command::Save => dmm
+            .save_measurement()
+            .map(|_| "Measurement is saved.".to_owned()),
+        Command::RangeUp | Command::RangeDown => {
+            match next_range(range(m), matches!(*cmd, Command::RangeUp)) {
+                Some(r) => dmm
+                    .set_range(r)
+                    .map(|_| format!("Range is set to {}.", range_name(r))),
+                None => Ok("No more ranges.".to_owned()),
+            }
+        }
+        Command::AutoRange => dmm
+            .set_range(Range::Auto)
+            .map(|_| "Auto range is on.".to_owned()),
+        Command::Quit | Command::Reset => Ok(String::new()),
+    };
+    dmm.monitor_on()?;
+    Ok(status.unwrap_or_else(|e| format!("Error: {}", e)))
+}
+
+/// Full-screen dashboard of live readings.
+pub(crate) fn watch(dmm: &mut dyn Meter) -> Result<()> {
+    dmm.monitor_on()?;
+    let result = watch_loop(dmm);
+    dmm.monitor_off()?;
+    result
+}
+
+fn watch_loop(dmm: &mut dyn Meter) -> Result<()> {
+    let mut screen = Screen::open()?;
+    let mut stats = Stats::new();
+    let mut status = String::new();
+    loop {
+        let m = dmm.get_measurement()?;
+        let width = usize::from(terminal::size()?.0);
+        stats.add(&m, width);
+        if let Some(cmd) = read_command()? {
+            match cmd {
+                Command::Quit => return Ok(()),
+                Command::Reset => {
+                    stats = Stats::new();
+                    status = "Statistics are reset.".to_owned();
+                }
+                _ => status = execute_command(dmm, &cmd, &m)?,
+            }
+        }
+        screen.draw(&m, &stats, &status)?;
+    }
+}
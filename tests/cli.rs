@@ -0,0 +1,146 @@
+//! Runs `ut181a-cli --simulate` commands end-to-end.
+
+extern crate serde_json;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use serde_json::Value as Json;
+
+/// Empty directory unique for test, used as config, state and output directory.
+fn temp_dir(test: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("ut181a-cli-test-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs binary against simulated DMM, user config and daemon are isolated in `dir`.
+fn run(dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ut181a-cli"))
+        .arg("--simulate")
+        .args(args)
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("XDG_STATE_HOME", dir)
+        .env("XDG_RUNTIME_DIR", dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+/// Stdout of successful command.
+fn stdout(dir: &PathBuf, args: &[&str]) -> String {
+    let output = run(dir, args);
+    assert!(
+        output.status.success(),
+        "{:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn check_record_csv(csv: &str) {
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        &lines[..6],
+        &[
+            "# name=DEMO",
+            "# start=2026-10-18T08:00:00",
+            "# interval=10",
+            "# unit=VDC",
+            "# sample_count=60",
+            "index,timestamp,value,unit,flag",
+        ]
+    );
+    assert_eq!(lines.len(), 6 + 60);
+    assert!(lines[6].starts_with("1,2026-10-18T08:00:00,"));
+    assert!(lines[65].starts_with("60,2026-10-18T08:09:50,"));
+}
+
+#[test]
+fn read_once_json() {
+    let dir = temp_dir("read");
+    let out = stdout(&dir, &["--format", "json", "read", "once"]);
+    let j: Json = serde_json::from_str(&out).unwrap();
+    assert_eq!(j["mode"], "VDC");
+    assert_eq!(j["main"]["unit"], "VDC");
+    let value = j["main"]["value"].as_f64().unwrap();
+    assert!((value - 5.0).abs() < 0.01, "{}", value);
+}
+
+#[test]
+fn record_list() {
+    let dir = temp_dir("record-list");
+    let out = stdout(&dir, &["record", "list"]);
+    assert!(out.contains("Name: DEMO"), "{}", out);
+    assert!(out.contains("Sample count: 60"), "{}", out);
+    assert!(out.contains("Total record count: 1"), "{}", out);
+}
+
+#[test]
+fn record_read_csv() {
+    let dir = temp_dir("record-read");
+    check_record_csv(&stdout(&dir, &["record", "read", "--csv", "1"]));
+}
+
+#[test]
+fn record_export() {
+    let dir = temp_dir("record-export");
+    check_record_csv(&stdout(&dir, &["record", "export", "1"]));
+}
+
+#[test]
+fn sync() {
+    let dir = temp_dir("sync");
+    let target = dir.join("sync");
+    let target_arg = target.to_str().unwrap();
+    let out = stdout(&dir, &["sync", target_arg]);
+    assert!(out.contains("New records: 1"), "{}", out);
+    let manifest: Json =
+        serde_json::from_str(&fs::read_to_string(target.join("manifest.json")).unwrap()).unwrap();
+    assert!(manifest.to_string().contains("20261018T080000-DEMO-60.csv"));
+    let csv = fs::read_to_string(target.join("records/20261018T080000-DEMO-60.csv")).unwrap();
+    check_record_csv(&csv);
+
+    // records are downloaded once
+    let out = stdout(&dir, &["sync", target_arg]);
+    assert!(
+        out.contains("New records: 0, already present: 1"),
+        "{}",
+        out
+    );
+}
+
+#[test]
+fn check_exit_code() {
+    let dir = temp_dir("check");
+    let pass = run(&dir, &["check", "vdc", "5V", "±2%"]);
+    assert_eq!(pass.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&pass.stdout).starts_with("PASS"));
+    let fail = run(&dir, &["check", "vdc", "1V", "±1%"]);
+    assert_eq!(fail.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&fail.stdout).starts_with("FAIL"));
+}
+
+#[test]
+fn run_script() {
+    let dir = temp_dir("run");
+    let script = dir.join("script.toml");
+    fs::write(
+        &script,
+        "[[steps]]\nmode = \"vdc\"\n\n\
+         [[steps]]\nname = \"5V rail\"\nread = { expect = \"5V\", tolerance = \"±2%\" }\n",
+    )
+    .unwrap();
+    let out = stdout(&dir, &["--format", "json", "run", script.to_str().unwrap()]);
+    let report: Json = serde_json::from_str(&out).unwrap();
+    assert_eq!(report["completed"], true);
+    assert_eq!(report["passed"], 1);
+    assert_eq!(report["failed"], 0);
+    assert_eq!(report["steps"][1]["name"], "5V rail");
+    assert_eq!(report["steps"][1]["status"], "pass");
+}